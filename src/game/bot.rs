@@ -0,0 +1,414 @@
+use bevy::prelude::*;
+use rand::Rng;
+use crate::AppState;
+use crate::game::buffers::{EventBuffer, PosBuffer};
+use crate::game::components::*;
+use crate::game::enemy::find_next;
+use crate::game::item::Inventory;
+use crate::game::map::{Biome, WorldMap, MAPSIZE, TILESIZE};
+use crate::game::mode::{GameMode, Hill, Teams};
+use crate::game::movement::correct_wall_collisions;
+use crate::game::player::{spawn_players, ATTACK_BITFLAG, DEFAULT_COOLDOWN, MAX_PLAYERS, PLAYER_DEFAULT_HP, PLAYER_SIZE, PLAYER_SPEED, SHIELD_BITFLAG, SHIELD_BLOCK_COST, SPAWN_BITFLAG, SWORD_LENGTH};
+use crate::game::powerup::PowerUpConfig;
+use crate::game::respawn::Respawn;
+use crate::game::round::Round;
+use crate::game::status::{StatusEffects, StatusType};
+use crate::net::{self, is_host, TICKLEN_S, TickNum};
+use crate::net::packets::{UserCmd, UserCmdEvent};
+
+const POWERUP_RANGE: f32 = 300.; // bots go out of their way for powerups this close
+const CHEST_RANGE: f32 = 400.;
+const ARRIVED: f32 = 8.; // close enough to a waypoint to move on to the next one
+const SHIELD_TICKS: u16 = 4; // how long a bot holds its shield up once it decides to block
+const CAMP_RADIUS: f32 = 96.; // wander this far around a camp while fighting it
+
+/// How well a bot plays, picked in the host's lobby before adding it
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum Difficulty {
+    Easy = 0,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn next(&self) -> Difficulty {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    /// ticks between picking a new goal and path
+    fn think_ticks(&self) -> u16 {
+        match self {
+            Difficulty::Easy => 10,
+            Difficulty::Normal => 5,
+            Difficulty::Hard => 2,
+        }
+    }
+
+    /// how far off a swing can be, in radians
+    fn aim_error(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.8,
+            Difficulty::Normal => 0.4,
+            Difficulty::Hard => 0.1,
+        }
+    }
+
+    /// chance to swing when the sword is ready and something is in reach
+    fn attack_chance(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.3,
+            Difficulty::Normal => 0.6,
+            Difficulty::Hard => 0.9,
+        }
+    }
+
+    /// chance to block when a player nearby swings
+    fn shield_chance(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.,
+            Difficulty::Normal => 0.3,
+            Difficulty::Hard => 0.7,
+        }
+    }
+
+    /// how far away a player has to be before the bot hunts them
+    fn aggro_range(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 250.,
+            Difficulty::Normal => 400.,
+            Difficulty::Hard => 600.,
+        }
+    }
+}
+
+/// Which player slots the host has filled with bots, kept across rematches
+#[derive(Resource, Default)]
+pub struct Bots(pub [Option<Difficulty>; MAX_PLAYERS]);
+
+impl Bots {
+    /// puts a bot in the first slot below the player cap nobody has taken, returns its player id
+    pub fn add(&mut self, difficulty: Difficulty, taken: &[bool; MAX_PLAYERS], max_players: u8) -> Option<u8> {
+        let cap = (max_players as usize).min(MAX_PLAYERS);
+        let id = (1..cap).find(|id| !taken[*id] && self.0[*id].is_none())?;
+        self.0[id] = Some(difficulty);
+        Some(id as u8)
+    }
+
+    /// takes out the bot in the highest slot, returns its player id
+    pub fn remove(&mut self) -> Option<u8> {
+        let id = (1..MAX_PLAYERS).rev().find(|id| self.0[*id].is_some())?;
+        self.0[id] = None;
+        Some(id as u8)
+    }
+
+    pub fn is_bot(&self, id: u8) -> bool {
+        self.0[id as usize].is_some()
+    }
+}
+
+/// The difficulty picked for the next bot added in the lobby
+#[derive(Resource, Default)]
+pub struct BotDifficulty(pub Difficulty);
+
+/// Drives a player entity on the host in place of a human, only the host has these
+#[derive(Component)]
+pub struct Bot {
+    pub difficulty: Difficulty,
+    pos: Option<Vec2>, // where the bot says it is, like a client's own transform
+    goal: Option<Vec2>,
+    waypoint: Option<Vec2>,
+    next_think: u16,
+    next_attack: u16,
+    shield_until: u16,
+}
+
+impl Bot {
+    pub fn new(difficulty: Difficulty) -> Bot {
+        Bot {
+            difficulty,
+            pos: None,
+            goal: None,
+            waypoint: None,
+            next_think: 0,
+            next_attack: 0,
+            shield_until: 0,
+        }
+    }
+}
+
+pub struct BotPlugin;
+
+impl Plugin for BotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Game), spawn_bots.after(spawn_players).run_if(is_host))
+            .add_systems(FixedUpdate, bot_simulate
+                .run_if(in_state(AppState::Game)).run_if(is_host).before(net::host::fixed))
+            .add_systems(OnEnter(AppState::MainMenu), reset_bots)
+            .init_resource::<Bots>()
+            .init_resource::<BotDifficulty>();
+    }
+}
+
+pub fn reset_bots(mut bots: ResMut<Bots>) {
+    *bots = Bots::default();
+}
+
+/// hands the bot slots over to bot controllers
+pub fn spawn_bots(
+    mut commands: Commands,
+    bots: Res<Bots>,
+    players: Query<(Entity, &Player)>,
+) {
+    for (e, pl) in &players {
+        if let Some(difficulty) = bots.0[pl.0 as usize] {
+            commands.entity(e).insert(Bot::new(difficulty));
+        }
+    }
+}
+
+/// a random open tile, the same way a player picks one off the respawn map
+fn random_spawn(map: &WorldMap) -> Vec2 {
+    let mut rng = rand::thread_rng();
+    loop {
+        let col = rng.gen_range(0..MAPSIZE);
+        let row = rng.gen_range(0..MAPSIZE);
+        if map.biome_map[row][col] == Biome::Wall { continue }
+        return Vec2::new(
+            (col as f32 - (MAPSIZE / 2) as f32) * TILESIZE as f32,
+            -(row as f32 - (MAPSIZE / 2) as f32) * TILESIZE as f32,
+        );
+    }
+}
+
+/// thinks for every bot and sends its input for the next tick through the same
+/// UserCmdEvent path a client's ClientTick takes, so the host applies the same rules
+pub fn bot_simulate(
+    tick: Res<TickNum>,
+    mut bots: Query<(&Player, &mut Bot, &Health, &Respawn, &Stats, &StoredPowerUps, &StatusEffects, &Stamina, &Inventory)>,
+    players: Query<(&Player, &PosBuffer, &Health, &EventBuffer, &Respawn)>,
+    enemies: Query<(&PosBuffer, &Health), With<Enemy>>,
+    camps: Query<(&Transform, &CampEnemies, Option<&Hill>), With<Camp>>,
+    chests: Query<(&Transform, &Health), With<ItemChest>>,
+    powerups: Query<(&Transform, &PowerUp)>,
+    map: Res<WorldMap>,
+    game_mode: Res<GameMode>,
+    teams: Res<Teams>,
+    round: Res<Round>,
+    powerup_config: Res<PowerUpConfig>,
+    mut usercmd_writer: EventWriter<UserCmdEvent>,
+) {
+    let mut rng = rand::thread_rng();
+    for (pl, mut bot, hp, respawn, stats, spu, effects, stamina, inv) in &mut bots {
+        let mut events: u8 = 0;
+        let mut dir: f32 = 0.;
+
+        if hp.dead {
+            bot.goal = None;
+            bot.waypoint = None;
+            if respawn.ready() && game_mode.can_respawn(stats) {
+                bot.pos = Some(random_spawn(&map));
+                events |= SPAWN_BITFLAG;
+            }
+            send(&mut usercmd_writer, tick.0, pl.0, bot.pos.unwrap_or(Vec2::ZERO), dir, events);
+            continue;
+        }
+        let Some(mut pos) = bot.pos else { continue };
+
+        // living opponents the bot is allowed to hurt right now
+        let rivals: Vec<(Vec2, u8, bool)> = players.iter()
+            .filter(|(other, _, other_hp, _, other_respawn)| other.0 != pl.0 && !other_hp.dead && !other_respawn.protected())
+            .filter(|(other, _, _, _, _)| round.phase.can_fight() && game_mode.can_damage(&teams, pl.0, other.0))
+            .filter_map(|(_, pb, other_hp, eb, _)| pb.0.get(tick.0.wrapping_sub(1)).map(|pos|
+                (pos, other_hp.current, eb.0.get(tick.0.wrapping_sub(1)).unwrap_or(0) & ATTACK_BITFLAG != 0)))
+            .collect();
+
+        if tick.0 >= bot.next_think {
+            bot.next_think = tick.0 + bot.difficulty.think_ticks();
+            bot.goal = pick_goal(&bot, pos, &rivals, &enemies, &camps, &chests, &powerups, spu, &powerup_config, &game_mode, tick.0, &mut rng);
+            bot.waypoint = bot.goal.map(|goal| find_next(&map.biome_map, pos, goal));
+        }
+
+        // walk the path, holding still while stunned or blocking
+        let stunned = effects.active(StatusType::Stun);
+        let blocking = tick.0 < bot.shield_until;
+        if let (Some(waypoint), false, false) = (bot.waypoint, stunned, blocking) {
+            let speed = PLAYER_SPEED * TICKLEN_S * powerup_config.move_speed_mult(spu) * effects.speed_mult() * inv.speed_mult();
+            let to = waypoint - pos;
+            if to.length() <= speed.max(ARRIVED) {
+                pos = waypoint;
+                bot.waypoint = bot.goal.filter(|goal| goal.distance(pos) > ARRIVED).map(|goal| find_next(&map.biome_map, pos, goal));
+            } else {
+                pos += to.normalize() * speed;
+            }
+            pos = correct_wall_collisions(&pos.extend(0.), &PLAYER_SIZE, &map.biome_map).truncate();
+            if pos.is_finite() { bot.pos = Some(pos) }
+        }
+
+        // block a swing from someone in reach
+        let threatened = rivals.iter().any(|(rival, _, attacking)| *attacking && rival.distance(pos) < SWORD_LENGTH + PLAYER_SIZE.x);
+        if !stunned && threatened && !blocking && stamina.current > SHIELD_BLOCK_COST && rng.gen::<f32>() < bot.difficulty.shield_chance() {
+            bot.shield_until = tick.0 + SHIELD_TICKS;
+        }
+        if tick.0 < bot.shield_until {
+            events |= SHIELD_BITFLAG;
+        }
+
+        // swing at whatever is closest and in reach, players first
+        let in_reach = |target: &Vec2| target.distance(pos) < SWORD_LENGTH * 0.9;
+        let target = rivals.iter().map(|(rival, _, _)| *rival).filter(in_reach).next()
+            .or_else(|| enemies.iter().filter(|(_, enemy_hp)| !enemy_hp.dead)
+                .filter_map(|(pb, _)| *pb.0.get(tick.0.wrapping_sub(1))).filter(in_reach).next())
+            .or_else(|| chests.iter().filter(|(_, chest_hp)| chest_hp.current > 0)
+                .map(|(tf, _)| tf.translation.truncate()).filter(in_reach).next());
+        if let Some(target) = target {
+            let to = target - pos;
+            let error = bot.difficulty.aim_error();
+            dir = to.y.atan2(to.x) + rng.gen_range(-error..=error);
+            let ready = tick.0 >= bot.next_attack;
+            if ready && !stunned && events & SHIELD_BITFLAG == 0 {
+                let cooldown = DEFAULT_COOLDOWN / powerup_config.attack_speed_mult(spu) / TICKLEN_S;
+                bot.next_attack = tick.0 + cooldown.ceil() as u16;
+                if rng.gen::<f32>() < bot.difficulty.attack_chance() {
+                    events |= ATTACK_BITFLAG;
+                }
+            }
+        } else if let Some(waypoint) = bot.waypoint {
+            let to = waypoint - pos;
+            dir = to.y.atan2(to.x);
+        }
+
+        send(&mut usercmd_writer, tick.0, pl.0, pos, dir, events);
+    }
+}
+
+fn send(usercmd_writer: &mut EventWriter<UserCmdEvent>, tick: u16, id: u8, pos: Vec2, dir: f32, events: u8) {
+    // input for the coming tick, like a client's ClientTick that arrived in time
+    usercmd_writer.send(UserCmdEvent {
+        seq_num: tick.wrapping_add(1),
+        id,
+        tick: UserCmd { pos, dir, events },
+    });
+}
+
+/// weak players nearby, then powerups, chests, the hill, and camps with enemies left
+fn pick_goal<R: Rng>(
+    bot: &Bot,
+    pos: Vec2,
+    rivals: &[(Vec2, u8, bool)],
+    enemies: &Query<(&PosBuffer, &Health), With<Enemy>>,
+    camps: &Query<(&Transform, &CampEnemies, Option<&Hill>), With<Camp>>,
+    chests: &Query<(&Transform, &Health), With<ItemChest>>,
+    powerups: &Query<(&Transform, &PowerUp)>,
+    spu: &StoredPowerUps,
+    powerup_config: &PowerUpConfig,
+    game_mode: &GameMode,
+    tick: u16,
+    rng: &mut R,
+) -> Option<Vec2> {
+    let closest = |targets: Vec<Vec2>| targets.into_iter()
+        .min_by(|a, b| a.distance(pos).total_cmp(&b.distance(pos)));
+
+    // hurt players are worth chasing from twice as far
+    let aggro = bot.difficulty.aggro_range();
+    let prey = rivals.iter()
+        .filter(|(rival, hp, _)| rival.distance(pos) < if *hp < PLAYER_DEFAULT_HP / 2 { aggro * 2. } else { aggro })
+        .map(|(rival, _, _)| *rival)
+        .collect();
+    if let Some(target) = closest(prey) { return Some(target) }
+
+    let pickups = powerups.iter()
+        .filter(|(_, powerup)| !powerup_config.capped(spu, powerup.0))
+        .map(|(tf, _)| tf.translation.truncate())
+        .filter(|powerup| powerup.distance(pos) < POWERUP_RANGE)
+        .collect();
+    if let Some(target) = closest(pickups) { return Some(target) }
+
+    let unopened = chests.iter()
+        .filter(|(_, hp)| hp.current > 0)
+        .map(|(tf, _)| tf.translation.truncate())
+        .filter(|chest| chest.distance(pos) < CHEST_RANGE)
+        .collect();
+    if let Some(target) = closest(unopened) { return Some(target) }
+
+    if game_mode.has_hill() {
+        if let Some((tf, _, _)) = camps.iter().find(|(_, _, hill)| hill.is_some()) {
+            return Some(tf.translation.truncate());
+        }
+    }
+
+    let mobs = enemies.iter()
+        .filter(|(_, hp)| !hp.dead)
+        .filter_map(|(pb, _)| *pb.0.get(tick.wrapping_sub(1)))
+        .filter(|enemy| enemy.distance(pos) < aggro)
+        .collect();
+    if let Some(target) = closest(mobs) { return Some(target) }
+
+    let camps_left = camps.iter()
+        .filter(|(_, enemies, _)| enemies.current_enemies > 0)
+        .map(|(tf, _, _)| tf.translation.truncate())
+        .collect();
+    if let Some(camp) = closest(camps_left) {
+        let offset = Vec2::new(rng.gen_range(-CAMP_RADIUS..CAMP_RADIUS), rng.gen_range(-CAMP_RADIUS..CAMP_RADIUS));
+        return Some(camp + offset);
+    }
+    bot.goal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bots_fill_free_slots_under_the_cap() {
+        let mut bots = Bots::default();
+        let mut taken = [false; MAX_PLAYERS];
+        taken[0] = true;
+        taken[1] = true;
+        assert_eq!(bots.add(Difficulty::Hard, &taken, 4), Some(2));
+        assert_eq!(bots.add(Difficulty::Easy, &taken, 4), Some(3));
+        assert_eq!(bots.add(Difficulty::Easy, &taken, 4), None);
+        assert!(bots.is_bot(2) && !bots.is_bot(1));
+        assert_eq!(bots.0[2], Some(Difficulty::Hard));
+    }
+
+    #[test]
+    fn bots_leave_from_the_top() {
+        let mut bots = Bots::default();
+        let taken = [false; MAX_PLAYERS];
+        bots.add(Difficulty::Normal, &taken, MAX_PLAYERS as u8);
+        bots.add(Difficulty::Normal, &taken, MAX_PLAYERS as u8);
+        assert_eq!(bots.remove(), Some(2));
+        assert_eq!(bots.add(Difficulty::Normal, &taken, u8::MAX), Some(2));
+        assert_eq!(bots.remove(), Some(2));
+        assert_eq!(bots.remove(), Some(1));
+        assert_eq!(bots.remove(), None);
+    }
+
+    #[test]
+    fn harder_bots_play_better() {
+        let levels = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+        for pair in levels.windows(2) {
+            let (easier, harder) = (pair[0], pair[1]);
+            assert_eq!(easier.next(), harder);
+            assert!(harder.think_ticks() < easier.think_ticks());
+            assert!(harder.aim_error() < easier.aim_error());
+            assert!(harder.attack_chance() > easier.attack_chance());
+            assert!(harder.shield_chance() > easier.shield_chance());
+            assert!(harder.aggro_range() > easier.aggro_range());
+        }
+        assert_eq!(Difficulty::Hard.next(), Difficulty::Easy);
+    }
+}
@@ -4,10 +4,14 @@ use bevy::window::PrimaryWindow;
 use crate::AppState;
 use crate::movement;
 use crate::game::camp::setup_camps;
-use crate::game::components::{Camp, CampStatus, Grade, Health, Player};
+use crate::game::components::{Camp, CampEnemies, CampOwner, CampStatus, Grade, Health, Player, Stats};
+use crate::game::mode::{GameMode, TEAM_COLORS, Teams};
+use crate::game::item::Inventory;
 use crate::game::{player, player::{LocalPlayer, LocalPlayerDeathEvent, LocalPlayerSpawnEvent, PLAYER_DEFAULT_HP, MAX_PLAYERS}, PlayerId};
+use crate::game::profile::PlayerProfiles;
 use crate::game::buffers::EventBuffer;
 use crate::game::player::SpawnEvent;
+use crate::game::respawn::Respawn;
 use crate::map;
 use crate::net::{IsHost, TickNum};
 
@@ -29,7 +33,7 @@ const CAMP_MARKER_COLORS: [Color; 5] = [
     Color::Rgba{red: 0.76, green: 0.6, blue: 0.13, alpha: 1.}  // Movement speed up
 ];
 
-const ENEMY_PLAYER_COLOR: Color = Color::Rgba {red: 1., green: 0.2, blue: 0.2, alpha: 1.};
+const ENEMY_PLAYER_COLOR: Color = Color::Rgba {red: 1., green: 0.2, blue: 0.2, alpha: 1.}; // outside of team modes
 
 #[derive(Component)]
 pub struct GameCamera;
@@ -37,12 +41,17 @@ pub struct GameCamera;
 #[derive(Component)]
 pub struct LocalPlayerMarker;
 
+/// Minimap marker for every player other than the local one, teammates included
 #[derive(Component)]
 pub struct EnemyPlayerMarker(pub u8);
 
 #[derive(Component)]
 pub struct CampMarker(pub u8);
 
+/// Enemy count shown next to a camp marker while a camp map is active
+#[derive(Component)]
+pub struct CampMarkerCount(pub u8);
+
 #[derive(Component)]
 pub struct Minimap;
 
@@ -64,12 +73,13 @@ impl Plugin for CameraPlugin {
             .add_systems(Update, spawn_update.run_if(player::local_player_dead))
             .add_systems(Update, marker_follow_local_player.run_if(not(player::local_player_dead)))
             .add_systems(OnEnter(AppState::Game), spawn_minimap.after(setup_camps))
+            .add_systems(OnExit(AppState::GameOver), remove_minimap)
             .add_systems(Update, configure_map_on_event)
             .add_systems(Update, spawn_camp_markers.run_if(any_with_component::<Camp>()))
             .add_systems(Update, hide_cleared_camp_markers.run_if(any_with_component::<CampMarker>()))
+            .add_systems(Update, reveal_camp_counts.run_if(any_with_component::<CampMarkerCount>()))
             .add_systems(Update, spawn_enemy_player_markers.run_if(any_with_component::<LocalPlayer>()))
-            .add_systems(Update, show_enemy_player_markers.run_if(player::local_player_dead))
-            .add_systems(Update, hide_enemy_player_markers.run_if(not(player::local_player_dead)))
+            .add_systems(Update, update_enemy_player_markers.run_if(any_with_component::<EnemyPlayerMarker>()))
             .add_systems(Update, show_hide_local_player_marker.run_if(any_with_component::<LocalPlayerMarker>()));
     }
 }
@@ -151,6 +161,20 @@ pub fn spawn_minimap(
     commands.entity(minimap_border_entity).add_child(minimap_entity);
 }
 
+// Despawns the minimap along with every marker on it, and unhides it for the next round
+fn remove_minimap(
+    mut commands: Commands,
+    minimap_border: Query<Entity, With<MinimapBorder>>,
+    mut camera_bundle: Query<&mut Visibility, With<SpatialCameraBundle>>,
+) {
+    for e in minimap_border.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+    for mut vis in &mut camera_bundle {
+        *vis = Visibility::Visible;
+    }
+}
+
 fn spawn_camp_markers(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
@@ -185,7 +209,21 @@ fn spawn_camp_markers(
                 },
                 CampMarker(camp_num.0),
             )).id();
+            let count_ent = commands.spawn((
+                Text2dBundle {
+                    text: Text::from_section("", TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 12.,
+                        color: Color::WHITE,
+                    }),
+                    transform: Transform::from_xyz(8., 0., 1.),
+                    visibility: Visibility::Hidden,
+                    ..Default::default()
+                },
+                CampMarkerCount(camp_num.0),
+            )).id();
 
+            commands.entity(camp_marker_ent).add_child(count_ent);
             commands.entity(parent).add_child(camp_marker_ent);
         }
     }
@@ -234,16 +272,19 @@ fn spawn_enemy_player_markers(
 }
 
 // TODO: Tie this function to a camp cleared/camp spawned event instead of running on Update
+// Owned camps stay on the minimap in their owner's color even after they're cleared
 fn hide_cleared_camp_markers(
-    mut camp_markers: Query<(&CampMarker, &mut Visibility), With<CampMarker>>,
-    camps: Query<(&Camp, &CampStatus), With<Camp>>,
+    mut camp_markers: Query<(&CampMarker, &mut Visibility, &mut Sprite), With<CampMarker>>,
+    camps: Query<(&Camp, &CampStatus, &CampOwner, &Grade), With<Camp>>,
     input: Res<Input<KeyCode>>,
     app_state_current_state: Res<State<AppState>>,
+    profiles: Res<PlayerProfiles>,
 ) {
-    for (marker_num, mut marker_visibility) in &mut camp_markers {
-        for (camp_num, camp_status) in &camps {
+    for (marker_num, mut marker_visibility, mut marker_sprite) in &mut camp_markers {
+        for (camp_num, camp_status, control, camp_grade) in &camps {
             if camp_num.0 == marker_num.0 {
-                if !camp_status.0 || input.pressed(KeyCode::Tab) ||
+                marker_sprite.color = control.owner.map_or(CAMP_MARKER_COLORS[(camp_grade.0 - 1) as usize], |id| profiles.color(id));
+                if (!camp_status.0 && control.owner.is_none()) || input.pressed(KeyCode::Tab) ||
                     *app_state_current_state.get() == AppState::GameOver {
                     *marker_visibility = Visibility::Hidden;
                 }
@@ -255,34 +296,52 @@ fn hide_cleared_camp_markers(
     }
 }
 
-fn show_enemy_player_markers(
-    mut enemy_player_markers: Query<(&EnemyPlayerMarker, &mut Visibility, &mut Transform), With<EnemyPlayerMarker>>,
-    players: Query<(&Player, &Transform, &Health), (With<Player>, Without<LocalPlayer>, Without<EnemyPlayerMarker>)>,
-    input: Res<Input<KeyCode>>,
-    app_state_current_state: Res<State<AppState>>,
+// Shows how many enemies are left in each camp while the local player has a camp map active
+fn reveal_camp_counts(
+    mut counts: Query<(&CampMarkerCount, &mut Text, &mut Visibility)>,
+    camps: Query<(&Camp, &CampEnemies)>,
+    local_player: Query<&Inventory, With<LocalPlayer>>,
 ) {
-    for (marker_id, mut marker_visibility, mut marker_transform) in &mut enemy_player_markers {
-        for (player_id, player_transform, player_health) in &players {
-            if input.pressed(KeyCode::Tab) ||
-                    *app_state_current_state.get() == AppState::GameOver {
-                    *marker_visibility = Visibility::Hidden;
-                }
-                else {
-                    if marker_id.0 == player_id.0 && !player_health.dead {
-                        *marker_visibility = Visibility::Visible;
-                        marker_transform.translation.x = make_position_not_float(player_transform.translation.x / map::TILESIZE as f32);
-                        marker_transform.translation.y = make_position_not_float(player_transform.translation.y / map::TILESIZE as f32);
-                    }
-                }
+    let revealed = local_player.get_single().is_ok_and(|inv| inv.reveal_ticks > 0);
+    for (count, mut text, mut vis) in &mut counts {
+        if !revealed {
+            *vis = Visibility::Hidden;
+            continue;
+        }
+        for (camp, enemies) in &camps {
+            if camp.0 == count.0 {
+                text.sections[0].value = enemies.current_enemies.to_string();
+                *vis = Visibility::Inherited;
+            }
         }
     }
 }
 
-fn hide_enemy_player_markers(
-    mut enemy_player_markers: Query<&mut Visibility, With<EnemyPlayerMarker>>,
+// Teammates are always shown on the minimap, opponents only while the local player is picking a spawn
+fn update_enemy_player_markers(
+    mut enemy_player_markers: Query<(&EnemyPlayerMarker, &mut Visibility, &mut Transform, &mut Sprite), With<EnemyPlayerMarker>>,
+    players: Query<(&Player, &Transform, &Health), (With<Player>, Without<LocalPlayer>, Without<EnemyPlayerMarker>)>,
+    local_player: Query<&Health, With<LocalPlayer>>,
+    local_player_id: Res<PlayerId>,
+    game_mode: Res<GameMode>,
+    teams: Res<Teams>,
+    input: Res<Input<KeyCode>>,
+    app_state_current_state: Res<State<AppState>>,
 ) {
-    for mut marker_visibility in &mut enemy_player_markers {
+    let local_dead = local_player.get_single().map_or(true, |hp| hp.dead);
+    let hidden = input.pressed(KeyCode::Tab) || *app_state_current_state.get() == AppState::GameOver;
+    for (marker_id, mut marker_visibility, mut marker_transform, mut marker_sprite) in &mut enemy_player_markers {
+        let teammate = game_mode.teams() && teams.of(marker_id.0) == teams.of(local_player_id.0);
+        marker_sprite.color = if game_mode.teams() { TEAM_COLORS[teams.of(marker_id.0) as usize] } else { ENEMY_PLAYER_COLOR };
         *marker_visibility = Visibility::Hidden;
+        if hidden || !(teammate || local_dead) { continue }
+        for (player_id, player_transform, player_health) in &players {
+            if marker_id.0 == player_id.0 && !player_health.dead {
+                *marker_visibility = Visibility::Visible;
+                marker_transform.translation.x = make_position_not_float(player_transform.translation.x / map::TILESIZE as f32);
+                marker_transform.translation.y = make_position_not_float(player_transform.translation.y / map::TILESIZE as f32);
+            }
+        }
     }
 }
 
@@ -407,6 +466,20 @@ fn show_hide_local_player_marker(
     }
 }
 
+/// the world position under the cursor if it's over the minimap, which is blown up
+/// in the middle of the screen while picking a spawn
+pub fn minimap_to_world(cursor: Vec2, enlarged: bool) -> Option<Vec2> {
+    let (corner, pixels_per_tile) = if enlarged {
+        (Vec2::new(super::WIN_W / 2. - MINIMAP_DIMENSIONS.x as f32, super::WIN_H / 2. - MINIMAP_DIMENSIONS.y as f32), 2.)
+    } else {
+        (Vec2::new(super::WIN_W - (MINIMAP_PAD.x + MINIMAP_DIMENSIONS.x) as f32, MINIMAP_PAD.y as f32), 1.)
+    };
+    let tile = (cursor - corner) / pixels_per_tile;
+    if tile.x < 0. || tile.y < 0. || tile.x >= map::MAPSIZE as f32 || tile.y >= map::MAPSIZE as f32 { return None }
+    let half = (map::MAPSIZE / 2) as f32;
+    Some(Vec2::new((tile.x - half) * map::TILESIZE as f32, -(tile.y - half) * map::TILESIZE as f32))
+}
+
 fn make_position_not_float(position: f32) -> f32 {
     return position as i32 as f32;
 }
@@ -424,7 +497,13 @@ fn spawn_update(
     tick: Res<TickNum>,
     mut lp_spawn_writer: EventWriter<LocalPlayerSpawnEvent>,
     mut spawn_writer: EventWriter<SpawnEvent>,
+    local_stats: Query<(&Stats, &Respawn), With<LocalPlayer>>,
+    game_mode: Res<GameMode>,
 ) {
+    // out of lives, stay on the minimap and watch
+    if local_stats.get_single().is_ok_and(|(stats, _)| !game_mode.can_respawn(stats)) { return }
+    // still waiting out the respawn timer, the host would ignore us anyway
+    if local_stats.get_single().is_ok_and(|(_, respawn)| !respawn.ready()) { return }
     if mouse_button_inputs.just_pressed(MouseButton::Left) {
         let window = window_query.get_single().unwrap();
         let cursor_position = window.cursor_position().unwrap();
@@ -492,35 +571,28 @@ fn game_update(
         for mut camera_transform in &mut game_camera {
             camera_transform.translation.x = local_player_transform.translation.x;
             camera_transform.translation.y = local_player_transform.translation.y;
+            clamp_to_map(&mut camera_transform.translation);
+        }
+    }
+}
 
-            let clamp_pos_x: f32 = ((((map::MAPSIZE * map::TILESIZE) as isize)/2) - (((super::WIN_W * GAME_PROJ_SCALE) / 2.) as isize)) as f32;
-            let clamp_pos_y: f32 = ((((map::MAPSIZE * map::TILESIZE) as isize)/2) - (((super::WIN_H * GAME_PROJ_SCALE) / 2.) as isize)) as f32;
+/// keeps the camera from showing past the edge of the map,
+/// centers it on an axis where the map is smaller than the window
+pub fn clamp_to_map(translation: &mut Vec3) {
+    let clamp_pos_x: f32 = ((((map::MAPSIZE * map::TILESIZE) as isize)/2) - (((super::WIN_W * GAME_PROJ_SCALE) / 2.) as isize)) as f32;
+    let clamp_pos_y: f32 = ((((map::MAPSIZE * map::TILESIZE) as isize)/2) - (((super::WIN_H * GAME_PROJ_SCALE) / 2.) as isize)) as f32;
 
-            // Clamp camera view to map borders
-            // Center camera in axis if map dimensions < window size
-            if map::MAPSIZE * map::TILESIZE < super::WIN_W as usize {
-                camera_transform.translation.x = 0.
-            }
-            else {
-                if camera_transform.translation.x > clamp_pos_x {
-                    camera_transform.translation.x = clamp_pos_x
-                }
-                if camera_transform.translation.x < -clamp_pos_x {
-                    camera_transform.translation.x = -clamp_pos_x;
-                }
-            }
+    if map::MAPSIZE * map::TILESIZE < super::WIN_W as usize {
+        translation.x = 0.
+    }
+    else {
+        translation.x = translation.x.clamp(-clamp_pos_x, clamp_pos_x);
+    }
 
-            if map::MAPSIZE * map::TILESIZE < super::WIN_H as usize {
-                camera_transform.translation.y = 0.
-            }
-            else {
-                if camera_transform.translation.y > clamp_pos_y {
-                    camera_transform.translation.y = clamp_pos_y
-                }
-                if camera_transform.translation.y < -clamp_pos_y {
-                    camera_transform.translation.y = -clamp_pos_y;
-                }
-            }
-        }
+    if map::MAPSIZE * map::TILESIZE < super::WIN_H as usize {
+        translation.y = 0.
     }
-}
\ No newline at end of file
+    else {
+        translation.y = translation.y.clamp(-clamp_pos_y, clamp_pos_y);
+    }
+}
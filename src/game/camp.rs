@@ -13,14 +13,23 @@ use crate::game::enemy::ENEMY_MAX_HP;
 use crate::game::map::setup_map;
 use crate::map::MapSeed;
 use crate::map::ChestCoords;
-use crate::net::{is_host, TickNum};
+use crate::net::{self, is_host, TickNum};
+use crate::game::profile::PlayerProfiles;
 use crate::PowerupAtlas;
+use crate::game::item::{spawn_item_pickup, ItemType, NUM_ITEM_TYPES};
+use crate::game::powerup::PowerUpConfig;
+use crate::game::mode::{GameMode, Hill, ScoreEvent};
+use crate::game::round::{MatchPhase, Round};
 
 const CAMP_ENEMIES: u8 = 5;
 const NUM_GRADES: u8 = 5;
 const DEC_SIZE: Vec2 = Vec2 {x: 32., y: 32.};
 const POWERUP_DROP_CHANCE: u32 = 50;
+const CHEST_TRAP_CHANCE: u32 = 25;
 const CAMP_RESPAWN_TIME: f32 = 60.;
+pub const CAMP_RADIUS: f32 = 160.;
+const CAMP_INCOME_TICKS: u16 = 50; // owners get a point this often
+const CAMP_CONTEST_TICKS: u8 = 50; // how long a rival has to hold a camp alone to take it
 
 #[derive(Component)]
 pub struct CampRespawnTimer(pub Timer);
@@ -39,7 +48,21 @@ impl Plugin for CampPlugin{
             handle_camp_clear,
             respawn_camp_enemies.run_if(is_host),
             handle_chest_hit,
+            camp_owner_draw,
         ));
+        app.add_systems(FixedUpdate, camp_control_simulate
+            .run_if(in_state(AppState::Game)).run_if(is_host).before(net::host::fixed));
+        app.add_systems(OnExit(AppState::GameOver), remove_camps);
+    }
+}
+
+/// despawns camps, their decorations, chests and any powerups left lying around
+pub fn remove_camps(
+    mut commands: Commands,
+    entities: Query<Entity, Or<(With<Camp>, With<CampDecoration>, With<ItemChest>, With<PowerUp>)>>,
+) {
+    for e in entities.iter() {
+        commands.entity(e).despawn_recursive();
     }
 }
 
@@ -50,6 +73,7 @@ pub fn setup_camps(
     decoration_atlas: Res<Decorations>,
     map_seed: Res<MapSeed>,
     asset_server: Res<AssetServer>,
+    game_mode: Res<GameMode>,
 ) {
     let mut rng = ChaChaRng::seed_from_u64(map_seed.0);
     // spawn a camp at a specified position
@@ -69,7 +93,7 @@ pub fn setup_camps(
 
         let special_enemy_index = rng.gen_range(0..CAMP_ENEMIES);
 
-        commands.spawn((
+        let mut camp = commands.spawn((
             Camp(campid),
             SpatialBundle {
                 transform: Transform::from_xyz(camp_pos.x, camp_pos.y, 0.),
@@ -81,8 +105,14 @@ pub fn setup_camps(
                 current_enemies: CAMP_ENEMIES,
             },
             CampStatus(true),
+            CampOwner::default(),
+            DamageLedger::default(),
             CampRespawnTimer(Timer::from_seconds(CAMP_RESPAWN_TIME, TimerMode::Once)),
         ));
+        // the map puts the central camp first
+        if campid == 0 && game_mode.has_hill() {
+            camp.insert(Hill);
+        }
 
         let mut vec_counter = 0;
 
@@ -105,6 +135,7 @@ pub fn setup_camps(
                     ..default()
                 },
                 Collider(DEC_SIZE),
+                CampDecoration(campid),
             ));
 
             vec_counter+=2;
@@ -150,6 +181,7 @@ pub fn setup_chests(
     chest_coords: Res<ChestCoords>,
     map_seed: Res<MapSeed>,
     chest_atlas: Res<Chests>,
+    powerup_config: Res<PowerUpConfig>,
 ){
 
     // for chests in chest_coords, commands.spawn with chest component and health
@@ -164,7 +196,9 @@ pub fn setup_chests(
             ItemChest{
                 id: i,
                 // 5 random powerups
-                contents: [rng.gen_range(0..5), rng.gen_range(0..5), rng.gen_range(0..5), rng.gen_range(0..5), rng.gen_range(0..5)]
+                contents: [powerup_config.roll(&mut rng), powerup_config.roll(&mut rng), powerup_config.roll(&mut rng), powerup_config.roll(&mut rng), powerup_config.roll(&mut rng)],
+                trapped: rng.gen_range(0..100) < CHEST_TRAP_CHANCE,
+                item: ItemType::from_u8(rng.gen_range(0..NUM_ITEM_TYPES as u8)).unwrap(),
             },
             pb,
             Health {
@@ -237,6 +271,7 @@ pub fn handle_chest_hit(
 
                 i+=2;
             }
+            spawn_item_pickup(&mut commands, chest.item, tf.translation.truncate());
         }
     }
 }
@@ -320,4 +355,120 @@ pub fn respawn_camp_enemies(
             }
         }
     }
-}
\ No newline at end of file
+}
+/// pays out camp owners and lets rivals take over a camp by standing in it while its owner is away
+pub fn camp_control_simulate(
+    tick: Res<TickNum>,
+    mut camp_query: Query<(&Transform, &mut CampOwner, Option<&Hill>), With<Camp>>,
+    mut players: Query<(&Player, &PosBuffer, &Health, &mut Stats)>,
+    game_mode: Res<GameMode>,
+    round: Res<Round>,
+) {
+    if round.phase == MatchPhase::Ended { return }
+    for (camp_tf, mut control, hill) in &mut camp_query {
+        let camp_pos = camp_tf.translation.truncate();
+        let mut owner_present = false;
+        let mut rivals: Vec<u8> = Vec::new();
+        for (pl, pb, hp, _) in &players {
+            if hp.dead { continue }
+            let pos = pb.0.get(tick.0);
+            if pos.is_none() || pos.unwrap().distance(camp_pos) > CAMP_RADIUS { continue }
+            if control.owner == Some(pl.0) { owner_present = true } else { rivals.push(pl.0) }
+        }
+
+        contest_camp(&mut control, owner_present, &rivals);
+
+        if tick.0 % CAMP_INCOME_TICKS != 0 || control.owner.is_none() { continue }
+        let income = if hill.is_some() { ScoreEvent::HillHeld } else { ScoreEvent::CampHeld };
+        for (pl, _, _, mut stats) in &mut players {
+            if control.owner == Some(pl.0) {
+                game_mode.award(&mut stats, income, 1.0);
+            }
+        }
+    }
+}
+
+/// only an unopposed rival makes progress, anyone else in the camp stalls it
+pub fn contest_camp(control: &mut CampOwner, owner_present: bool, rivals: &[u8]) {
+    if control.owner.is_some() && !owner_present && rivals.len() == 1 {
+        if control.contester != Some(rivals[0]) {
+            control.contester = Some(rivals[0]);
+            control.progress = 0;
+        }
+        control.progress = control.progress.saturating_add(1);
+        if control.progress >= CAMP_CONTEST_TICKS {
+            control.owner = control.contester.take();
+            control.progress = 0;
+        }
+    }
+    else if rivals.is_empty() {
+        control.contester = None;
+        control.progress = control.progress.saturating_sub(1);
+    }
+}
+
+/// tints the decorations of owned camps in their owner's color
+pub fn camp_owner_draw(
+    camp_query: Query<(&Camp, &CampOwner), Changed<CampOwner>>,
+    mut decorations: Query<(&CampDecoration, &mut TextureAtlasSprite)>,
+    profiles: Res<PlayerProfiles>,
+) {
+    for (camp, control) in &camp_query {
+        for (decoration, mut sprite) in &mut decorations {
+            if decoration.0 != camp.0 { continue }
+            sprite.color = control.owner.map_or(Color::WHITE, |id| profiles.color(id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owned_by(id: u8) -> CampOwner {
+        CampOwner { owner: Some(id), contester: None, progress: 0 }
+    }
+
+    #[test]
+    fn lone_rival_takes_an_empty_camp() {
+        let mut control = owned_by(1);
+        for _ in 1..CAMP_CONTEST_TICKS {
+            contest_camp(&mut control, false, &[2]);
+        }
+        assert_eq!(control.owner, Some(1));
+        assert_eq!(control.contester, Some(2));
+        contest_camp(&mut control, false, &[2]);
+        assert_eq!(control.owner, Some(2));
+        assert_eq!(control.contester, None);
+        assert_eq!(control.progress, 0);
+    }
+
+    #[test]
+    fn owner_or_second_rival_stalls_the_contest() {
+        let mut control = owned_by(1);
+        contest_camp(&mut control, false, &[2]);
+        contest_camp(&mut control, true, &[2]);
+        contest_camp(&mut control, false, &[2, 3]);
+        assert_eq!(control.progress, 1);
+        assert_eq!(control.contester, Some(2));
+        // a new rival starts over
+        contest_camp(&mut control, false, &[3]);
+        assert_eq!(control.contester, Some(3));
+        assert_eq!(control.progress, 1);
+    }
+
+    #[test]
+    fn progress_decays_once_the_camp_is_empty() {
+        let mut control = owned_by(1);
+        contest_camp(&mut control, false, &[2]);
+        contest_camp(&mut control, false, &[2]);
+        contest_camp(&mut control, false, &[]);
+        assert_eq!(control.contester, None);
+        assert_eq!(control.progress, 1);
+        // unowned camps can't be contested
+        let mut control = CampOwner { owner: None, contester: None, progress: 0 };
+        contest_camp(&mut control, false, &[2]);
+        assert_eq!(control.owner, None);
+        assert_eq!(control.progress, 0);
+    }
+}
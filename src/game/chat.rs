@@ -0,0 +1,431 @@
+use std::collections::VecDeque;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use crate::AppState;
+use crate::game::PlayerId;
+use crate::game::bot::Bots;
+use crate::game::player::MAX_PLAYERS;
+use crate::game::profile::PlayerProfiles;
+use crate::game::round::{MatchPhase, Round};
+use crate::game::spectate::SPECTATOR_ID;
+use crate::menus::components::{ChatInput, ChatInputLine, Switch};
+use crate::net::{self, is_client, is_host, IsHost, TickNum};
+use crate::net::packets::{ChatMessage, Packet, send_buf};
+
+pub const CHAT_MAX_LEN: usize = 100; // in characters, and never more than fits in a length byte
+pub const CHAT_LOG_LEN: usize = 8;
+pub const CHAT_FADE_SECS: f32 = 10.; // how long a line stays up while the input is closed
+const CHAT_FADE_OUT_SECS: f32 = 2.;
+pub const CHAT_RESEND_TICKS: u16 = 30; // the host repeats each line in its ticks this long so a dropped packet doesn't lose it
+pub const CHAT_TICK_BYTES: usize = 1024; // most chat one host tick carries, older lines wait for the next tick or drop off
+const CHAT_BURST: u8 = 3; // messages someone can send back to back
+const CHAT_REFILL_TICKS: u16 = 20; // then one more every 2 seconds
+pub const SYSTEM_SENDER: u8 = 0xFE; // lines from the host itself rather than a player
+pub const CHAT_PROMPT: &str = "Say: ";
+const SYSTEM_COLOR: Color = Color::YELLOW;
+
+/// One line of chat, ids are handed out by the host so clients can tell which lines they've seen
+#[derive(Clone, Eq, PartialEq)]
+pub struct ChatLine {
+    pub id: u16,
+    pub sender: u8,
+    pub text: String,
+}
+
+impl ChatLine {
+    /// bytes the line takes up in a host tick
+    pub fn wire_size(&self) -> usize {
+        4 + self.text.len().min(u8::MAX as usize)
+    }
+}
+
+/// sent when a player's message reaches the host, straight from the input line for the host's own
+#[derive(Event)]
+pub struct ChatEvent {
+    pub sender: u8,
+    pub text: String,
+}
+
+/// sent by /kick for the network module to drop the player
+#[derive(Event)]
+pub struct KickEvent(pub u8);
+
+/// Lines the host has handed out recently, and how many more messages each player can send right now
+#[derive(Resource)]
+pub struct ChatRelay {
+    next_id: u16,
+    pub recent: Vec<(u16, Option<u8>, ChatLine)>, // tick it was posted, who it's for (None is everyone), the line
+    allowance: [u8; MAX_PLAYERS],
+}
+
+impl Default for ChatRelay {
+    fn default() -> ChatRelay {
+        ChatRelay {
+            next_id: 1,
+            recent: Vec::new(),
+            allowance: [CHAT_BURST; MAX_PLAYERS],
+        }
+    }
+}
+
+impl ChatRelay {
+    pub fn post(&mut self, tick: u16, to: Option<u8>, sender: u8, text: String) -> ChatLine {
+        let line = ChatLine { id: self.next_id, sender, text };
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        self.recent.push((tick, to, line.clone()));
+        line
+    }
+
+    /// what goes out in the host tick for a player, spectators only get lines meant for everyone,
+    /// the newest lines that fit in CHAT_TICK_BYTES
+    pub fn lines_for(&self, id: u8) -> Vec<ChatLine> {
+        let mut size = 0;
+        let mut lines: Vec<ChatLine> = self.recent.iter().rev()
+            .filter(|(_, to, _)| to.map_or(true, |to| to == id))
+            .map(|(_, _, line)| line)
+            .take_while(|line| {
+                size += line.wire_size();
+                size <= CHAT_TICK_BYTES
+            })
+            .take(u8::MAX as usize)
+            .cloned()
+            .collect();
+        lines.reverse();
+        lines
+    }
+}
+
+/// Messages a client has sent that the host hasn't acked yet, all of them go out every tick until it does
+#[derive(Resource)]
+pub struct ChatOutbox {
+    pub next_seq: u16,
+    pub pending: VecDeque<(u16, String)>,
+}
+
+impl Default for ChatOutbox {
+    fn default() -> ChatOutbox {
+        ChatOutbox { next_seq: 1, pending: VecDeque::new() }
+    }
+}
+
+impl ChatOutbox {
+    pub fn push(&mut self, text: String) {
+        self.pending.push_back((self.next_seq, text));
+        self.next_seq = self.next_seq.wrapping_add(1);
+    }
+
+    /// the host has everything up to seq
+    pub fn ack(&mut self, seq: u16) {
+        while self.pending.front().is_some_and(|(pending, _)| pending.wrapping_sub(seq) > u16::MAX / 2 || *pending == seq) {
+            self.pending.pop_front();
+        }
+    }
+}
+
+/// The lines this machine shows and when each one arrived
+#[derive(Resource, Default)]
+pub struct ChatLog {
+    pub lines: VecDeque<(ChatLine, f32)>,
+    last_id: u16,
+}
+
+impl ChatLog {
+    /// adds a line unless it's one we've already seen
+    pub fn receive(&mut self, line: ChatLine, now: f32) {
+        if line.id.wrapping_sub(self.last_id) == 0 || line.id.wrapping_sub(self.last_id) > u16::MAX / 2 { return }
+        self.last_id = line.id;
+        self.lines.push_back((line, now));
+        if self.lines.len() > CHAT_LOG_LEN {
+            self.lines.pop_front();
+        }
+    }
+
+    /// empties the log but remembers what we've seen, the host may still be repeating it
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    pub fn reset(&mut self) {
+        *self = ChatLog::default();
+    }
+}
+
+/// Whether the chat input line is open, the game doesn't see the keyboard while it is
+#[derive(Resource, Default)]
+pub struct ChatOpen(pub bool);
+
+/// Shows the most recent chat lines
+#[derive(Component)]
+pub struct ChatLogDisplay;
+
+pub struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Game), reset_chat)
+            .add_systems(PreUpdate, chat_keys.after(InputSystem).run_if(in_state(AppState::Game)))
+            .add_systems(Update, (
+                host_chat.run_if(is_host),
+                chat_log_display,
+            ).run_if(in_state(AppState::Game)))
+            .add_systems(FixedUpdate, chat_relay_simulate
+                .run_if(in_state(AppState::Game)).run_if(is_host).before(net::host::fixed))
+            .add_systems(FixedUpdate, client_chat_fixed
+                .run_if(in_state(AppState::Game)).run_if(is_client))
+            .init_resource::<ChatRelay>()
+            .init_resource::<ChatOutbox>()
+            .init_resource::<ChatLog>()
+            .init_resource::<ChatOpen>()
+            .add_event::<ChatEvent>()
+            .add_event::<KickEvent>();
+    }
+}
+
+pub fn reset_chat(
+    mut relay: ResMut<ChatRelay>,
+    mut log: ResMut<ChatLog>,
+    mut chat_open: ResMut<ChatOpen>,
+) {
+    relay.recent.clear();
+    relay.allowance = [CHAT_BURST; MAX_PLAYERS];
+    log.clear();
+    chat_open.0 = false;
+}
+
+/// strips control characters and surrounding whitespace and cuts the message down to size
+pub fn sanitize_message(raw: &str) -> String {
+    let message: String = raw.chars().filter(|ch| !ch.is_control()).collect();
+    let mut message: String = message.trim().chars().take(CHAT_MAX_LEN).collect();
+    while message.len() > u8::MAX as usize {
+        message.pop();
+    }
+    message
+}
+
+/// enter opens the input line and sends what's in it, everything else typed goes into the line
+pub fn chat_keys(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut chat_open: ResMut<ChatOpen>,
+    player_id: Res<PlayerId>,
+    is_host: Res<IsHost>,
+    mut inputs: Query<(&mut Text, &mut ChatInput)>,
+    mut input_lines: Query<(&mut Switch, &mut Visibility), With<ChatInputLine>>,
+    mut chat_writer: EventWriter<ChatEvent>,
+    mut outbox: ResMut<ChatOutbox>,
+) {
+    // spectators can read chat but not send it
+    if player_id.0 == SPECTATOR_ID { return }
+    if keys.just_pressed(KeyCode::Return) {
+        if chat_open.0 {
+            for (mut text, mut input) in &mut inputs {
+                let message = sanitize_message(&std::mem::take(&mut input.text));
+                text.sections[0].value = CHAT_PROMPT.to_string();
+                if message.is_empty() { continue }
+                if is_host.0 {
+                    chat_writer.send(ChatEvent { sender: player_id.0, text: message });
+                } else {
+                    outbox.push(message);
+                }
+            }
+        }
+        chat_open.0 = !chat_open.0;
+        for (mut switch, mut vis) in &mut input_lines {
+            switch.chat = chat_open.0;
+            *vis = if chat_open.0 { Visibility::Inherited } else { Visibility::Hidden };
+        }
+    }
+    if chat_open.0 {
+        keys.reset_all();
+    }
+}
+
+/// sends everything the host hasn't acked yet
+pub fn client_chat_fixed(
+    sock: Res<net::Socket>,
+    outbox: Res<ChatOutbox>,
+) {
+    let Some(sock) = sock.0.as_ref() else { return };
+    let Ok(host_addr) = sock.peer_addr() else { return };
+    for (seq, text) in &outbox.pending {
+        let mut bytes: Vec<u8> = Vec::new();
+        ChatMessage { seq: *seq, text: text.clone() }.to_buf(&mut bytes);
+        if let Err(e) = send_buf(bytes.as_slice(), sock, &host_addr) {
+            println!("ChatMessage send failed: {}", e);
+            return;
+        }
+    }
+}
+
+/// gives back a message to everyone who's used up their burst, and forgets lines once they've been repeated enough
+pub fn chat_relay_simulate(
+    tick: Res<TickNum>,
+    mut relay: ResMut<ChatRelay>,
+) {
+    if tick.0 % CHAT_REFILL_TICKS == 0 {
+        for allowance in relay.allowance.iter_mut() {
+            *allowance = (*allowance + 1).min(CHAT_BURST);
+        }
+    }
+    relay.recent.retain(|(posted, _, _)| tick.0.saturating_sub(*posted) < CHAT_RESEND_TICKS);
+}
+
+/// checks incoming messages against the limits, runs the host's commands and posts the rest for everyone
+pub fn host_chat(
+    tick: Res<TickNum>,
+    time: Res<Time>,
+    mut chat_reader: EventReader<ChatEvent>,
+    mut relay: ResMut<ChatRelay>,
+    mut log: ResMut<ChatLog>,
+    profiles: Res<PlayerProfiles>,
+    bots: Res<Bots>,
+    mut round: ResMut<Round>,
+    mut kick_writer: EventWriter<KickEvent>,
+) {
+    for ev in chat_reader.iter() {
+        if ev.sender as usize >= MAX_PLAYERS { continue }
+        let text = sanitize_message(&ev.text);
+        if text.is_empty() { continue }
+        let mut posted: Vec<(Option<u8>, u8, String)> = Vec::new();
+        if let Some(command) = text.strip_prefix('/') {
+            if ev.sender != 0 {
+                posted.push((Some(ev.sender), SYSTEM_SENDER, "Only the host can use commands".to_string()));
+            } else {
+                posted.extend(run_command(command, &profiles, &bots, &tick, &mut round, &mut kick_writer));
+            }
+        } else if relay.allowance[ev.sender as usize] == 0 {
+            posted.push((Some(ev.sender), SYSTEM_SENDER, "You're sending messages too fast".to_string()));
+        } else {
+            relay.allowance[ev.sender as usize] -= 1;
+            posted.push((None, ev.sender, text));
+        }
+        for (to, sender, text) in posted {
+            let line = relay.post(tick.0, to, sender, text);
+            if to.map_or(true, |to| to == 0) {
+                log.receive(line, time.elapsed_seconds());
+            }
+        }
+    }
+}
+
+/// what a host command says back, and who to (None is everyone)
+fn run_command(
+    command: &str,
+    profiles: &PlayerProfiles,
+    bots: &Bots,
+    tick: &TickNum,
+    round: &mut Round,
+    kick_writer: &mut EventWriter<KickEvent>,
+) -> Vec<(Option<u8>, u8, String)> {
+    let (name, arg) = command.split_once(' ').unwrap_or((command, ""));
+    let arg = arg.trim();
+    let reply = |text: String| vec![(Some(0), SYSTEM_SENDER, text)];
+    match name.to_lowercase().as_str() {
+        "kick" => {
+            // by name, or by number for anyone still going by "Player N"
+            let target = (0..MAX_PLAYERS as u8).find(|id| profiles.0[*id as usize].as_ref().is_some_and(|profile| profile.name.eq_ignore_ascii_case(arg)))
+                .or(arg.parse::<u8>().ok().filter(|n| (1..=MAX_PLAYERS as u8).contains(n)).map(|n| n - 1).filter(|id| profiles.0[*id as usize].is_some()));
+            match target {
+                None => reply(format!("Nobody called {}", arg)),
+                Some(0) => reply("You can't kick yourself".to_string()),
+                Some(id) if bots.is_bot(id) => reply("Bots can only be removed in the lobby".to_string()),
+                Some(id) => {
+                    kick_writer.send(KickEvent(id));
+                    vec![(None, SYSTEM_SENDER, format!("{} was kicked", profiles.name(id)))]
+                }
+            }
+        }
+        "restart" => {
+            if round.phase == MatchPhase::Ended { return reply("The round is already over".to_string()) }
+            round.phase = MatchPhase::Ended;
+            round.ended_tick = tick.0;
+            round.restarting = true;
+            vec![(None, SYSTEM_SENDER, "The host restarted the match".to_string())]
+        }
+        "help" => reply("/kick <name or number>, /restart".to_string()),
+        _ => reply(format!("Unknown command /{}, try /help", name)),
+    }
+}
+
+/// lines fade out after a while, they all come back while the input is open
+pub fn chat_log_display(
+    time: Res<Time>,
+    log: Res<ChatLog>,
+    chat_open: Res<ChatOpen>,
+    profiles: Res<PlayerProfiles>,
+    mut chat_displays: Query<&mut Text, With<ChatLogDisplay>>,
+) {
+    let now = time.elapsed_seconds();
+    for mut text in &mut chat_displays {
+        let style = text.sections[0].style.clone();
+        let mut sections = vec![TextSection::new("", style.clone())];
+        for (line, at) in &log.lines {
+            let alpha = if chat_open.0 { 1. } else { ((CHAT_FADE_SECS - (now - at)) / CHAT_FADE_OUT_SECS).clamp(0., 1.) };
+            if alpha <= 0. { continue }
+            if line.sender == SYSTEM_SENDER {
+                sections.push(TextSection::new(format!("{}\n", line.text), TextStyle { color: SYSTEM_COLOR.with_a(alpha), ..style.clone() }));
+                continue;
+            }
+            sections.push(TextSection::new(format!("{}: ", profiles.name(line.sender)), TextStyle { color: profiles.color(line.sender).with_a(alpha), ..style.clone() }));
+            sections.push(TextSection::new(format!("{}\n", line.text), TextStyle { color: style.color.with_a(alpha), ..style.clone() }));
+        }
+        text.sections = sections;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relay_caps_the_bytes_per_tick_and_keeps_the_newest() {
+        let mut relay = ChatRelay::default();
+        // 16 players each sending a burst of full length lines
+        for sender in 0..MAX_PLAYERS as u8 {
+            for _ in 0..CHAT_BURST {
+                relay.post(0, None, sender, "x".repeat(u8::MAX as usize));
+            }
+        }
+        let lines = relay.lines_for(0);
+        assert!(!lines.is_empty());
+        assert!(lines.iter().map(ChatLine::wire_size).sum::<usize>() <= CHAT_TICK_BYTES);
+        // the newest line made it and the lines are still oldest first
+        assert_eq!(lines.last().unwrap().id, relay.recent.last().unwrap().2.id);
+        assert!(lines.windows(2).all(|pair| pair[0].id < pair[1].id));
+    }
+
+    #[test]
+    fn relay_sends_private_lines_only_to_their_player() {
+        let mut relay = ChatRelay::default();
+        relay.post(0, None, 0, "everyone".to_string());
+        relay.post(0, Some(2), SYSTEM_SENDER, "just you".to_string());
+        assert_eq!(relay.lines_for(2).len(), 2);
+        assert_eq!(relay.lines_for(3).len(), 1);
+        assert_eq!(relay.lines_for(SPECTATOR_ID).len(), 1);
+    }
+
+    #[test]
+    fn outbox_ack_clears_up_to_the_seq_across_the_wrap() {
+        let mut outbox = ChatOutbox { next_seq: u16::MAX - 1, pending: VecDeque::new() };
+        for text in ["a", "b", "c", "d"] {
+            outbox.push(text.to_string());
+        }
+        outbox.ack(u16::MAX);
+        assert_eq!(outbox.pending.iter().map(|(seq, _)| *seq).collect::<Vec<u16>>(), vec![0, 1]);
+        outbox.ack(1);
+        assert!(outbox.pending.is_empty());
+    }
+
+    #[test]
+    fn log_takes_each_line_once() {
+        let mut log = ChatLog::default();
+        let line = ChatLine { id: 1, sender: 0, text: "hi".to_string() };
+        log.receive(line.clone(), 0.);
+        log.receive(line, 1.);
+        assert_eq!(log.lines.len(), 1);
+        for id in 2..2 + CHAT_LOG_LEN as u16 {
+            log.receive(ChatLine { id, sender: 0, text: String::new() }, 2.);
+        }
+        assert_eq!(log.lines.len(), CHAT_LOG_LEN);
+        assert_eq!(log.lines.front().unwrap().0.id, 2);
+    }
+}
@@ -2,6 +2,8 @@
 // Components which are only used locally can be left inside a more localized file.
 use bevy::prelude::*;
 use core::fmt::Debug;
+use std::cmp::Reverse;
+use crate::game::item::ItemType;
 
 #[derive(Component)]
 pub struct Health {
@@ -10,6 +12,15 @@ pub struct Health {
     pub dead: bool
 }
 
+#[derive(Component)]
+pub struct Stamina {
+    pub current: u8,
+    pub max: u8,
+}
+
+#[derive(Component)]
+pub struct StaminaBar;
+
 #[derive(Component)]
 pub struct Fade {
     pub current: f32,
@@ -17,11 +28,7 @@ pub struct Fade {
 }
 
 pub const NUM_POWERUPS: usize = 5;
-pub const DAMAGE_DEALT_UP: u8 = 10;
-pub const DAMAGE_REDUCTION_UP: f32 = 0.9;
 pub const MEAT_VALUE: u8 = 30;
-pub const ATTACK_SPEED_UP: f32 = 1.1;
-pub const MOVEMENT_SPEED_UP: u8 = 15;
 pub const CHEST_CONTENTS: usize = 5;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
@@ -33,6 +40,9 @@ pub enum PowerUpType {
     MovementSpeedUp,
 }
 
+pub const POWERUP_TYPES: [PowerUpType; NUM_POWERUPS] = [PowerUpType::Meat, PowerUpType::DamageDealtUp,
+    PowerUpType::DamageReductionUp, PowerUpType::AttackSpeedUp, PowerUpType::MovementSpeedUp];
+
 #[derive(Component, Eq, PartialEq, Clone)]
 pub struct StoredPowerUps{
     pub power_ups: [u8; NUM_POWERUPS],
@@ -55,14 +65,109 @@ pub struct ScoreDisplay;
 #[derive(Component)]
 pub struct PowerupDisplayText(pub u8);
 
-#[derive(Component, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum AbilityType {
+    Attack = 0,
+    Dash,
+}
+
+#[derive(Component)]
+pub struct CooldownDisplayText(pub AbilityType);
+
+#[derive(Component, Clone, Default)]
 pub struct Stats{
-    pub score: u8,
-    pub enemies_killed: u8,
-    pub players_killed: u8,
-    pub camps_captured: u8,
-    pub deaths: u8,
-    pub kd_ratio: f32,
+    pub score: u32,
+    pub enemies_killed: u16,
+    pub players_killed: u16,
+    pub camps_captured: u16,
+    pub deaths: u16,
+    pub assists: u16,
+    pub damage_dealt: u32,
+    pub damage_taken: u32,
+    pub powerups_collected: u16,
+    pub chests_opened: u16,
+    pub kill_streak: u16, // kills since the last death
+    pub best_streak: u16,
+    pub ticks_alive: u32,
+}
+
+impl Stats {
+    pub fn kd_ratio(&self) -> f32 {
+        self.players_killed as f32 / self.deaths.max(1) as f32
+    }
+
+    pub fn record_kill(&mut self) {
+        self.players_killed = self.players_killed.saturating_add(1);
+        self.kill_streak = self.kill_streak.saturating_add(1);
+        self.best_streak = self.best_streak.max(self.kill_streak);
+    }
+
+    pub fn record_death(&mut self) {
+        self.deaths = self.deaths.saturating_add(1);
+        self.kill_streak = 0;
+    }
+
+    pub fn record_damage(&mut self, dealt: u8) {
+        self.damage_dealt = self.damage_dealt.saturating_add(dealt as u32);
+    }
+
+    pub fn record_hurt(&mut self, taken: u8) {
+        self.damage_taken = self.damage_taken.saturating_add(taken as u32);
+    }
+}
+
+pub const ASSIST_SHARE: f32 = 0.25; // fraction of the damage taken a player needs to deal to get an assist
+
+/// How much damage each player has dealt to this player, enemy or camp since it last died or was cleared.
+/// Only the host keeps these
+#[derive(Component, Clone, Default)]
+pub struct DamageLedger(pub Vec<(u8, u32)>);
+
+impl DamageLedger {
+    pub fn record(&mut self, id: u8, damage: u8) {
+        if damage == 0 { return }
+        match self.0.iter_mut().find(|(pid, _)| *pid == id) {
+            Some((_, total)) => *total += damage as u32,
+            None => self.0.push((id, damage as u32)),
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.0.iter().map(|(_, damage)| damage).sum()
+    }
+
+    /// fraction of all recorded damage dealt by this player
+    pub fn share(&self, id: u8) -> f32 {
+        let total = self.total();
+        if total == 0 { return 0. }
+        self.0.iter().find(|(pid, _)| *pid == id).map_or(0, |(_, damage)| *damage) as f32 / total as f32
+    }
+
+    /// the player who dealt the most damage, ties go to the lowest id
+    pub fn top(&self) -> Option<u8> {
+        self.0.iter().max_by_key(|(id, damage)| (*damage, Reverse(*id))).map(|(id, _)| *id)
+    }
+
+    /// everyone besides the killer who did enough damage to count
+    pub fn assists(&self, killer: u8) -> Vec<u8> {
+        self.0.iter()
+            .map(|(id, _)| *id)
+            .filter(|id| *id != killer && self.share(*id) >= ASSIST_SHARE)
+            .collect()
+    }
+
+    pub fn merge(&mut self, other: &DamageLedger) {
+        for (id, damage) in &other.0 {
+            match self.0.iter_mut().find(|(pid, _)| pid == id) {
+                Some((_, total)) => *total += damage,
+                None => self.0.push((*id, *damage)),
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
 }
 
 #[derive(Component)]
@@ -91,6 +196,18 @@ pub struct CampEnemies{
 #[derive(Component)]
 pub struct CampStatus(pub bool); // true if camp is captured, false if not
 
+/// The player holding a camp, and a rival standing in it trying to take it over.
+/// Progress is only tracked by the host
+#[derive(Component, Default)]
+pub struct CampOwner {
+    pub owner: Option<u8>,
+    pub contester: Option<u8>,
+    pub progress: u8,
+}
+
+#[derive(Component)]
+pub struct CampDecoration(pub u8); // holds id of the decoration's camp
+
 #[derive(Component)]
 pub struct EnemyCamp(pub u8); // holds id of enemy's parent camp
 
@@ -98,6 +215,68 @@ pub struct EnemyCamp(pub u8); // holds id of enemy's parent camp
 pub struct ItemChest{
     pub id: u8,
     pub contents: [u8; CHEST_CONTENTS],
+    pub trapped: bool, // poisons whoever opens it
+    pub item: ItemType,
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ledger_shares_add_up_per_player() {
+        let mut ledger = DamageLedger::default();
+        ledger.record(2, 30);
+        ledger.record(5, 10);
+        ledger.record(2, 0);
+        ledger.record(2, 20);
+        assert_eq!(ledger.total(), 60);
+        assert_eq!(ledger.0.len(), 2);
+        assert!((ledger.share(2) - 50. / 60.).abs() < 1e-6);
+        assert!((ledger.share(5) - 10. / 60.).abs() < 1e-6);
+        assert_eq!(ledger.share(7), 0.);
+        assert_eq!(DamageLedger::default().share(2), 0.);
+    }
+
+    #[test]
+    fn ledger_assists_need_the_assist_share() {
+        let mut ledger = DamageLedger::default();
+        ledger.record(1, 50);
+        ledger.record(2, 25);  // exactly ASSIST_SHARE of 100
+        ledger.record(3, 24);
+        ledger.record(4, 1);
+        assert_eq!(ledger.top(), Some(1));
+        assert_eq!(ledger.assists(1), vec![2]);
+        // the killer never assists themselves
+        assert_eq!(ledger.assists(2), vec![1]);
+        assert!(DamageLedger::default().assists(1).is_empty());
+    }
+
+    #[test]
+    fn ledger_ties_go_to_the_lowest_id() {
+        let mut ledger = DamageLedger::default();
+        assert_eq!(ledger.top(), None);
+        ledger.record(6, 40);
+        ledger.record(3, 40);
+        ledger.record(9, 40);
+        assert_eq!(ledger.top(), Some(3));
+        ledger.record(9, 1);
+        assert_eq!(ledger.top(), Some(9));
+    }
+
+    #[test]
+    fn ledger_merge_adds_to_existing_players() {
+        let mut camp = DamageLedger::default();
+        camp.record(1, 10);
+        let mut enemy = DamageLedger::default();
+        enemy.record(1, 5);
+        enemy.record(2, 30);
+        camp.merge(&enemy);
+        assert_eq!(camp.0, vec![(1, 15), (2, 30)]);
+        assert_eq!(camp.top(), Some(2));
+        camp.clear();
+        assert_eq!((camp.total(), camp.top()), (0, None));
+    }
+}
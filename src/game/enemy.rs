@@ -9,7 +9,12 @@ use crate::net::{is_client, is_host, TickNum};
 use crate::game::components::PowerUpType;
 use crate::game::map::{Biome, TILESIZE, MAPSIZE, WorldMap};
 use crate::game::movement;
-use crate::game::player::{LocalPlayer, LocalPlayerDeathEvent, LocalPlayerSpawnEvent, PLAYER_DEFAULT_DEF, PLAYER_DEFAULT_HP, PlayerShield};
+use crate::game::player::{LocalPlayer, LocalPlayerDeathEvent, LocalPlayerSpawnEvent, PLAYER_DEFAULT_DEF, PLAYER_DEFAULT_HP, PlayerDash, PlayerShield, block_hit};
+use crate::game::item::{random_item, spawn_item_pickup};
+use crate::game::powerup::PowerUpConfig;
+use crate::game::mode::{GameMode, ScoreEvent};
+use crate::game::status::{spawn_status_icons, StatusEffects, StatusType, STATUS_TICKS};
+use crate::game::respawn::Respawn;
 use std::collections::{BinaryHeap, HashMap};
 use std::cmp::Ordering;
 use bevy::ecs::component::Tick;
@@ -55,6 +60,20 @@ pub struct IsSpecial(bool);
 #[derive(Component)]
 pub struct SpawnPosition(pub Vec2);
 
+/// Status applied to players hit by this enemy's attack
+#[derive(Component)]
+pub struct InflictsStatus(pub Option<StatusType>);
+
+/// which status an enemy of the given camp grade attacks with
+fn grade_status(grade: i32) -> Option<StatusType> {
+    match grade {
+        2 => Some(StatusType::Poison),
+        3 => Some(StatusType::Slow),
+        4 => Some(StatusType::Burn),
+        _ => None,
+    }
+}
+
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin{
@@ -133,6 +152,9 @@ pub fn spawn_enemy(
         SpawnEnemyWeaponTimer(Timer::from_seconds(enemy_attack_rate, TimerMode::Once)),//add a timer to spawn the enemy attack very 4 seconds
         EnemyRegenTimer(Timer::from_seconds(1.0, TimerMode::Repeating)),
         IsSpecial(is_special),
+        (StatusEffects::default(),
+        InflictsStatus(grade_status(sprite)),
+        DamageLedger::default()),
     )).id();
     if is_special {
         let special_entity = commands.spawn(SpriteBundle {
@@ -142,6 +164,7 @@ pub fn spawn_enemy(
         }).id();
         commands.entity(enemy_entity).add_child(special_entity);
     }
+    spawn_status_icons(commands, enemy_entity);
 }
 
 pub fn remove_enemies(mut commands: Commands, enemies: Query<Entity, With<Enemy>>) {
@@ -201,16 +224,17 @@ pub fn attack_simulate(
     asset_server: Res<AssetServer>,
     time: Res<Time>,
     tick: Res<TickNum>,
-    mut query_enemies: Query<(Entity, &Health, &mut EventBuffer, &Transform, &mut SpawnEnemyWeaponTimer, &Aggro, &IsSpecial), (With<Enemy>, Without<Player>)>,
-    mut player_query: Query<(&Transform, &mut HpBuffer, &StoredPowerUps, &PlayerShield), With<Player>>
+    mut query_enemies: Query<(Entity, &Health, &mut EventBuffer, &Transform, &mut SpawnEnemyWeaponTimer, &Aggro, &IsSpecial, &InflictsStatus, &mut StatusEffects), (With<Enemy>, Without<Player>)>,
+    mut player_query: Query<(&Transform, &mut HpBuffer, &StoredPowerUps, &PlayerShield, &PlayerDash, &mut Stamina, &mut StatusEffects, &mut Stats, &Respawn), With<Player>>,
+    powerup_config: Res<PowerUpConfig>,
 ) {
-    for (enemy_entity, enemy_hp, mut enemy_eb, enemy_transform, mut spawn_timer, aggro, is_special) in query_enemies.iter_mut() {
-        if enemy_hp.current <= 0 || aggro.0 == None { continue; }
+    for (enemy_entity, enemy_hp, mut enemy_eb, enemy_transform, mut spawn_timer, aggro, is_special, inflicts, mut enemy_effects) in query_enemies.iter_mut() {
+        if enemy_hp.current <= 0 || aggro.0 == None || enemy_effects.active(StatusType::Stun) { continue; }
         if spawn_timer.0.finished() {
             spawn_timer.0.reset();
             let events = enemy_eb.0.get(tick.0).unwrap_or(0);
             enemy_eb.0.set(tick.0, Some(events | ATTACK_BITFLAG));
-            for (player_transform, mut player_hp, player_power_ups, shield) in player_query.iter_mut() {
+            for (player_transform, mut player_hp, player_power_ups, shield, dash, mut stamina, mut effects, mut stats, respawn) in player_query.iter_mut() {
                 let circle_radius;
                 if is_special.0 {
                     circle_radius = CIRCLE_RADIUS * SPECIAL_ATTACK_RADIUS_MOD;
@@ -219,12 +243,20 @@ pub fn attack_simulate(
                 }
                 if player_transform.translation.distance(enemy_transform.translation) < circle_radius {
                     // must check if damage reduction is greater than damage dealt, otherwise subtraction overflow or player will gain health
-                    if shield.active { continue }
-                    // Multiply enemy's damage value by player's default defense and DAMAGE_REDUCTION_UP ^ stacks of damage reduction
-                    let dmg: u8 = (CIRCLE_DAMAGE as f32 * PLAYER_DEFAULT_DEF * DAMAGE_REDUCTION_UP.powf(player_power_ups.power_ups[PowerUpType::DamageReductionUp as usize] as f32)) as u8;
+                    if dash.invulnerable() || respawn.protected() { continue }
+                    if shield.active {
+                        block_hit(&mut commands, &asset_server, tick.0, shield, &mut stamina, &mut effects, &mut enemy_effects);
+                        continue;
+                    }
+                    // Multiply enemy's damage value by player's default defense and damage reduction multiplier
+                    let dmg: u8 = (CIRCLE_DAMAGE as f32 * enemy_effects.damage_mult() * PLAYER_DEFAULT_DEF * powerup_config.defense_mult(player_power_ups)) as u8;
                     if dmg > 0 {
-                        let hp = player_hp.0.get(tick.0).unwrap_or(PLAYER_DEFAULT_HP).saturating_sub(dmg);
-                        player_hp.0.set(tick.0, Some(hp));
+                        let prev_hp = player_hp.0.get(tick.0).unwrap_or(PLAYER_DEFAULT_HP);
+                        player_hp.0.set(tick.0, Some(prev_hp.saturating_sub(dmg)));
+                        stats.record_hurt(dmg.min(prev_hp));
+                    }
+                    if let Some(status) = inflicts.0 {
+                        effects.apply(status, STATUS_TICKS[status as usize]);
                     }
                     commands.spawn(AudioBundle {
                         source: asset_server.load("playerHurt.ogg"),
@@ -239,12 +271,13 @@ pub fn attack_simulate(
 pub fn update_enemies(
     mut commands: Commands,
     tick: Res<TickNum>,
-    mut enemies: Query<(&mut Health, &HpBuffer, &LastAttacker, &StoredPowerUps, &mut TextureAtlasSprite, &Transform, &EnemyCamp, &ChanceDropPWU, &mut Visibility), With<Enemy>>,
+    mut enemies: Query<(&mut Health, &HpBuffer, &mut LastAttacker, &mut DamageLedger, &StoredPowerUps, &mut TextureAtlasSprite, &Transform, &EnemyCamp, &ChanceDropPWU, &IsSpecial, &mut Visibility), With<Enemy>>,
     mut player: Query<(&mut Stats, &Player)>,
     powerup_atlas: Res<PowerupAtlas>,
-    mut camp_query: Query<(&Camp, &mut CampEnemies, &CampStatus), With<Camp>>,
+    mut camp_query: Query<(&Camp, &mut CampEnemies, &CampStatus, &mut DamageLedger, &mut CampOwner), (With<Camp>, Without<Enemy>)>,
+    game_mode: Res<GameMode>,
 ) {
-    for (mut hp, hb, la, spu, mut sp, tf, ec_num, cdpu, mut vis) in enemies.iter_mut() {
+    for (mut hp, hb, mut la, mut ledger, spu, mut sp, tf, ec_num, cdpu, is_special, mut vis) in enemies.iter_mut() {
         let next_hp = hb.0.get(tick.0);
         if next_hp.is_none() { continue }
         hp.current = next_hp.unwrap();
@@ -269,32 +302,55 @@ pub fn update_enemies(
                     }
                 }
             }
+            // special enemies are camp bosses and always drop an item
+            if is_special.0 {
+                spawn_item_pickup(&mut commands, random_item(), tf.translation.truncate());
+            }
+            // whoever did the most damage gets the kill, the last hit doesn't count for more.
+            // enemies that died to something other than a player give no credit
+            let killer = ledger.top();
+            let assists = killer.map_or(Vec::new(), |killer| ledger.assists(killer));
+
             // decrement the enemy counter of the camp that this enemy is apart of
-            for (camp_num, mut enemies_in_camp, camp_status) in camp_query.iter_mut() {
-                if camp_num.0 == ec_num.0 {
-                    enemies_in_camp.current_enemies = enemies_in_camp.current_enemies.saturating_sub(1);
-                }
+            for (camp_num, mut enemies_in_camp, camp_status, mut camp_ledger, mut control) in camp_query.iter_mut() {
+                if camp_num.0 != ec_num.0 { continue }
+                enemies_in_camp.current_enemies = enemies_in_camp.current_enemies.saturating_sub(1);
+                camp_ledger.merge(&ledger);
 
-                // check if the camp is cleared and assign 5 points for clearing the camp
+                // check if the camp is cleared and split the clear bonus by damage dealt to the camp.
+                // whoever did the most damage takes ownership of it
                 if enemies_in_camp.current_enemies == 0 && camp_status.0 == true{
+                    let captor = camp_ledger.top();
+                    if captor.is_some() {
+                        control.owner = captor;
+                        control.contester = None;
+                        control.progress = 0;
+                    }
                     for (mut stats, pl) in player.iter_mut() {
-                        if pl.0 == la.0.expect("camp has no attacker") {
-                            stats.score = stats.score.saturating_add(5);
+                        game_mode.award(&mut stats, ScoreEvent::CampClear, camp_ledger.share(pl.0));
+                        if captor == Some(pl.0) {
                             stats.camps_captured = stats.camps_captured.saturating_add(1);
                         }
                     }
+                    camp_ledger.clear();
                 }
             }
 
-            // kill the enemy and increment the score of the player who killed it
+            // kill the enemy and increment the score of the player who killed it and of anyone who helped
             hp.dead = true;
             *vis = Visibility::Hidden;
             for (mut stats, pl) in player.iter_mut() {
-                if pl.0 == la.0.expect("died with no attacker?") {
-                    stats.score = stats.score.saturating_add(1);
+                if killer == Some(pl.0) {
+                    game_mode.award(&mut stats, ScoreEvent::EnemyKill, 1.0);
                     stats.enemies_killed = stats.enemies_killed.saturating_add(1);
                 }
+                else if assists.contains(&pl.0) {
+                    game_mode.award(&mut stats, ScoreEvent::EnemyAssist, 1.0);
+                    stats.assists = stats.assists.saturating_add(1);
+                }
             }
+            la.0 = None;
+            ledger.clear();
             continue;
         }
         let damage = hp.current as f32 / hp.max as f32;
@@ -356,23 +412,25 @@ pub fn fixed_aggro(
 
 pub fn fixed_move(
     tick: Res<net::TickNum>,
-    mut enemies: Query<(&mut PosBuffer, &Aggro, &SpawnPosition), (With<Enemy>, Without<Player>)>,
+    mut enemies: Query<(&mut PosBuffer, &Aggro, &SpawnPosition, &StatusEffects), (With<Enemy>, Without<Player>)>,
     players: Query<(&Player, &PosBuffer), (With<Player>, Without<Enemy>)>,
     map: Res<WorldMap>
 ) {
-    for (mut epb, aggro, spawn_pos) in &mut enemies {
+    for (mut epb, aggro, spawn_pos, effects) in &mut enemies {
         let prev = epb.0.get(tick.0.wrapping_sub(1));
         if prev.is_none() { continue }
         let prev = prev.unwrap();
         let mut next = prev.clone();
 
         'mov: {
+            // staggered enemies hold their position
+            if effects.active(StatusType::Stun) { break 'mov }
             if aggro.0.is_none() {
                 // move the enemy to their spawn position
                 let displacement = spawn_pos.0 - prev;
                 if !(displacement.length() < CIRCLE_RADIUS) {
                     let posit = find_next(&map.biome_map, prev, spawn_pos.0);
-                    let movement = (posit - prev).normalize() * ENEMY_SPEED;
+                    let movement = (posit - prev).normalize() * ENEMY_SPEED * effects.speed_mult();
                     next += movement;
                 }
             } else {
@@ -389,7 +447,7 @@ pub fn fixed_move(
                 let displacement = player_pos - prev;
                 if !(displacement.length() < CIRCLE_RADIUS) {
                     let posit = find_next(&map.biome_map, prev, player_pos);
-                    let movement = (posit - prev).normalize() * ENEMY_SPEED;
+                    let movement = (posit - prev).normalize() * ENEMY_SPEED * effects.speed_mult();
                     next += movement;
                 }
             }
@@ -697,14 +755,15 @@ pub fn health_simulate(
 pub fn handle_packet(
     tick: Res<TickNum>,
     mut enemy_reader: EventReader<net::packets::EnemyTickEvent>,
-    mut enemy_query: Query<(Entity, &Enemy, &mut PosBuffer, &mut HpBuffer, &mut EventBuffer, &IsSpecial)>,
+    mut enemy_query: Query<(Entity, &Enemy, &mut PosBuffer, &mut HpBuffer, &mut EventBuffer, &IsSpecial, &mut StatusEffects)>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
 ) {
     for ev in enemy_reader.iter() {
-        for (e, en, mut pb, mut hb, mut eb, is) in &mut enemy_query {
+        for (e, en, mut pb, mut hb, mut eb, is, mut effects) in &mut enemy_query {
             if en.0 == ev.tick.id {
                 pb.0.set(ev.seq_num, Some(ev.tick.pos));
+                *effects = StatusEffects::unpack(ev.tick.status);
                 hb.0.set(tick.0, Some(ev.tick.hp));
                 eb.0.set(tick.0, Some(ev.tick.events));
                 if ev.tick.events & ATTACK_BITFLAG != 0 {
@@ -0,0 +1,434 @@
+use bevy::prelude::*;
+use rand::Rng;
+use crate::{AppState, net};
+use crate::game::buffers::*;
+use crate::game::components::*;
+use crate::game::enemy::LastAttacker;
+use crate::game::map::{Biome, WorldMap, get_tile_at_pos};
+use crate::game::movement::KeyBinds;
+use crate::game::player::{self, LocalPlayer, PLAYER_DEFAULT_HP};
+use crate::game::status::{StatusEffects, StatusType, STATUS_TICKS};
+use crate::net::{is_host, IsHost, TickNum};
+
+pub const NUM_ITEM_TYPES: usize = 5;
+pub const HOTBAR_SLOTS: usize = 4;
+/// the item used on a tick is stored in these bits of the event buffer as ItemType + 1
+pub const ITEM_MASK: u8 = 0b0111_0000;
+pub const ITEM_SHIFT: u8 = 4;
+pub const NO_ITEM: u8 = 0;
+const PICKUP_RANGE: f32 = 32.;
+const ITEM_SIZE: Vec2 = Vec2 { x: 12., y: 12. };
+const POTION_HEAL: u8 = 40;
+const BOMB_RADIUS: f32 = 96.;
+const BOMB_DAMAGE: u8 = 60;
+pub const TONIC_SPEED_MULT: f32 = 1.5;
+const TONIC_TICKS: u8 = 50;
+const TELEPORT_DISTANCE: f32 = 480.;
+const TELEPORT_SLACK: f32 = player::PLAYER_SPEED * TONIC_SPEED_MULT * net::TICKLEN_S; // the player can walk on the tick they teleport
+const MAP_REVEAL_TICKS: u8 = 200;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ItemType {
+    Potion = 0,
+    Bomb,
+    SpeedTonic,
+    TeleportScroll,
+    CampMap,
+}
+
+impl ItemType {
+    pub fn from_u8(id: u8) -> Option<ItemType> {
+        match id {
+            0 => Some(ItemType::Potion),
+            1 => Some(ItemType::Bomb),
+            2 => Some(ItemType::SpeedTonic),
+            3 => Some(ItemType::TeleportScroll),
+            4 => Some(ItemType::CampMap),
+            _ => None,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            ItemType::Potion => Color::CRIMSON,
+            ItemType::Bomb => Color::DARK_GRAY,
+            ItemType::SpeedTonic => Color::AQUAMARINE,
+            ItemType::TeleportScroll => Color::VIOLET,
+            ItemType::CampMap => Color::BEIGE,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ItemType::Potion => "Potion",
+            ItemType::Bomb => "Bomb",
+            ItemType::SpeedTonic => "Tonic",
+            ItemType::TeleportScroll => "Scroll",
+            ItemType::CampMap => "Map",
+        }
+    }
+}
+
+/// packs an item into the event buffer bits
+pub fn encode_item(item: ItemType) -> u8 {
+    (item as u8 + 1) << ITEM_SHIFT
+}
+
+/// reads the item used on a tick out of the event buffer bits
+pub fn decode_item(events: u8) -> Option<ItemType> {
+    let id = (events & ITEM_MASK) >> ITEM_SHIFT;
+    if id == NO_ITEM { return None }
+    ItemType::from_u8(id - 1)
+}
+
+/// A player's hotbar and the timed effects of items they've used.
+/// Owned by the host and copied to clients every tick
+#[derive(Component, Clone, Default)]
+pub struct Inventory {
+    pub slots: [Option<ItemType>; HOTBAR_SLOTS],
+    pub speed_ticks: u8,
+    pub reveal_ticks: u8,
+}
+
+impl Inventory {
+    /// puts an item in the first empty slot, returns false if the hotbar is full
+    pub fn add(&mut self, item: ItemType) -> bool {
+        let slot = self.slots.iter_mut().find(|slot| slot.is_none());
+        if slot.is_none() { return false }
+        *slot.unwrap() = Some(item);
+        true
+    }
+
+    /// removes the first slot holding the item, returns false if there wasn't one
+    pub fn take(&mut self, item: ItemType) -> bool {
+        let slot = self.slots.iter_mut().find(|slot| **slot == Some(item));
+        if slot.is_none() { return false }
+        *slot.unwrap() = None;
+        true
+    }
+
+    pub fn speed_mult(&self) -> f32 {
+        if self.speed_ticks > 0 { TONIC_SPEED_MULT } else { 1. }
+    }
+}
+
+/// An item lying on the ground waiting to be picked up
+#[derive(Component)]
+pub struct ItemPickup(pub ItemType);
+
+/// Marks a hotbar slot in the HUD
+#[derive(Component)]
+pub struct HotbarSlot(pub usize);
+
+/// Marks the text inside a hotbar slot
+#[derive(Component)]
+pub struct HotbarText(pub usize);
+
+#[derive(Event)]
+pub struct ItemUseEvent {
+    pub seq_num: u16,
+    pub id: u8,
+    pub item: ItemType,
+}
+
+pub struct ItemPlugin;
+
+pub fn remove_item_pickups(mut commands: Commands, items: Query<Entity, With<ItemPickup>>) {
+    for e in items.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+}
+
+impl Plugin for ItemPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (
+                item_input,
+                item_feedback,
+            ).run_if(in_state(AppState::Game)))
+            .add_systems(FixedUpdate, (
+                item_host.before(item_simulate),
+                item_simulate.before(player::attack_simulate),
+                item_grab_simulate,
+                item_tick.after(item_simulate),
+            ).run_if(in_state(AppState::Game)).run_if(is_host).before(net::host::fixed))
+            .add_systems(FixedUpdate, item_draw.run_if(in_state(AppState::Game)))
+            .add_systems(OnExit(AppState::GameOver), remove_item_pickups)
+            .init_resource::<Events<ItemUseEvent>>();
+    }
+}
+
+/// spawns an item on the ground, only the host's pickups are real, clients mirror them from HostTick
+pub fn spawn_item_pickup(commands: &mut Commands, item: ItemType, pos: Vec2) {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: item.color(),
+                custom_size: Some(ITEM_SIZE),
+                ..default()
+            },
+            transform: Transform::from_xyz(pos.x, pos.y, 1.),
+            ..default()
+        },
+        ItemPickup(item),
+    ));
+}
+
+pub fn random_item() -> ItemType {
+    ItemType::from_u8(rand::thread_rng().gen_range(0..NUM_ITEM_TYPES as u8)).unwrap()
+}
+
+/// number keys use the matching hotbar slot. the host decides whether the use went through,
+/// but clients clear the slot right away so it can't be sent twice before the host answers
+pub fn item_input(
+    tick: Res<TickNum>,
+    is_host: Res<IsHost>,
+    keyboard_input: Res<Input<KeyCode>>,
+    key_binds: Res<KeyBinds>,
+    map: Res<WorldMap>,
+    mut players: Query<(&mut Inventory, &mut EventBuffer, &mut Transform, &DirBuffer, &Health, &StatusEffects), With<LocalPlayer>>,
+) {
+    let player = players.get_single_mut();
+    if player.is_err() { return }
+    let (mut inv, mut eb, mut tf, db, hp, effects) = player.unwrap();
+    if hp.dead || effects.active(StatusType::Stun) { return }
+    let slot = key_binds.items.iter().position(|key| keyboard_input.just_pressed(*key));
+    if slot.is_none() { return }
+    let item = inv.slots[slot.unwrap()];
+    if item.is_none() { return }
+    let item = item.unwrap();
+    let events = eb.0.get(tick.0).unwrap_or(0);
+    if events & ITEM_MASK != 0 { return } // one item per tick
+
+    // positions are client authoritative, so the teleport happens here
+    if item == ItemType::TeleportScroll {
+        let angle = db.0.get(tick.0).or(*db.0.get(tick.0.wrapping_sub(1)));
+        if angle.is_none() { return }
+        let angle = angle.unwrap();
+        let target = tf.translation + Vec3::new(angle.cos(), angle.sin(), 0.) * TELEPORT_DISTANCE;
+        if get_tile_at_pos(&target, &map.biome_map) == Biome::Wall { return }
+        tf.translation = target;
+    }
+    eb.0.set(tick.0, Some(events | encode_item(item)));
+    if !is_host.0 {
+        inv.slots[slot.unwrap()] = None;
+    }
+}
+
+pub fn item_host(
+    players: Query<&EventBuffer, With<LocalPlayer>>,
+    tick: Res<TickNum>,
+    mut item_writer: EventWriter<ItemUseEvent>
+) {
+    let eb = players.get_single();
+    if eb.is_err() { return }
+    let events = eb.unwrap().0.get(tick.0);
+    if events.is_none() { return }
+    let item = decode_item(events.unwrap());
+    if item.is_none() { return }
+    item_writer.send(ItemUseEvent {
+        seq_num: tick.0,
+        id: 0,
+        item: item.unwrap(),
+    });
+}
+
+/// consumes items from the host's copy of the inventory and applies them.
+/// uses of items the player doesn't have are removed from the event buffer
+pub fn item_simulate(
+    tick: Res<TickNum>,
+    mut item_reader: EventReader<ItemUseEvent>,
+    mut players: Query<(&Player, &mut Inventory, &mut EventBuffer, &mut PosBuffer, &mut HpBuffer, &Health, &mut Stats)>,
+    mut enemies: Query<(&PosBuffer, &mut HpBuffer, &mut LastAttacker, &mut DamageLedger, &mut StatusEffects), (With<Enemy>, Without<Player>)>,
+    mut chests: Query<(&Transform, &mut Health), (With<ItemChest>, Without<Player>, Without<Enemy>)>,
+    map: Res<WorldMap>,
+) {
+    for ev in &mut item_reader {
+        for (pl, mut inv, mut eb, mut pb, mut hb, hp, mut stats) in &mut players {
+            if pl.0 != ev.id { continue }
+            // bombs go off where the player is and teleports are checked against where they were,
+            // so an item that can't be placed isn't used up
+            let pos = *pb.0.get(ev.seq_num);
+            let last_pos = *pb.0.get(ev.seq_num.wrapping_sub(1));
+            let placed = match ev.item {
+                ItemType::Bomb => pos.is_some(),
+                ItemType::TeleportScroll => pos.is_some() && last_pos.is_some(),
+                _ => true,
+            };
+            if hp.dead || !placed || !inv.take(ev.item) {
+                let events = eb.0.get(ev.seq_num).unwrap_or(0);
+                eb.0.set(ev.seq_num, Some(events & !ITEM_MASK));
+                continue;
+            }
+            match ev.item {
+                ItemType::Potion => {
+                    let cur = hb.0.get(tick.0).unwrap_or(0);
+                    hb.0.set(tick.0, Some(cur.saturating_add(POTION_HEAL).min(PLAYER_DEFAULT_HP)));
+                }
+                ItemType::Bomb => {
+                    let pos = pos.unwrap();
+                    for (enemy_pb, mut enemy_hb, mut last_attacker, mut ledger, mut effects) in &mut enemies {
+                        let enemy_pos = enemy_pb.0.get(tick.0);
+                        let enemy_hp = *enemy_hb.0.get(tick.0);
+                        if enemy_pos.is_none() || enemy_hp.is_none() { continue }
+                        if enemy_hp.unwrap() == 0 || enemy_pos.unwrap().distance(pos) > BOMB_RADIUS { continue }
+                        last_attacker.0 = Some(pl.0);
+                        ledger.record(pl.0, enemy_hp.unwrap().min(BOMB_DAMAGE));
+                        stats.record_damage(enemy_hp.unwrap().min(BOMB_DAMAGE));
+                        enemy_hb.0.set(tick.0, Some(enemy_hp.unwrap().saturating_sub(BOMB_DAMAGE)));
+                        effects.apply(StatusType::Burn, STATUS_TICKS[StatusType::Burn as usize]);
+                    }
+                    for (chest_tf, mut chest_hp) in &mut chests {
+                        if chest_hp.current > 0 && chest_tf.translation.truncate().distance(pos) <= BOMB_RADIUS {
+                            chest_hp.current = 0;
+                            stats.chests_opened = stats.chests_opened.saturating_add(1);
+                        }
+                    }
+                }
+                ItemType::SpeedTonic => inv.speed_ticks = TONIC_TICKS,
+                ItemType::TeleportScroll => {
+                    // the client moves itself, the host only keeps it from going too far or into a wall
+                    let (from, to) = (last_pos.unwrap(), pos.unwrap());
+                    let mut target = from + (to - from).clamp_length_max(TELEPORT_DISTANCE + TELEPORT_SLACK);
+                    if get_tile_at_pos(&target.extend(0.), &map.biome_map) == Biome::Wall {
+                        target = from;
+                    }
+                    if target != to {
+                        pb.0.set(ev.seq_num, Some(target));
+                    }
+                }
+                ItemType::CampMap => inv.reveal_ticks = MAP_REVEAL_TICKS,
+            }
+        }
+    }
+}
+
+pub fn item_tick(mut players: Query<&mut Inventory>) {
+    for mut inv in &mut players {
+        inv.speed_ticks = inv.speed_ticks.saturating_sub(1);
+        inv.reveal_ticks = inv.reveal_ticks.saturating_sub(1);
+    }
+}
+
+/// if a player walks over an item and has a free slot, put it in their hotbar
+pub fn item_grab_simulate(
+    mut commands: Commands,
+    tick: Res<TickNum>,
+    mut players: Query<(&PosBuffer, &Health, &mut Inventory), With<Player>>,
+    pickups: Query<(Entity, &Transform, &ItemPickup)>,
+) {
+    for (pickup_entity, pickup_tf, pickup) in &pickups {
+        for (pb, hp, mut inv) in &mut players {
+            if hp.dead { continue }
+            let pos = pb.0.get(tick.0);
+            if pos.is_none() { continue }
+            if pos.unwrap().distance(pickup_tf.translation.truncate()) > PICKUP_RANGE { continue }
+            if inv.add(pickup.0) {
+                commands.entity(pickup_entity).despawn();
+                break;
+            }
+        }
+    }
+}
+
+/// plays effects for items used by any player
+pub fn item_draw(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    tick: Res<TickNum>,
+    players: Query<(Entity, &EventBuffer, Option<&LocalPlayer>)>,
+) {
+    for (e, eb, lp) in &players {
+        let tick = if lp.is_some() { tick.0 } else { tick.0.saturating_sub(net::DELAY) };
+        let item = decode_item(eb.0.get(tick).unwrap_or(0));
+        if item.is_none() { continue }
+        match item.unwrap() {
+            ItemType::Bomb => {
+                let blast = commands.spawn((
+                    SpriteBundle {
+                        texture: asset_server.load("EnemyAttack01.png"),
+                        sprite: Sprite {
+                            color: Color::ORANGE,
+                            ..default()
+                        },
+                        transform: Transform {
+                            translation: Vec3::new(0., 0., 5.),
+                            scale: Vec3::new(BOMB_RADIUS / 64., BOMB_RADIUS / 64., 1.),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    Fade { current: 0.5, max: 0.5 },
+                )).id();
+                commands.entity(e).add_child(blast);
+                commands.spawn(AudioBundle {
+                    source: asset_server.load("hitHurt.ogg"),
+                    ..default()
+                });
+            }
+            _ => {
+                if lp.is_some() {
+                    commands.spawn(AudioBundle {
+                        source: asset_server.load("powerup.ogg"),
+                        ..default()
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// shows the local player's items in the HUD hotbar
+pub fn item_feedback(
+    players: Query<&Inventory, With<LocalPlayer>>,
+    mut slots: Query<(&mut BackgroundColor, &HotbarSlot)>,
+    mut texts: Query<(&mut Text, &HotbarText)>,
+) {
+    let inv = players.get_single();
+    if inv.is_err() { return }
+    let inv = inv.unwrap();
+    for (mut color, slot) in &mut slots {
+        *color = match inv.slots[slot.0] {
+            Some(item) => item.color().with_a(0.6).into(),
+            None => Color::rgba(0.15, 0.15, 0.15, 0.6).into(),
+        };
+    }
+    for (mut text, slot) in &mut texts {
+        text.sections[1].value = match inv.slots[slot.0] {
+            Some(item) => item.name().to_string(),
+            None => String::new(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::player::{ATTACK_BITFLAG, DASH_BITFLAG, SHIELD_BITFLAG, SPAWN_BITFLAG};
+
+    #[test]
+    fn items_round_trip_through_event_bits() {
+        let flags = ATTACK_BITFLAG | SPAWN_BITFLAG | SHIELD_BITFLAG | DASH_BITFLAG;
+        assert_eq!(decode_item(flags), None);
+        for id in 0..NUM_ITEM_TYPES as u8 {
+            let item = ItemType::from_u8(id).unwrap();
+            let events = encode_item(item) | flags;
+            assert_eq!(encode_item(item) & flags, 0);
+            assert_eq!(decode_item(events), Some(item));
+        }
+        assert_eq!(ItemType::from_u8(NUM_ITEM_TYPES as u8), None);
+    }
+
+    #[test]
+    fn hotbar_fills_and_empties_by_slot() {
+        let mut inv = Inventory::default();
+        for _ in 0..HOTBAR_SLOTS {
+            assert!(inv.add(ItemType::Bomb));
+        }
+        assert!(!inv.add(ItemType::Potion));
+        assert!(!inv.take(ItemType::Potion));
+        assert!(inv.take(ItemType::Bomb));
+        assert_eq!(inv.slots[0], None);
+        assert!(inv.add(ItemType::Potion));
+        assert_eq!(inv.slots[0], Some(ItemType::Potion));
+    }
+}
@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+use crate::AppState;
+use crate::game::profile::PlayerProfiles;
+use crate::net::{self, is_host, TickNum};
+
+pub const KILL_FEED_LEN: usize = 5; // most kills shown at once
+pub const KILL_FEED_TICKS: u16 = 60; // how long a kill stays up
+
+/// One player killing another
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct Kill {
+    pub tick: u16,
+    pub killer: u8,
+    pub victim: u8,
+}
+
+/// The most recent kills, the host records them and sends the whole feed out with every tick
+/// so a dropped packet doesn't lose one
+#[derive(Resource, Default)]
+pub struct KillFeed(pub Vec<Kill>);
+
+impl KillFeed {
+    pub fn record(&mut self, tick: u16, killer: u8, victim: u8) {
+        self.0.push(Kill { tick, killer, victim });
+        if self.0.len() > KILL_FEED_LEN {
+            self.0.remove(0);
+        }
+    }
+}
+
+/// Lists recent kills in the corner of the screen
+#[derive(Component)]
+pub struct KillFeedDisplay;
+
+pub struct KillFeedPlugin;
+
+impl Plugin for KillFeedPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Game), reset_kill_feed)
+            .add_systems(FixedUpdate, expire_kills
+                .run_if(in_state(AppState::Game)).run_if(is_host).before(net::host::fixed))
+            .add_systems(Update, kill_feed_display.run_if(in_state(AppState::Game)))
+            .init_resource::<KillFeed>();
+    }
+}
+
+pub fn reset_kill_feed(mut kill_feed: ResMut<KillFeed>) {
+    kill_feed.0.clear();
+}
+
+pub fn expire_kills(
+    tick: Res<TickNum>,
+    mut kill_feed: ResMut<KillFeed>,
+) {
+    kill_feed.0.retain(|kill| tick.0.saturating_sub(kill.tick) < KILL_FEED_TICKS);
+}
+
+/// a line per kill with both names in their player's color
+pub fn kill_feed_display(
+    kill_feed: Res<KillFeed>,
+    profiles: Res<PlayerProfiles>,
+    mut kill_feed_displays: Query<&mut Text, With<KillFeedDisplay>>,
+) {
+    if !kill_feed.is_changed() && !profiles.is_changed() { return }
+    for mut text in &mut kill_feed_displays {
+        let style = text.sections[0].style.clone();
+        let mut sections = vec![TextSection::new("", style.clone())];
+        for kill in &kill_feed.0 {
+            sections.push(TextSection::new(profiles.name(kill.killer), TextStyle { color: profiles.color(kill.killer), ..style.clone() }));
+            sections.push(TextSection::new(" killed ", style.clone()));
+            sections.push(TextSection::new(format!("{}\n", profiles.name(kill.victim)), TextStyle { color: profiles.color(kill.victim), ..style.clone() }));
+        }
+        text.sections = sections;
+    }
+}
@@ -7,6 +7,7 @@ use rand_chacha::{rand_core::SeedableRng,ChaChaRng};
 use crate::noise::Perlin;
 use crate::AppState;
 use crate::menus::components::{NumCampsInput, MapSeedInput};
+use crate::game::mode::GameMode;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Biome{
@@ -80,9 +81,24 @@ impl Plugin for MapPlugin {
         app.add_systems(OnExit(AppState::Hosting), set_seed);
         app.add_systems(OnExit(AppState::Hosting), set_num_camps);
         app.add_systems(OnEnter(AppState::Game), setup_map);
+        app.add_systems(OnExit(AppState::GameOver), remove_map);
     }
 }
 
+// Despawn the tiles and forget the camp and chest spots so a rematch can generate a new map
+fn remove_map(
+    mut commands: Commands,
+    tiles: Query<Entity, Or<(With<Ground>, With<Wall>, With<Camp>, With<Path>)>>,
+    mut camp_nodes: ResMut<CampNodes>,
+    mut chest_coords: ResMut<ChestCoords>,
+) {
+    for e in tiles.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+    camp_nodes.0.clear();
+    chest_coords.0.clear();
+}
+
 // calculate the euclidean distance between two points
 fn euclidean_distance(a: Vec2, b: Vec2) -> f32 {
     (a - b).length()
@@ -106,6 +122,32 @@ fn refine_coordinates(coords: &mut Vec<Vec2>) {
     *coords = new_coords;
 }
 
+// Find the tile closest to center, searching outward a ring at a time, that has no wall within
+// radius tiles of it. Falls back to center if there isn't one
+fn nearest_open_tile(map: &WorldMap, center: Vec2, radius: usize) -> Vec2 {
+    let (center_row, center_col) = (center.y as i32, center.x as i32);
+    let radius = radius as i32;
+    let open = |row: i32, col: i32| -> bool {
+        if row < radius || col < radius || row + radius >= MAPSIZE as i32 || col + radius >= MAPSIZE as i32 {
+            return false;
+        }
+        (row - radius..=row + radius).all(|r| (col - radius..=col + radius)
+            .all(|c| map.biome_map[r as usize][c as usize] != Biome::Wall))
+    };
+    for dist in 0..MAPSIZE as i32 {
+        for row in center_row - dist..=center_row + dist {
+            for col in center_col - dist..=center_col + dist {
+                // only the ring dist away, everything closer was already checked
+                if (row - center_row).abs() != dist && (col - center_col).abs() != dist { continue }
+                if open(row, col) {
+                    return Vec2::new(col as f32, row as f32);
+                }
+            }
+        }
+    }
+    center
+}
+
 // Create a minimum spanning tree from a vector of points
 fn create_mst(points: Vec<Vec2>) -> UnGraph<Vec2, f32> {
     let mut graph: UnGraph<Vec2, f32> = UnGraph::new_undirected();
@@ -176,6 +218,7 @@ fn read_map(
     num_camps: &Res<NumCamps>,
     mut rng: &mut ChaChaRng,
     chest_coords: &mut Vec<Vec2>,
+    central_camp: bool,
 ) -> Result<(), Box<dyn Error>> {
     // seed, amplitude, frequency, octaves
     let perlin = Perlin::new(rng.next_u64(), 1.0, 0.08, 3);
@@ -200,6 +243,13 @@ fn read_map(
     // and shuffle them, then truncate the vector to the number of camps
     refine_coordinates(camp_nodes);
     camp_nodes.shuffle(&mut rng);
+    // put a camp in the middle of the map, first so it gets the first camp id
+    if central_camp {
+        // the middle can land in a wall, so the hill goes on the nearest open ground instead
+        let center = nearest_open_tile(map, Vec2::splat(MAPSIZE as f32 / 2.), CAMPSIZE / 2);
+        camp_nodes.retain(|node| euclidean_distance(*node, center) > 50.0);
+        camp_nodes.insert(0, center);
+    }
     if camp_nodes.len() > num_camps.0 as usize {
         camp_nodes.truncate(num_camps.0 as usize);
     }
@@ -421,12 +471,13 @@ pub fn setup_map(
     mut camp_nodes: ResMut<CampNodes>,
     mut world_map: ResMut<WorldMap>,
     mut chest_coords: ResMut<ChestCoords>,
+    game_mode: Res<GameMode>,
 ) {
     //create an rng to randomly choose a goober in the near future
     let mut rng = rand_chacha::ChaChaRng::seed_from_u64(map_seed.0);
 
     // Generate the map, camp nodes, and item nodes
-    let _ = read_map(&mut world_map, &mut camp_nodes.0, &num_camps, &mut rng, &mut chest_coords.0);
+    let _ = read_map(&mut world_map, &mut camp_nodes.0, &num_camps, &mut rng, &mut chest_coords.0, game_mode.has_hill());
 
     // Get a handle for a pure white TILESIZE x TILESIZE image to be colored based on tile type later
     let tile_handle = assets.add(create_tile_image());
@@ -9,6 +9,19 @@ pub mod movement;
 pub mod buffers;
 pub mod camp;
 pub mod components;
+pub mod status;
+pub mod item;
+pub mod powerup;
+pub mod mode;
+pub mod round;
+pub mod respawn;
+pub mod bot;
+pub mod spectate;
+pub mod profile;
+pub mod killfeed;
+pub mod chat;
+pub mod ping;
+pub mod report;
 
 pub const TITLE: &str = "JORDQUEST: SPAWNED INTO A PIXELATED WORLD WITH ENEMIES, CAMPS, AND... ANOTHER PLAYER!? CAN I EARN ENOUGH UPGRADES TO BE VICTORIOUS AND FILL MY DIAPER?";
 pub const WIN_W: f32 = 1280.;
@@ -118,8 +131,19 @@ impl Plugin for GamePlugin{
             enemy::EnemyPlugin,
             map::MapPlugin,
             camera::CameraPlugin,
-            camp::CampPlugin
-        ));
+            camp::CampPlugin,
+            status::StatusPlugin,
+            item::ItemPlugin,
+            mode::ModePlugin,
+            round::RoundPlugin,
+            respawn::RespawnPlugin,
+            bot::BotPlugin,
+            spectate::SpectatePlugin,
+            profile::ProfilePlugin,
+            killfeed::KillFeedPlugin,
+            chat::ChatPlugin,
+        ))
+        .add_plugins((ping::PingPlugin, report::ReportPlugin));
     }
 }
 
@@ -181,7 +205,8 @@ pub fn startup(mut commands: Commands, asset_server: Res<AssetServer>, mut textu
         num_camps: String::new(), num_chests: String::new(), enemy_per_camp: String::new(),
         map_seed: String::new(), eid_percentage: String::new(),
     });
-    commands.insert_resource(movement::KeyBinds::new());
+    commands.insert_resource(powerup::PowerUpConfig::default());
+    commands.insert_resource(mode::GameMode::FreeForAll);
 }
 
 pub fn update_fades(
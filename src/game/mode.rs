@@ -0,0 +1,348 @@
+use bevy::prelude::*;
+use crate::AppState;
+use crate::game::components::*;
+use crate::game::player::{LocalPlayer, MAX_PLAYERS};
+use crate::game::profile::PlayerProfiles;
+
+pub const NUM_GAME_MODES: u8 = 4;
+pub const NUM_TEAMS: u8 = 2;
+const TEAM_SCORE_LIMIT: u32 = 200;
+const HILL_SCORE_LIMIT: u32 = 150;
+pub const LIVES: u16 = 3; // deaths allowed in last player standing
+pub const TEAM_COLORS: [Color; NUM_TEAMS as usize] = [
+    Color::Rgba { red: 0.35, green: 0.55, blue: 1., alpha: 1. },
+    Color::Rgba { red: 1., green: 0.4, blue: 0.35, alpha: 1. },
+];
+
+/// The rule set for a match, picked on the host page and sent to clients when they connect
+#[derive(Resource, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GameMode {
+    FreeForAll = 0,
+    TeamDeathmatch,
+    KingOfTheHill,
+    LastPlayerStanding,
+}
+
+/// Everything a player can get points for
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ScoreEvent {
+    EnemyKill, // did the most damage to an enemy that died
+    EnemyAssist, // did enough damage to an enemy someone else got the kill on
+    PlayerKill,
+    PlayerAssist,
+    CampClear, // split between everyone who damaged the camp
+    CampHeld, // every few seconds a camp is owned
+    HillHeld,
+    Death, // taken away rather than given, when the death penalty is score
+}
+
+impl GameMode {
+    pub fn from_u8(id: u8) -> Option<GameMode> {
+        match id {
+            0 => Some(GameMode::FreeForAll),
+            1 => Some(GameMode::TeamDeathmatch),
+            2 => Some(GameMode::KingOfTheHill),
+            3 => Some(GameMode::LastPlayerStanding),
+            _ => None,
+        }
+    }
+
+    /// the mode after this one, for cycling through them on the host page
+    pub fn next(&self) -> GameMode {
+        GameMode::from_u8((*self as u8 + 1) % NUM_GAME_MODES).unwrap()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::FreeForAll => "Free for All",
+            GameMode::TeamDeathmatch => "Team Deathmatch",
+            GameMode::KingOfTheHill => "King of the Hill",
+            GameMode::LastPlayerStanding => "Last Player Standing",
+        }
+    }
+
+    pub fn points(&self, event: ScoreEvent) -> u8 {
+        match (self, event) {
+            (GameMode::TeamDeathmatch, ScoreEvent::PlayerKill) => 20,
+            (GameMode::TeamDeathmatch, ScoreEvent::PlayerAssist) => 5,
+            (GameMode::TeamDeathmatch, ScoreEvent::Death) => 10,
+            (GameMode::TeamDeathmatch, _) => 0,
+            (GameMode::KingOfTheHill, ScoreEvent::EnemyKill) => 1,
+            (GameMode::KingOfTheHill, ScoreEvent::EnemyAssist) => 1,
+            (GameMode::KingOfTheHill, ScoreEvent::PlayerKill) => 5,
+            (GameMode::KingOfTheHill, ScoreEvent::PlayerAssist) => 1,
+            (GameMode::KingOfTheHill, ScoreEvent::CampClear) => 5,
+            (GameMode::KingOfTheHill, ScoreEvent::CampHeld) => 0,
+            (GameMode::KingOfTheHill, ScoreEvent::HillHeld) => 3,
+            (GameMode::KingOfTheHill, ScoreEvent::Death) => 3,
+            (_, ScoreEvent::EnemyKill) => 1,
+            (_, ScoreEvent::EnemyAssist) => 1,
+            (_, ScoreEvent::PlayerKill) => 20,
+            (_, ScoreEvent::PlayerAssist) => 5,
+            (_, ScoreEvent::CampClear) => 5,
+            (_, ScoreEvent::CampHeld) => 1,
+            (_, ScoreEvent::HillHeld) => 0,
+            (_, ScoreEvent::Death) => 10,
+        }
+    }
+
+    /// the only way score changes, share scales the points for split camp clears and multiple assists.
+    /// checked so a long round pins the score at the max instead of overflowing, and a death can't take it below 0
+    pub fn award(&self, stats: &mut Stats, event: ScoreEvent, share: f32) {
+        let points = (self.points(event) as f32 * share).round() as u32;
+        stats.score = match event {
+            ScoreEvent::Death => stats.score.saturating_sub(points),
+            _ => stats.score.checked_add(points).unwrap_or(u32::MAX),
+        };
+    }
+
+    pub fn teams(&self) -> bool {
+        *self == GameMode::TeamDeathmatch
+    }
+
+    /// generates a camp in the middle of the map for players to fight over
+    pub fn has_hill(&self) -> bool {
+        *self == GameMode::KingOfTheHill
+    }
+
+    pub fn can_damage(&self, teams: &Teams, attacker: u8, target: u8) -> bool {
+        !self.teams() || teams.friendly_fire || teams.of(attacker) != teams.of(target)
+    }
+
+    pub fn can_respawn(&self, stats: &Stats) -> bool {
+        *self != GameMode::LastPlayerStanding || stats.deaths < LIVES
+    }
+
+    /// true once the mode's win condition is met, running out of time is handled by the round.
+    /// players is every player that has spawned in at least once
+    pub fn finished(&self, players: &[(u8, &Stats)], teams: &Teams) -> bool {
+        match self {
+            GameMode::FreeForAll => false,
+            GameMode::TeamDeathmatch => (0..NUM_TEAMS).any(|team| teams.score(players, team) >= TEAM_SCORE_LIMIT),
+            GameMode::KingOfTheHill => players.iter().any(|(_, stats)| stats.score >= HILL_SCORE_LIMIT),
+            GameMode::LastPlayerStanding => players.len() > 1
+                && players.iter().filter(|(_, stats)| self.can_respawn(stats)).count() <= 1,
+        }
+    }
+}
+
+/// Which team every player is on, the host picks and sends it out with each player's tick
+#[derive(Resource, Default)]
+pub struct Teams {
+    pub members: [Option<u8>; MAX_PLAYERS],
+    pub friendly_fire: bool, // only matters on the host since it does the damage
+}
+
+impl Teams {
+    /// players that haven't been assigned yet fall back to alternating by id
+    pub fn of(&self, id: u8) -> u8 {
+        self.members[id as usize].unwrap_or(id % NUM_TEAMS)
+    }
+
+    pub fn size(&self, team: u8) -> usize {
+        self.members.iter().filter(|member| **member == Some(team)).count()
+    }
+
+    /// puts a player on the team they asked for if that keeps the teams even, otherwise on the smallest team
+    pub fn assign(&mut self, id: u8, preference: Option<u8>) -> u8 {
+        self.members[id as usize] = None;
+        let smallest = (0..NUM_TEAMS).min_by_key(|team| self.size(*team)).unwrap();
+        let team = match preference {
+            Some(team) if team < NUM_TEAMS && self.size(team) <= self.size(smallest) => team,
+            _ => smallest,
+        };
+        self.members[id as usize] = Some(team);
+        team
+    }
+
+    pub fn clear(&mut self) {
+        self.members = [None; MAX_PLAYERS];
+    }
+
+    pub fn score(&self, players: &[(u8, &Stats)], team: u8) -> u32 {
+        players.iter().filter(|(id, _)| self.of(*id) == team).map(|(_, stats)| stats.score).fold(0, u32::saturating_add)
+    }
+}
+
+/// The team picked on the host or join page, None lets the host balance the teams
+#[derive(Resource, Default)]
+pub struct TeamPreference(pub Option<u8>);
+
+impl TeamPreference {
+    pub fn next(&self) -> TeamPreference {
+        match self.0 {
+            None => TeamPreference(Some(0)),
+            Some(team) if team + 1 < NUM_TEAMS => TeamPreference(Some(team + 1)),
+            Some(_) => TeamPreference(None),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.0.map_or("Auto".to_string(), |team| format!("{}", team + 1))
+    }
+}
+
+/// Marks a player that has spawned in, players that never joined don't count towards win conditions
+#[derive(Component)]
+pub struct Joined;
+
+/// Marks the camp in the middle of the map in king of the hill
+#[derive(Component)]
+pub struct Hill;
+
+/// Marks the HUD text showing the mode and its objective
+#[derive(Component)]
+pub struct ModeDisplay;
+
+pub struct ModePlugin;
+
+impl Plugin for ModePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (
+                mode_feedback,
+                team_colors,
+            ).run_if(in_state(AppState::Game)))
+            .init_resource::<Teams>()
+            .init_resource::<TeamPreference>();
+    }
+}
+
+/// shows the mode and how close everyone is to winning it
+pub fn mode_feedback(
+    game_mode: Res<GameMode>,
+    teams: Res<Teams>,
+    players: Query<(&Player, &Stats, Option<&LocalPlayer>), With<Joined>>,
+    hills: Query<&CampOwner, With<Hill>>,
+    mut mode_displays: Query<&mut Text, With<ModeDisplay>>,
+) {
+    let stats: Vec<(u8, &Stats)> = players.iter().map(|(pl, stats, _)| (pl.0, stats)).collect();
+    let objective = match *game_mode {
+        GameMode::FreeForAll => "Most points wins".to_string(),
+        GameMode::TeamDeathmatch => {
+            let local_team = players.iter().find(|(_, _, lp)| lp.is_some()).map(|(pl, _, _)| teams.of(pl.0) + 1);
+            format!("Team 1: {}  Team 2: {}  (first to {})\nYou are on team {}",
+                teams.score(&stats, 0), teams.score(&stats, 1), TEAM_SCORE_LIMIT, local_team.map_or("?".to_string(), |team| team.to_string()))
+        }
+        GameMode::KingOfTheHill => {
+            let holder = hills.get_single().ok().and_then(|hill| hill.owner);
+            match holder {
+                Some(id) => format!("Hill held by Player {}  (first to {})", id + 1, HILL_SCORE_LIMIT),
+                None => format!("Hill unclaimed  (first to {})", HILL_SCORE_LIMIT),
+            }
+        }
+        GameMode::LastPlayerStanding => {
+            let local = players.iter().find(|(_, _, lp)| lp.is_some());
+            match local {
+                Some((_, stats, _)) if game_mode.can_respawn(stats) => format!("Lives left: {}", LIVES - stats.deaths),
+                Some(_) => "Eliminated".to_string(),
+                None => format!("Lives left: {}", LIVES),
+            }
+        }
+    };
+    for mut text in &mut mode_displays {
+        text.sections[0].value = format!("{}\n", game_mode.name());
+        text.sections[1].value = objective.clone();
+    }
+}
+
+/// tints every player in their team's color while teams are on, and in the color from their profile otherwise
+pub fn team_colors(
+    game_mode: Res<GameMode>,
+    teams: Res<Teams>,
+    profiles: Res<PlayerProfiles>,
+    mut players: Query<(&Player, &mut TextureAtlasSprite)>,
+) {
+    for (pl, mut sprite) in &mut players {
+        sprite.color = if game_mode.teams() { TEAM_COLORS[teams.of(pl.0) as usize] } else { profiles.color(pl.0) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn award_scales_points_by_share() {
+        let mut stats = Stats::default();
+        GameMode::FreeForAll.award(&mut stats, ScoreEvent::PlayerKill, 1.);
+        assert_eq!(stats.score, 20);
+        GameMode::FreeForAll.award(&mut stats, ScoreEvent::CampClear, 0.5);
+        assert_eq!(stats.score, 23); // 2.5 rounds up
+        GameMode::FreeForAll.award(&mut stats, ScoreEvent::PlayerAssist, 2.);
+        assert_eq!(stats.score, 33);
+    }
+
+    #[test]
+    fn award_uses_the_mode_points() {
+        let mut stats = Stats::default();
+        GameMode::TeamDeathmatch.award(&mut stats, ScoreEvent::EnemyKill, 1.);
+        GameMode::TeamDeathmatch.award(&mut stats, ScoreEvent::CampHeld, 1.);
+        assert_eq!(stats.score, 0);
+        GameMode::KingOfTheHill.award(&mut stats, ScoreEvent::HillHeld, 1.);
+        GameMode::KingOfTheHill.award(&mut stats, ScoreEvent::EnemyAssist, 1.);
+        assert_eq!(stats.score, 4);
+    }
+
+    #[test]
+    fn award_saturates_both_ways() {
+        let mut stats = Stats { score: u32::MAX - 5, ..default() };
+        GameMode::FreeForAll.award(&mut stats, ScoreEvent::PlayerKill, 1.);
+        assert_eq!(stats.score, u32::MAX);
+        let mut stats = Stats { score: 4, ..default() };
+        GameMode::FreeForAll.award(&mut stats, ScoreEvent::Death, 1.);
+        assert_eq!(stats.score, 0);
+        let mut stats = Stats { score: 40, ..default() };
+        GameMode::KingOfTheHill.award(&mut stats, ScoreEvent::Death, 1.);
+        assert_eq!(stats.score, 37);
+    }
+
+    #[test]
+    fn team_score_adds_up_members() {
+        let mut teams = Teams::default();
+        assert_eq!(teams.assign(0, Some(0)), 0);
+        assert_eq!(teams.assign(1, Some(0)), 1); // team 0 would be bigger, so it goes to the smaller one
+        assert_eq!(teams.assign(2, Some(1)), 1);
+        let (a, b, c) = (Stats { score: 10, ..default() }, Stats { score: u32::MAX, ..default() }, Stats { score: 7, ..default() });
+        let players = [(0, &a), (1, &b), (2, &c)];
+        assert_eq!(teams.score(&players, 0), 10);
+        assert_eq!(teams.score(&players, 1), u32::MAX);
+    }
+
+    #[test]
+    fn friendly_fire_only_blocks_teammates() {
+        let mut teams = Teams::default();
+        teams.assign(0, Some(0));
+        teams.assign(1, Some(1));
+        teams.assign(2, Some(0));
+        assert!(!GameMode::TeamDeathmatch.can_damage(&teams, 0, 2));
+        assert!(GameMode::TeamDeathmatch.can_damage(&teams, 0, 1));
+        assert!(GameMode::FreeForAll.can_damage(&teams, 0, 2));
+        teams.friendly_fire = true;
+        assert!(GameMode::TeamDeathmatch.can_damage(&teams, 0, 2));
+    }
+
+    #[test]
+    fn teams_fall_back_to_alternating_ids() {
+        let mut teams = Teams::default();
+        assert_eq!(teams.of(3), 3 % NUM_TEAMS);
+        teams.assign(3, Some(0));
+        assert_eq!(teams.of(3), 0);
+        // reassigning doesn't count the player against their old team
+        assert_eq!(teams.assign(3, Some(1)), 1);
+        assert_eq!(teams.size(0), 0);
+        assert_eq!(teams.assign(4, Some(NUM_TEAMS)), 0);
+        teams.clear();
+        assert_eq!(teams.size(1), 0);
+    }
+
+    #[test]
+    fn team_preference_cycles_back_to_auto() {
+        let mut pref = TeamPreference::default();
+        assert_eq!(pref.name(), "Auto");
+        for team in 0..NUM_TEAMS {
+            pref = pref.next();
+            assert_eq!(pref.0, Some(team));
+        }
+        assert_eq!(pref.next().0, None);
+    }
+}
@@ -11,26 +11,35 @@ use crate::game::buffers::{DirBuffer, PosBuffer};
 use crate::game::camera::SpatialCameraBundle;
 use crate::game::map::Biome::Wall;
 use crate::game::map::{get_pos_in_tile, get_tile_at_pos, TILESIZE};
+use crate::game::status::{StatusEffects, StatusType};
+use crate::game::item::{Inventory, HOTBAR_SLOTS};
+use crate::game::powerup::PowerUpConfig;
 use crate::net::TickNum;
 
 pub const WALL_DAMAGE: u8 = 5;
+pub const DASH_DISTANCE: f32 = 128.;
+pub const DASH_TIME: f32 = 0.15;
 
 #[derive(Resource)]
 pub struct KeyBinds {
     pub up: KeyCode,
     pub down: KeyCode,
     pub left: KeyCode,
-    pub right: KeyCode
+    pub right: KeyCode,
+    pub dash: KeyCode,
+    pub items: [KeyCode; HOTBAR_SLOTS],
 }
 
 impl KeyBinds {
-    // later on, we should have a constructor that reads bindings from a file
+    /// the defaults, the profile file overrides them
     pub fn new() -> KeyBinds {
         KeyBinds {
             up: KeyCode::W,
             down: KeyCode::S,
             left: KeyCode::A,
-            right: KeyCode::D
+            right: KeyCode::D,
+            dash: KeyCode::Space,
+            items: [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4],
         }
     }
 }
@@ -57,34 +66,49 @@ pub const MOVE_VECTORS: [Vec2; 16] = [
     Vec2 { x:0., y:0. },  // 1111
 ];
 
+/// Looks up the unit vector for whichever movement keys are currently held
+pub fn input_direction(keyboard_input: &Input<KeyCode>, key_binds: &KeyBinds) -> Vec2 {
+    let mut mv: usize = keyboard_input.pressed(key_binds.up) as usize * 0b0001;
+    mv |= keyboard_input.pressed(key_binds.down) as usize * 0b0010;
+    mv |= keyboard_input.pressed(key_binds.left) as usize * 0b0100;
+    mv |= keyboard_input.pressed(key_binds.right) as usize * 0b1000;
+    MOVE_VECTORS[mv]
+}
+
 /// Player movement function. Runs on Update schedule.
 pub fn handle_move(
     tick: Res<TickNum>,
     keyboard_input: Res<Input<KeyCode>>,
-    mut players: Query<(&Player, &mut Transform, &mut Health, &Collider, &StoredPowerUps, &PlayerShield, &mut buffers::HpBuffer), With<LocalPlayer>>,
+    mut players: Query<(&Player, &mut Transform, &mut Health, &Collider, &StoredPowerUps, &PlayerShield, &mut PlayerDash, &StatusEffects, &Inventory, &mut buffers::HpBuffer), With<LocalPlayer>>,
     other_colliders: Query<(&Transform, &Collider, Option<&Health>), Without<LocalPlayer>>,
     map: Res<map::WorldMap>,
     time: Res<Time>,
-    key_binds: Res<KeyBinds>
+    key_binds: Res<KeyBinds>,
+    powerup_config: Res<PowerUpConfig>,
 ) {
     // should only be a single entry in this query (with localplayer)
     let player = players.get_single_mut();
     if player.is_err() { return; }
-    let (_, mut pos, mut hp, collider, spu, shield, mut hb) = player.unwrap();
+    let (_, mut pos, mut hp, collider, spu, shield, mut dash, effects, inv, mut hb) = player.unwrap();
 
-    if hp.dead || shield.active { return }
+    if hp.dead || shield.active || effects.active(StatusType::Stun) { return }
 
-    let mut mv: usize = keyboard_input.pressed(key_binds.up) as usize * 0b0001;
-    mv |= keyboard_input.pressed(key_binds.down) as usize * 0b0010;
-    mv |= keyboard_input.pressed(key_binds.left) as usize * 0b0100;
-    mv |= keyboard_input.pressed(key_binds.right) as usize * 0b1000;
-    let dir = MOVE_VECTORS[mv];
+    // while dashing the player ignores input and slides along the dash direction
+    if !dash.active.finished() {
+        dash.active.tick(time.delta());
+        let delta = dash.dir * (DASH_DISTANCE / DASH_TIME) * time.delta_seconds();
+        pos.translation = dash_move(&pos.translation, delta, &collider.0, &map.biome_map);
+        return;
+    }
+
+    let dir = input_direction(&keyboard_input, &key_binds);
+    let speed = PLAYER_SPEED * powerup_config.move_speed_mult(spu) * effects.speed_mult() * inv.speed_mult();
     let can_move = true;
 
 
     let mut new_pos = Vec3 {
-        x: pos.translation.x + dir.x * (PLAYER_SPEED + spu.power_ups[PowerUpType::MovementSpeedUp as usize] as f32 * MOVEMENT_SPEED_UP as f32) * time.delta_seconds(),
-        y: pos.translation.y + dir.y * (PLAYER_SPEED + spu.power_ups[PowerUpType::MovementSpeedUp as usize] as f32 * MOVEMENT_SPEED_UP as f32) * time.delta_seconds(),
+        x: pos.translation.x + dir.x * speed * time.delta_seconds(),
+        y: pos.translation.y + dir.y * speed * time.delta_seconds(),
         z: 0.0,
     };
 
@@ -111,12 +135,36 @@ pub fn handle_move(
 
     pos.translation = correct_wall_collisions(&pos.translation, &collider.0, &map.biome_map);
     if get_tile_at_pos(&pos.translation, &map.biome_map) == Wall {
-        let mut curhp = hb.0.get(tick.0).unwrap_or(0);
-        curhp = curhp.saturating_sub(WALL_DAMAGE);
-        hb.0.set(tick.0, Some(curhp));
+        hazard_damage(&mut hb, tick.0, WALL_DAMAGE);
     }
 }
 
+/// Damage from standing somewhere the player shouldn't be, like inside a wall or outside the safe zone
+pub fn hazard_damage(hb: &mut buffers::HpBuffer, tick: u16, amount: u8) {
+    let mut curhp = hb.0.get(tick).unwrap_or(0);
+    curhp = curhp.saturating_sub(amount);
+    hb.0.set(tick, Some(curhp));
+}
+
+/// Moves a collider by delta in steps smaller than a tile so it can't skip over a wall.
+/// Stops at the last position that wasn't inside a wall.
+pub fn dash_move(
+    pos: &Vec3,
+    delta: Vec2,
+    collider: &Vec2,
+    map: &[[map::Biome; map::MAPSIZE]; map::MAPSIZE],
+) -> Vec3 {
+    let steps = (delta.length() / (TILESIZE as f32 / 4.)).ceil().max(1.) as usize;
+    let step = delta / steps as f32;
+    let mut pos = *pos;
+    for _ in 0..steps {
+        let next = pos + step.extend(0.);
+        if get_tile_at_pos(&next, map) == Wall { break }
+        pos = correct_wall_collisions(&next, collider, map);
+    }
+    pos
+}
+
 pub fn correct_wall_collisions(
     pos: &Vec3,
     collider: &Vec2,
@@ -0,0 +1,336 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use crate::AppState;
+use crate::game::PlayerId;
+use crate::game::camera::{self, GameCamera, Minimap};
+use crate::game::chat::ChatOpen;
+use crate::game::map;
+use crate::game::mode::{GameMode, Teams};
+use crate::game::components::Health;
+use crate::game::player::{LocalPlayer, MAX_PLAYERS};
+use crate::game::profile::PlayerProfiles;
+use crate::game::spectate::SPECTATOR_ID;
+use crate::menus::layout::PopupEvent;
+use crate::net::{self, client, is_client, is_host, IsHost, TickNum};
+use crate::net::packets::{Packet, PingRequest, send_buf};
+
+pub const PING_TICKS: u16 = 40; // how long a ping stays up
+const PING_FADE_TICKS: u16 = 10; // fades out over the end of that
+const PING_RESENDS: u8 = 5; // a client sends each ping this many ticks in a row in case some are dropped
+const PING_BURST: u8 = 3; // pings someone can place back to back
+const PING_REFILL_TICKS: u16 = 20; // then one more every 2 seconds
+const PING_SCALE: f32 = 2.; // in the world, on the minimap they're drawn at their normal size
+const PING_PULSE: f32 = 0.25;
+const PING_PULSE_SPEED: f32 = 8.;
+
+/// What a ping means, picked with the modifier held while middle-clicking
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PingType {
+    Attack = 0,
+    Danger,
+    Chest,
+}
+
+impl PingType {
+    pub fn from_u8(id: u8) -> Option<PingType> {
+        match id {
+            0 => Some(PingType::Attack),
+            1 => Some(PingType::Danger),
+            2 => Some(PingType::Chest),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PingType::Attack => "Attack here",
+            PingType::Danger => "Danger",
+            PingType::Chest => "Chest",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            PingType::Attack => Color::RED,
+            PingType::Danger => Color::ORANGE,
+            PingType::Chest => Color::GOLD,
+        }
+    }
+}
+
+/// A marked spot, the host hands out ids so every machine can tell which ones it's already showing
+#[derive(Copy, Clone, PartialEq)]
+pub struct Ping {
+    pub id: u16,
+    pub tick: u16,
+    pub sender: u8,
+    pub kind: PingType,
+    pub pos: Vec2,
+}
+
+/// sent when a player's ping reaches the host, straight from the input for the host's own
+#[derive(Event)]
+pub struct PingEvent {
+    pub sender: u8,
+    pub kind: PingType,
+    pub pos: Vec2,
+}
+
+/// The pings still up, the host places them and sends them out with every tick so a dropped packet
+/// doesn't lose one. Clients only ever get the ones meant for them
+#[derive(Resource)]
+pub struct Pings {
+    next_id: u16,
+    pub active: Vec<Ping>,
+    allowance: [u8; MAX_PLAYERS],
+}
+
+impl Default for Pings {
+    fn default() -> Pings {
+        Pings {
+            next_id: 1,
+            active: Vec::new(),
+            allowance: [PING_BURST; MAX_PLAYERS],
+        }
+    }
+}
+
+impl Pings {
+    /// in team modes pings only go to the sender's team, spectators see all of them
+    pub fn visible_to(&self, id: u8, game_mode: &GameMode, teams: &Teams) -> Vec<Ping> {
+        self.active.iter()
+            .filter(|ping| id == SPECTATOR_ID || !game_mode.teams() || teams.of(ping.sender) == teams.of(id))
+            .copied()
+            .collect()
+    }
+}
+
+/// The ping a client is still sending to the host
+#[derive(Resource, Default)]
+pub struct LocalPing {
+    pub seq: u16,
+    pub ping: Option<(PingType, Vec2)>,
+    pub resends: u8,
+}
+
+/// A ping in the world, or on the minimap
+#[derive(Component)]
+pub struct PingMarker {
+    pub id: u16,
+    pub tick: u16,
+    pub on_minimap: bool,
+}
+
+pub struct PingPlugin;
+
+impl Plugin for PingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Game), reset_pings)
+            .add_systems(OnExit(AppState::Game), despawn_ping_markers)
+            .add_systems(Update, (
+                place_ping,
+                host_pings.run_if(is_host).after(place_ping),
+                sync_ping_markers.after(host_pings),
+                animate_ping_markers.after(sync_ping_markers),
+            ).run_if(in_state(AppState::Game)))
+            .add_systems(FixedUpdate, ping_simulate
+                .run_if(in_state(AppState::Game)).run_if(is_host).before(net::host::fixed))
+            .add_systems(FixedUpdate, client_ping_fixed
+                .run_if(in_state(AppState::Game)).run_if(is_client))
+            .init_resource::<Pings>()
+            .init_resource::<LocalPing>()
+            .add_event::<PingEvent>();
+    }
+}
+
+pub fn reset_pings(
+    mut pings: ResMut<Pings>,
+    mut local_ping: ResMut<LocalPing>,
+) {
+    pings.active.clear();
+    pings.allowance = [PING_BURST; MAX_PLAYERS];
+    local_ping.ping = None;
+}
+
+pub fn despawn_ping_markers(
+    mut commands: Commands,
+    markers: Query<Entity, With<PingMarker>>,
+) {
+    for e in &markers {
+        commands.entity(e).despawn_recursive();
+    }
+}
+
+/// middle-click pings the spot under the cursor, on the minimap or in the world.
+/// shift marks danger and ctrl a chest
+pub fn place_ping(
+    mouse_button_inputs: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    game_camera: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+    player_id: Res<PlayerId>,
+    is_host: Res<IsHost>,
+    chat_open: Res<ChatOpen>,
+    local_player: Query<&Health, With<LocalPlayer>>,
+    mut ping_writer: EventWriter<PingEvent>,
+    mut local_ping: ResMut<LocalPing>,
+) {
+    if player_id.0 == SPECTATOR_ID || chat_open.0 { return }
+    if !mouse_button_inputs.just_pressed(MouseButton::Middle) { return }
+    let Ok(hp) = local_player.get_single() else { return };
+    let Some(cursor) = window_query.get_single().ok().and_then(|window| window.cursor_position()) else { return };
+    let picking_spawn = hp.dead;
+    let pos = camera::minimap_to_world(cursor, picking_spawn).or_else(|| {
+        // the world is covered by the minimap while picking a spawn
+        if picking_spawn { return None }
+        let (camera, camera_transform) = game_camera.get_single().ok()?;
+        camera.viewport_to_world_2d(camera_transform, cursor)
+    });
+    let Some(pos) = pos else { return };
+    let kind = if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        PingType::Danger
+    } else if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        PingType::Chest
+    } else {
+        PingType::Attack
+    };
+    if is_host.0 {
+        ping_writer.send(PingEvent { sender: player_id.0, kind, pos });
+    } else {
+        local_ping.seq = local_ping.seq.wrapping_add(1);
+        local_ping.ping = Some((kind, pos));
+        local_ping.resends = PING_RESENDS;
+    }
+}
+
+/// keeps sending the latest ping for a few ticks, the host ignores repeats
+pub fn client_ping_fixed(
+    mut socket: ResMut<net::Socket>,
+    mut local_ping: ResMut<LocalPing>,
+    mut popup_writer: EventWriter<PopupEvent>,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
+) {
+    let Some((kind, pos)) = local_ping.ping else { return };
+    let Some(sock) = socket.0.as_ref() else { return };
+    let Ok(host_addr) = sock.peer_addr() else { return };
+    let mut bytes: Vec<u8> = Vec::new();
+    PingRequest { seq: local_ping.seq, kind: kind as u8, pos }.to_buf(&mut bytes);
+    if let Err(e) = send_buf(bytes.as_slice(), sock, &host_addr) {
+        local_ping.ping = None;
+        client::lose_host(&mut socket, e, &mut popup_writer, &mut app_state_next_state, AppState::GameOver);
+        return;
+    }
+    local_ping.resends -= 1;
+    if local_ping.resends == 0 {
+        local_ping.ping = None;
+    }
+}
+
+/// gives back a ping to everyone who's used up their burst, and takes down old pings
+pub fn ping_simulate(
+    tick: Res<TickNum>,
+    mut pings: ResMut<Pings>,
+) {
+    if tick.0 % PING_REFILL_TICKS == 0 {
+        for allowance in pings.allowance.iter_mut() {
+            *allowance = (*allowance + 1).min(PING_BURST);
+        }
+    }
+    pings.active.retain(|ping| tick.0.saturating_sub(ping.tick) < PING_TICKS);
+}
+
+/// places pings that are within their sender's limit
+pub fn host_pings(
+    tick: Res<TickNum>,
+    mut ping_reader: EventReader<PingEvent>,
+    mut pings: ResMut<Pings>,
+) {
+    for ev in ping_reader.iter() {
+        let sender = ev.sender as usize;
+        if sender >= MAX_PLAYERS || pings.allowance[sender] == 0 { continue }
+        let half_map = (map::MAPSIZE * map::TILESIZE / 2) as f32;
+        if ev.pos.x.abs() > half_map || ev.pos.y.abs() > half_map { continue }
+        pings.allowance[sender] -= 1;
+        let id = pings.next_id;
+        pings.next_id = pings.next_id.wrapping_add(1).max(1);
+        pings.active.push(Ping { id, tick: tick.0, sender: ev.sender, kind: ev.kind, pos: ev.pos });
+    }
+}
+
+/// puts up markers for new pings with a sound, and takes down the ones that are gone
+pub fn sync_ping_markers(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    pings: Res<Pings>,
+    player_id: Res<PlayerId>,
+    game_mode: Res<GameMode>,
+    teams: Res<Teams>,
+    profiles: Res<PlayerProfiles>,
+    minimap: Query<Entity, With<Minimap>>,
+    markers: Query<(Entity, &PingMarker)>,
+) {
+    if !pings.is_changed() { return }
+    let visible = pings.visible_to(player_id.0, &game_mode, &teams);
+    for (e, marker) in &markers {
+        if !visible.iter().any(|ping| ping.id == marker.id) {
+            commands.entity(e).despawn_recursive();
+        }
+    }
+    for ping in visible {
+        if markers.iter().any(|(_, marker)| marker.id == ping.id) { continue }
+        commands.spawn(AudioBundle {
+            source: asset_server.load("click.ogg"),
+            ..default()
+        });
+        commands.spawn((
+            SpriteBundle {
+                texture: asset_server.load("camp_marker.png"),
+                sprite: Sprite { color: ping.kind.color(), ..default() },
+                transform: Transform::from_xyz(ping.pos.x, ping.pos.y, 4.).with_scale(Vec3::splat(PING_SCALE)),
+                ..default()
+            },
+            PingMarker { id: ping.id, tick: ping.tick, on_minimap: false },
+        )).with_children(|parent| {
+            parent.spawn(Text2dBundle {
+                text: Text::from_section(format!("{}: {}", profiles.name(ping.sender), ping.kind.name()), TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 16.,
+                    color: ping.kind.color(),
+                }).with_alignment(TextAlignment::Center),
+                transform: Transform::from_xyz(0., 12., 1.).with_scale(Vec3::splat(1. / PING_SCALE)),
+                ..default()
+            });
+        });
+        for parent in &minimap {
+            let minimap_marker = commands.spawn((
+                SpriteBundle {
+                    texture: asset_server.load("camp_marker.png"),
+                    sprite: Sprite { color: ping.kind.color(), ..default() },
+                    transform: Transform::from_xyz(
+                        (ping.pos.x / map::TILESIZE as f32) as i32 as f32,
+                        (ping.pos.y / map::TILESIZE as f32) as i32 as f32,
+                        4.,
+                    ),
+                    ..default()
+                },
+                PingMarker { id: ping.id, tick: ping.tick, on_minimap: true },
+            )).id();
+            commands.entity(parent).add_child(minimap_marker);
+        }
+    }
+}
+
+/// pulses the markers and fades them out as they run out
+pub fn animate_ping_markers(
+    time: Res<Time>,
+    tick: Res<TickNum>,
+    mut markers: Query<(&PingMarker, &mut Transform, &mut Sprite)>,
+) {
+    let pulse = 1. + PING_PULSE * (time.elapsed_seconds() * PING_PULSE_SPEED).sin();
+    for (marker, mut tf, mut sprite) in &mut markers {
+        let base = if marker.on_minimap { 1. } else { PING_SCALE };
+        tf.scale = Vec3::splat(base * pulse);
+        let left = PING_TICKS.saturating_sub(tick.0.saturating_sub(marker.tick));
+        sprite.color.set_a((left as f32 / PING_FADE_TICKS as f32).min(1.));
+    }
+}
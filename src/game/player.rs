@@ -7,32 +7,102 @@ use crate::buffers::*;
 use crate::game::components::*;
 use crate::game::enemy::LastAttacker;
 use crate::game::PlayerId;
-use crate::net::{is_client, is_host, TICKLEN_S, TickNum};
+use crate::game::status::{spawn_status_icons, StatusEffects, StatusType, STATUS_TICKS};
+use crate::game::item::{decode_item, Inventory, ItemUseEvent, TONIC_SPEED_MULT};
+use crate::game::powerup::PowerUpConfig;
+use crate::game::mode::{GameMode, Joined, ScoreEvent, Teams};
+use crate::game::round::{MatchPhase, Round};
+use crate::game::respawn::{respawn_delay, DeathPenalty, Respawn, SPAWN_PROTECTION_TICKS};
+use crate::game::killfeed::KillFeed;
+use crate::game::profile;
+use crate::game::map::WorldMap;
+use crate::net::{is_client, is_host, IsHost, TICKLEN_S, TickNum};
 use crate::net::packets::{PlayerTickEvent, UserCmdEvent};
 use crate::menus::layout::{toggle_leaderboard, update_leaderboard};
+use crate::net::lobby::Lobby;
 
 pub const PLAYER_SPEED: f32 = 250.;
 pub const PLAYER_DEFAULT_HP: u8 = 100;
 pub const PLAYER_DEFAULT_DEF: f32 = 1.;
 pub const PLAYER_SIZE: Vec2 = Vec2 { x: 32., y: 32. };
-pub const MAX_PLAYERS: usize = 4;
+pub const MAX_PLAYERS: usize = 16; // the most the host page can allow, ids and bitfields are sized for this
+pub const PLAYER_CAPS: [u8; 5] = [2, 4, 8, 12, 16];
+pub const PLAYER_SPRITES: usize = 4; // player columns on the entity sheet, later ids reuse them with a tint
+/// the colors players pick from on the profile page, anyone without a profile gets the one at their id
+pub const PLAYER_COLORS: [Color; MAX_PLAYERS] = [
+    Color::Rgba{red: 0.2, green: 0.4, blue: 1., alpha: 1.},
+    Color::Rgba{red: 1., green: 0.25, blue: 0.25, alpha: 1.},
+    Color::Rgba{red: 0.25, green: 0.9, blue: 0.25, alpha: 1.},
+    Color::Rgba{red: 1., green: 0.85, blue: 0.2, alpha: 1.},
+    Color::Rgba{red: 0.7, green: 0.3, blue: 1., alpha: 1.},
+    Color::Rgba{red: 1., green: 0.55, blue: 0.1, alpha: 1.},
+    Color::Rgba{red: 0.2, green: 0.9, blue: 0.9, alpha: 1.},
+    Color::Rgba{red: 1., green: 0.4, blue: 0.75, alpha: 1.},
+    Color::Rgba{red: 0.55, green: 0.35, blue: 0.2, alpha: 1.},
+    Color::Rgba{red: 0.6, green: 1., blue: 0.4, alpha: 1.},
+    Color::Rgba{red: 0.1, green: 0.2, blue: 0.6, alpha: 1.},
+    Color::Rgba{red: 0.6, green: 0.1, blue: 0.15, alpha: 1.},
+    Color::Rgba{red: 0.1, green: 0.5, blue: 0.3, alpha: 1.},
+    Color::Rgba{red: 0.95, green: 0.95, blue: 0.95, alpha: 1.},
+    Color::Rgba{red: 0.5, green: 0.5, blue: 0.1, alpha: 1.},
+    Color::Rgba{red: 0.4, green: 0.4, blue: 0.45, alpha: 1.},
+];
 pub const SWORD_DAMAGE: u8 = 40;
 pub const SWORD_LENGTH: f32 = 90.0;
 pub const SWORD_DEGREES: f32 = 70.0;
-const DEFAULT_COOLDOWN: f32 = 0.8;
+pub const DEFAULT_COOLDOWN: f32 = 0.8;
 pub const ATTACK_BITFLAG: u8 = 1;
 pub const SPAWN_BITFLAG: u8 = 2;
 pub const SHIELD_BITFLAG: u8 = 4;
+pub const DASH_BITFLAG: u8 = 8;
+pub const DASH_COOLDOWN_TICKS: u16 = 20;
+pub const DASH_IFRAME_TICKS: u8 = 3;
+const DASH_SLACK: f32 = PLAYER_SPEED * TONIC_SPEED_MULT * TICKLEN_S; // the player can walk on the tick they dash
+const DASH_TICKS: u16 = 3; // DASH_TIME runs into a third tick when the dash starts late in one
+pub const SHIELD_MAX_STAMINA: u8 = 100;
+const SHIELD_DRAIN: u8 = 2; // per tick while the shield is raised
+const SHIELD_REGEN: u8 = 3; // per tick while the shield is lowered
+pub const SHIELD_BLOCK_COST: u8 = 20;
+pub const PARRY_WINDOW_TICKS: u16 = 2;
+pub const PARRY_STAGGER_TICKS: u8 = 10;
+pub const SHIELD_BREAK_STUN_TICKS: u8 = 20;
 
 #[derive(Event)]
 pub struct SetIdEvent(pub u8);
 
+/// How many players the host lets in, counting itself and bots, picked on the host page
+#[derive(Resource)]
+pub struct MaxPlayers(pub u8);
+
+impl Default for MaxPlayers {
+    fn default() -> MaxPlayers {
+        MaxPlayers(4)
+    }
+}
+
+impl MaxPlayers {
+    pub fn next(&self) -> MaxPlayers {
+        MaxPlayers(PLAYER_CAPS.into_iter().find(|cap| *cap > self.0).unwrap_or(PLAYER_CAPS[0]))
+    }
+}
+
+/// Which player ids are in the match. the host keeps it up to date as people join and leave
+/// and sends it out with every tick, everyone spawns and despawns player entities to match it
+#[derive(Resource, Default)]
+pub struct Roster(pub [bool; MAX_PLAYERS]);
+
 #[derive(Event)]
 pub struct AttackEvent {
     pub seq_num: u16,
     pub id: u8,
 }
 
+#[derive(Event)]
+pub struct DashEvent {
+    pub seq_num: u16,
+    pub id: u8,
+}
+
 #[derive(Event)]
 pub struct SpawnEvent {
     pub id: u8
@@ -70,6 +140,71 @@ pub struct Shield;
 #[derive(Component)]
 pub struct PlayerShield {
     pub active: bool,
+    pub raised_tick: u16,
+    /// knocked down by a stun, it stays down until the player lets go and raises it again
+    pub broken: bool,
+}
+
+impl PlayerShield {
+    /// true if the shield went up recently enough to parry a hit landing on this tick
+    pub fn parrying(&self, tick: u16) -> bool {
+        self.active && tick.wrapping_sub(self.raised_tick) <= PARRY_WINDOW_TICKS
+    }
+}
+
+/// active and cooldown drive the local player's movement and HUD,
+/// last_tick, iframes and budget are only tracked by the host
+#[derive(Component)]
+pub struct PlayerDash {
+    pub active: Timer,
+    pub cooldown: Timer,
+    pub dir: Vec2,
+    pub last_tick: Option<u16>,
+    pub iframes: u8,
+    pub budget: f32, // how much further than walking the last dash can still go
+}
+
+impl PlayerDash {
+    pub fn new() -> PlayerDash {
+        PlayerDash {
+            active: Timer::from_seconds(DASH_TIME, TimerMode::Once),
+            cooldown: Timer::from_seconds(DASH_COOLDOWN_TICKS as f32 * TICKLEN_S, TimerMode::Once),
+            dir: Vec2::ZERO,
+            last_tick: None,
+            iframes: 0,
+            budget: 0.,
+        }
+    }
+
+    /// whether DASH_COOLDOWN_TICKS have passed since the last dash the host took
+    pub fn ready(&self, seq_num: u16) -> bool {
+        !self.last_tick.is_some_and(|last| seq_num.wrapping_sub(last) < DASH_COOLDOWN_TICKS)
+    }
+
+    pub fn take(&mut self, seq_num: u16) {
+        self.last_tick = Some(seq_num);
+        self.iframes = DASH_IFRAME_TICKS;
+        self.budget = DASH_DISTANCE;
+    }
+
+    /// whether a tick is one the last dash can have moved the player on
+    pub fn dashing(&self, seq_num: u16) -> bool {
+        self.last_tick.is_some_and(|last| seq_num.wrapping_sub(last) < DASH_TICKS)
+    }
+
+    /// the furthest the player can have gone on a tick, what's left of a dash spreads over the ticks it lasts
+    pub fn max_step(&self, seq_num: u16) -> f32 {
+        if self.dashing(seq_num) { DASH_SLACK + self.budget } else { DASH_SLACK }
+    }
+
+    /// uses up whatever part of a step was more than walking
+    pub fn spend(&mut self, step: f32) {
+        self.budget = (self.budget - (step - DASH_SLACK).max(0.)).max(0.);
+    }
+
+    pub fn invulnerable(&self) -> bool {
+        self.iframes > 0
+    }
 }
 
 pub struct PlayerPlugin;
@@ -82,15 +217,22 @@ impl Plugin for PlayerPlugin{
             .add_systems(Update, (
                 attack_input,
                 shield_input,
+                dash_input.before(handle_move),
                 animate_sword,
                 handle_move,
                 update_score,
                 powerup_feedback,
+                sync_cooldown,
+                cooldown_feedback,
+                stamina_feedback,
                 handle_player_ticks.run_if(is_client),
                 ).run_if(in_state(AppState::Game)))
             .add_systems(FixedUpdate, (
                 attack_host.before(attack_simulate),
+                dash_host.before(dash_simulate),
+                dash_simulate.before(attack_simulate).before(enemy::attack_simulate),
                 attack_simulate.after(enemy::fixed_move),
+                dash_iframes_tick.after(attack_simulate).after(enemy::attack_simulate),
                 spawn_simulate,
                 powerup_grab_simulate,
             ).run_if(in_state(AppState::Game)).run_if(is_host).before(net::host::fixed))
@@ -98,14 +240,19 @@ impl Plugin for PlayerPlugin{
                 update_buffer.before(attack_host),
                 attack_draw.after(attack_simulate),
                 shield_draw,
+                stamina_simulate.after(shield_draw).before(attack_simulate).run_if(is_host),
                 health_simulate.after(spawn_simulate),
                 health_draw.after(health_simulate),
                 ).run_if(in_state(AppState::Game)).before(net::client::fixed).before(net::host::fixed))
             .add_systems(Update, handle_id_events.run_if(is_client).run_if(in_state(AppState::Connecting)))
-            .add_systems(OnEnter(AppState::Game), (spawn_players, reset_cooldowns))
+            .add_systems(OnEnter(AppState::Game), (fill_roster, spawn_players.after(fill_roster), reset_cooldowns.after(spawn_players)))
+            .add_systems(Update, sync_players.run_if(in_state(AppState::Game)))
             .add_systems(OnEnter(AppState::GameOver), remove_players.after(toggle_leaderboard).after(update_leaderboard))
             .add_event::<SetIdEvent>()
+            .init_resource::<MaxPlayers>()
+            .init_resource::<Roster>()
             .init_resource::<Events<AttackEvent>>()
+            .init_resource::<Events<DashEvent>>()
             .init_resource::<Events<SpawnEvent>>()
             .add_event::<PlayerTickEvent>()
             .add_event::<UserCmdEvent>()
@@ -114,87 +261,151 @@ impl Plugin for PlayerPlugin{
     }
 }
 
-pub fn reset_cooldowns(mut query: Query<&mut Cooldown, With<Player>>) {
-    for mut c in &mut query {
+pub fn reset_cooldowns(mut query: Query<(&mut Cooldown, &mut PlayerDash), With<Player>>) {
+    for (mut c, mut dash) in &mut query {
         c.0.tick(Duration::from_secs_f32(100.));
+        dash.active.tick(Duration::from_secs_f32(100.));
+        dash.cooldown.tick(Duration::from_secs_f32(100.));
     }
 }
 
+/// everyone starts with whoever was in the lobby
+pub fn fill_roster(
+    lobby: Res<Lobby>,
+    mut roster: ResMut<Roster>,
+) {
+    roster.0 = lobby.connected;
+}
+
 pub fn spawn_players(
     mut commands: Commands,
     entity_atlas: Res<Atlas>,
     asset_server: Res<AssetServer>,
-    res_id: Res<PlayerId>
+    res_id: Res<PlayerId>,
+    roster: Res<Roster>,
 ) {
-    for i in 0..MAX_PLAYERS {
-        let pl;
-        pl = commands.spawn((
-            Player(i as u8),
-            PosBuffer(CircularBuffer::new()),
-            DirBuffer(CircularBuffer::new()),
-            EventBuffer(CircularBuffer::new()),
-            HpBuffer(CircularBuffer::new()),
-            Stats {
-                score: 0,
-                enemies_killed: 0,
-                players_killed: 0,
-                camps_captured: 0,
-                deaths: 0,
-                kd_ratio: 0.
-            },
-            Health {
-                current: 0,
-                max: PLAYER_DEFAULT_HP,
-                dead: true
-            },
-            SpriteSheetBundle {
-                texture_atlas: entity_atlas.handle.clone(),
-                sprite: TextureAtlasSprite { index: entity_atlas.coord_to_index(i as i32, 0), ..default()},
-                visibility: Visibility::Hidden,
-                transform: Transform::from_xyz(0., 0., 1.),
-                ..default()
-            },
-            Collider(PLAYER_SIZE),
-            Cooldown(Timer::from_seconds(DEFAULT_COOLDOWN, TimerMode::Once)),
-            StoredPowerUps {
-                power_ups: [0; NUM_POWERUPS],
-            },
-            PlayerShield {
-                active: false,
-            },
-        )).id();
-
-        if i as u8 == res_id.0 {
-            commands.entity(pl).insert(LocalPlayer);
-        }
-
-        let health_bar = commands.spawn((
-            SpriteBundle {
-                texture: asset_server.load("healthbar.png"),
-                transform: Transform {
-                    translation: Vec3::new(0., 24., 2.),
-                    ..Default::default()
-                },
-                ..Default::default()},
-            HealthBar,
-        )).id();
-
-        let shield = commands.spawn(
-            (SpriteBundle {
-            texture: asset_server.load("shield01.png").clone(),
+    for id in 0..MAX_PLAYERS as u8 {
+        if roster.0[id as usize] {
+            spawn_player(&mut commands, &entity_atlas, &asset_server, id, id == res_id.0);
+        }
+    }
+}
+
+/// spawns players that joined mid-match and despawns ones that left
+pub fn sync_players(
+    mut commands: Commands,
+    entity_atlas: Res<Atlas>,
+    asset_server: Res<AssetServer>,
+    res_id: Res<PlayerId>,
+    roster: Res<Roster>,
+    players: Query<(Entity, &Player)>,
+) {
+    if !roster.is_changed() { return }
+    let mut spawned = [false; MAX_PLAYERS];
+    for (e, pl) in &players {
+        spawned[pl.0 as usize] = true;
+        if !roster.0[pl.0 as usize] {
+            commands.entity(e).despawn_recursive();
+        }
+    }
+    for id in 0..MAX_PLAYERS as u8 {
+        if roster.0[id as usize] && !spawned[id as usize] {
+            spawn_player(&mut commands, &entity_atlas, &asset_server, id, id == res_id.0);
+        }
+    }
+}
+
+/// column of the entity sheet a player's sprite comes from
+pub fn sprite_column(id: u8) -> i32 {
+    (id as usize % PLAYER_SPRITES) as i32
+}
+
+/// players past the sheet's columns share a sprite, each time one is reused it gets a new tint
+pub fn sprite_tint(id: u8) -> Color {
+    let variant = id as usize / PLAYER_SPRITES;
+    if variant == 0 { return Color::WHITE }
+    Color::hsl((variant as f32 * 137.5) % 360., 0.7, 0.7)
+}
+
+pub fn spawn_player(
+    commands: &mut Commands,
+    entity_atlas: &Atlas,
+    asset_server: &AssetServer,
+    id: u8,
+    local: bool,
+) {
+    let pl = commands.spawn((
+        Player(id),
+        PosBuffer(CircularBuffer::new()),
+        DirBuffer(CircularBuffer::new()),
+        EventBuffer(CircularBuffer::new()),
+        HpBuffer(CircularBuffer::new()),
+        Stats::default(),
+        Respawn::default(),
+        Health {
+            current: 0,
+            max: PLAYER_DEFAULT_HP,
+            dead: true
+        },
+        SpriteSheetBundle {
+            texture_atlas: entity_atlas.handle.clone(),
+            sprite: TextureAtlasSprite { index: entity_atlas.coord_to_index(sprite_column(id), 0), color: sprite_tint(id), ..default()},
             visibility: Visibility::Hidden,
+            transform: Transform::from_xyz(0., 0., 1.),
+            ..default()
+        },
+        Collider(PLAYER_SIZE),
+        Cooldown(Timer::from_seconds(DEFAULT_COOLDOWN, TimerMode::Once)),
+        StoredPowerUps {
+            power_ups: [0; NUM_POWERUPS],
+        },
+        PlayerShield {
+            active: false,
+            raised_tick: 0,
+            broken: false,
+        },
+        PlayerDash::new(),
+        (Stamina {
+            current: SHIELD_MAX_STAMINA,
+            max: SHIELD_MAX_STAMINA,
+        },
+        StatusEffects::default(),
+        Inventory::default(),
+        DamageLedger::default()),
+    )).id();
+
+    if local {
+        commands.entity(pl).insert(LocalPlayer);
+    }
+
+    let health_bar = commands.spawn((
+        SpriteBundle {
+            texture: asset_server.load("healthbar.png"),
             transform: Transform {
-                translation: Vec3::new(0.0, 0.0, 0.5),
+                translation: Vec3::new(0., 24., 2.),
                 ..Default::default()
             },
+            ..Default::default()},
+        HealthBar,
+    )).id();
+
+    let shield = commands.spawn(
+        (SpriteBundle {
+        texture: asset_server.load("shield01.png").clone(),
+        visibility: Visibility::Hidden,
+        transform: Transform {
+            translation: Vec3::new(0.0, 0.0, 0.5),
             ..Default::default()
-            },
-            Shield)
-        ).id();
+        },
+        ..Default::default()
+        },
+        Shield)
+    ).id();
 
-        commands.entity(pl).add_child(health_bar);
-        commands.entity(pl).add_child(shield);
-    }
+    commands.entity(pl).add_child(health_bar);
+    commands.entity(pl).add_child(shield);
+    spawn_status_icons(commands, pl);
+    profile::spawn_nameplate(commands, asset_server, pl);
 }
 
 pub fn remove_players(
@@ -225,35 +436,79 @@ pub fn update_score(
     }
 }
 
-/// sets powerup ui text, if it changed from before play powerup collection sound
+/// sets powerup ui text from the same multipliers combat uses
 pub fn powerup_feedback(
-    mut players: Query<(&Transform, &mut HpBuffer, &mut Cooldown, &mut StoredPowerUps), With<LocalPlayer>>,
+    players: Query<&StoredPowerUps, With<LocalPlayer>>,
     mut powerup_displays: Query<(&mut Text, &PowerupDisplayText)>,
+    powerup_config: Res<PowerUpConfig>,
 ) {
-    let mut player = players.get_single_mut();
-    if player.is_err() { return }
-    let (tf, mut hb, mut cd, mut spu) = player.unwrap();
+    let spu = players.get_single();
+    if spu.is_err() { return }
+    let spu = spu.unwrap();
     for (mut powerup, index) in &mut powerup_displays {
-        if index.0 == PowerUpType::DamageDealtUp as u8 {
-            powerup.sections[0].value = format!("{:.2}x",
-                (SWORD_DAMAGE as f32 + spu.power_ups[PowerUpType::DamageDealtUp as usize] as f32 * DAMAGE_DEALT_UP as f32)
-                    / SWORD_DAMAGE as f32);
+        let mult = if index.0 == PowerUpType::DamageDealtUp as u8 {
+            powerup_config.damage_mult(spu)
         }
         else if index.0 == PowerUpType::DamageReductionUp as u8 {
-            powerup.sections[0].value = format!("{:.2}x",
-                                                (PLAYER_DEFAULT_DEF
-                                                    / (PLAYER_DEFAULT_DEF * DAMAGE_REDUCTION_UP.powf(spu.power_ups[PowerUpType::DamageReductionUp as usize] as f32))));
+            1. / powerup_config.defense_mult(spu)
         }
         else if index.0 == PowerUpType::AttackSpeedUp as u8 {
-            powerup.sections[0].value = format!("{:.2}x",
-                                                (DEFAULT_COOLDOWN
-                                                    / (cd.0.duration().as_millis() as f32 / 1000.)));
+            powerup_config.attack_speed_mult(spu)
         }
         else if index.0 == PowerUpType::MovementSpeedUp as u8 {
-            powerup.sections[0].value = format!("{:.2}x",
-                                                (PLAYER_SPEED + (spu.power_ups[PowerUpType::MovementSpeedUp as usize] as f32 * MOVEMENT_SPEED_UP as f32))
-                                                    / PLAYER_SPEED);
+            powerup_config.move_speed_mult(spu)
         }
+        else { continue };
+        let capped = powerup_config.capped(spu, POWERUP_TYPES[index.0 as usize]);
+        powerup.sections[0].value = format!("{:.2}x{}", mult, if capped { " (max)" } else { "" });
+    }
+}
+
+/// keeps the attack cooldown in line with attack speed stacks however they changed
+pub fn sync_cooldown(
+    mut players: Query<(&StoredPowerUps, &mut Cooldown), Changed<StoredPowerUps>>,
+    powerup_config: Res<PowerUpConfig>,
+) {
+    for (spu, mut cooldown) in &mut players {
+        let duration = Duration::from_secs_f32(DEFAULT_COOLDOWN / powerup_config.attack_speed_mult(spu));
+        if cooldown.0.duration() != duration {
+            cooldown.0.set_duration(duration);
+        }
+    }
+}
+
+/// scales the stamina bar in the HUD, turning it red while the shield is broken
+pub fn stamina_feedback(
+    players: Query<(&Stamina, &StatusEffects), With<LocalPlayer>>,
+    mut stamina_bars: Query<(&mut Style, &mut BackgroundColor), With<StaminaBar>>,
+) {
+    let player = players.get_single();
+    if player.is_err() { return }
+    let (stamina, effects) = player.unwrap();
+    for (mut style, mut color) in &mut stamina_bars {
+        style.width = Val::Percent(100. * stamina.current as f32 / stamina.max as f32);
+        *color = if effects.active(StatusType::Stun) { Color::RED.into() } else { Color::YELLOW.into() };
+    }
+}
+
+/// sets the attack and dash cooldown text in the HUD
+pub fn cooldown_feedback(
+    players: Query<(&Cooldown, &PlayerDash), With<LocalPlayer>>,
+    mut cooldown_displays: Query<(&mut Text, &CooldownDisplayText)>,
+) {
+    let player = players.get_single();
+    if player.is_err() { return }
+    let (cd, dash) = player.unwrap();
+    for (mut text, ability) in &mut cooldown_displays {
+        let timer = match ability.0 {
+            AbilityType::Attack => &cd.0,
+            AbilityType::Dash => &dash.cooldown,
+        };
+        text.sections[1].value = if timer.finished() {
+            "Ready".to_string()
+        } else {
+            format!("{:.1}s", timer.remaining_secs())
+        };
     }
 }
 
@@ -262,24 +517,24 @@ pub fn powerup_grab_simulate(
     mut commands: Commands,
     tick: Res<TickNum>,
     asset_server: Res<AssetServer>,
-    mut player_query: Query<(&Transform, &mut HpBuffer, &mut Cooldown, &mut StoredPowerUps, Option<&LocalPlayer>), With<Player>>,
+    mut player_query: Query<(&Transform, &mut HpBuffer, &mut StoredPowerUps, &mut Stats, Option<&LocalPlayer>), With<Player>>,
     powerup_query: Query<(Entity, &Transform, &PowerUp), With<PowerUp>>,
+    powerup_config: Res<PowerUpConfig>,
 ) {
-    for (player_transform, mut player_health, mut cooldown, mut player_power_ups, lp) in player_query.iter_mut() {
+    for (player_transform, mut player_health, mut player_power_ups, mut stats, lp) in player_query.iter_mut() {
         for (powerup_entity, powerup_transform, power_up) in powerup_query.iter() {
             let player_pos = player_transform.translation.truncate();
             let powerup_pos = powerup_transform.translation.truncate();
             if player_pos.distance(powerup_pos) < 32. {
+                // capped powerups stay on the ground for someone else
+                if powerup_config.capped(&player_power_ups, power_up.0) { continue }
                 player_power_ups.power_ups[power_up.0 as usize] = player_power_ups.power_ups[power_up.0 as usize].saturating_add(1);
+                stats.powerups_collected = stats.powerups_collected.saturating_add(1);
                 commands.entity(powerup_entity).despawn();
                 if power_up.0 == PowerUpType::Meat {
                     let hp = player_health.0.get(tick.0).unwrap().saturating_add(MEAT_VALUE);
                     player_health.0.set(tick.0, Some(hp));
                 }
-                else if power_up.0 == PowerUpType::AttackSpeedUp {
-                    let updated_duration = cooldown.0.duration().mul_f32(1. / ATTACK_SPEED_UP);
-                    cooldown.0.set_duration(updated_duration);
-                }
                 if lp.is_some() {
                     commands.spawn(AudioBundle {
                         source: asset_server.load("powerup.ogg"),
@@ -296,13 +551,13 @@ pub fn attack_input(
     time: Res<Time>,
     tick: Res<TickNum>,
     mouse_button_inputs: Res<Input<MouseButton>>,
-    mut players: Query<(&mut Cooldown, &mut EventBuffer, &PlayerShield), With<LocalPlayer>>,
+    mut players: Query<(&mut Cooldown, &mut EventBuffer, &PlayerShield, &StatusEffects), With<LocalPlayer>>,
 ) {
     let player = players.get_single_mut();
     if player.is_err() { return }
-    let (mut c, mut eb, shield) = player.unwrap();
-    c.0.tick(time.delta());
-    if shield.active { return }
+    let (mut c, mut eb, shield, effects) = player.unwrap();
+    c.0.tick(time.delta().mul_f32(effects.speed_mult()));
+    if shield.active || effects.active(StatusType::Stun) { return }
     if !(mouse_button_inputs.pressed(MouseButton::Left) && c.0.finished()) {
         return;
     }
@@ -317,6 +572,87 @@ pub fn attack_input(
     c.0.reset();
 }
 
+pub fn dash_input(
+    time: Res<Time>,
+    tick: Res<TickNum>,
+    keyboard_input: Res<Input<KeyCode>>,
+    key_binds: Res<KeyBinds>,
+    mut players: Query<(&mut PlayerDash, &mut EventBuffer, &DirBuffer, &Health, &PlayerShield, &StatusEffects), With<LocalPlayer>>,
+) {
+    let player = players.get_single_mut();
+    if player.is_err() { return }
+    let (mut dash, mut eb, db, hp, shield, effects) = player.unwrap();
+    dash.cooldown.tick(time.delta().mul_f32(effects.speed_mult()));
+    if hp.dead || shield.active || effects.active(StatusType::Stun) { return }
+    if !(keyboard_input.just_pressed(key_binds.dash) && dash.cooldown.finished()) {
+        return;
+    }
+    // dash in the held movement direction, or towards the cursor if standing still
+    let mut dir = input_direction(&keyboard_input, &key_binds);
+    if dir == Vec2::ZERO {
+        let angle = db.0.get(tick.0);
+        if angle.is_none() { return }
+        let angle = angle.unwrap();
+        dir = Vec2 { x: angle.cos(), y: angle.sin() };
+    }
+    let events = eb.0.get(tick.0).unwrap_or(0);
+    eb.0.set(tick.0, Some(events | DASH_BITFLAG));
+    dash.dir = dir;
+    dash.active.reset();
+    dash.cooldown.reset();
+}
+
+pub fn dash_host(
+    players: Query<&EventBuffer, With<LocalPlayer>>,
+    tick: Res<TickNum>,
+    mut dash_writer: EventWriter<DashEvent>
+) {
+    let eb = players.get_single();
+    if eb.is_err() { return }
+    let events = eb.unwrap().0.get(tick.0);
+    if events.is_none() { return }
+    if events.unwrap() & DASH_BITFLAG != 0 {
+        dash_writer.send(DashEvent {
+            seq_num: tick.0,
+            id: 0
+        });
+    }
+}
+
+/// takes the host's own dashes, granting invulnerability frames. clients' dashes are checked as their ticks come in
+pub fn dash_simulate(
+    mut dash_reader: EventReader<DashEvent>,
+    mut players: Query<(&Player, &mut PlayerDash, &mut EventBuffer, &Health, &PlayerShield, &StatusEffects)>,
+) {
+    for ev in &mut dash_reader {
+        for (pl, mut dash, mut eb, hp, shield, effects) in &mut players {
+            if pl.0 != ev.id { continue }
+            if can_dash(&dash, ev.seq_num, hp, shield, effects) {
+                dash.take(ev.seq_num);
+            } else {
+                let events = eb.0.get(ev.seq_num).unwrap_or(0);
+                eb.0.set(ev.seq_num, Some(events & !DASH_BITFLAG));
+            }
+        }
+    }
+}
+
+fn can_dash(dash: &PlayerDash, seq_num: u16, hp: &Health, shield: &PlayerShield, effects: &StatusEffects) -> bool {
+    dash.ready(seq_num) && !(hp.dead || shield.active || effects.active(StatusType::Stun))
+}
+
+/// where a client can really have got to on a tick it says it went from `from` to `to`,
+/// no further than max_step and stopping at walls the way the client's own dash does
+pub fn dash_step(from: Vec2, to: Vec2, max_step: f32, map: &WorldMap) -> Vec2 {
+    dash_move(&from.extend(0.), (to - from).clamp_length_max(max_step), &PLAYER_SIZE, &map.biome_map).truncate()
+}
+
+pub fn dash_iframes_tick(mut players: Query<&mut PlayerDash>) {
+    for mut dash in &mut players {
+        dash.iframes = dash.iframes.saturating_sub(1);
+    }
+}
+
 pub fn attack_host(
     players: Query<(&EventBuffer, &PlayerShield), With<LocalPlayer>>,
     tick: Res<TickNum>,
@@ -380,20 +716,32 @@ pub fn attack_simulate(
     asset_server: Res<AssetServer>,
     tick: Res<TickNum>,
     mut attack_reader: EventReader<AttackEvent>,
-    mut players: Query<(&Player, &PosBuffer, &DirBuffer, &mut HpBuffer, &StoredPowerUps, &PlayerShield, &mut Stats), (Without<ItemChest>, Without<Enemy>)>,
-    mut enemies: Query<(&PosBuffer, &mut HpBuffer, &mut LastAttacker), With<Enemy>>,
-    mut chest: Query<(&Transform, &mut Health), (With<ItemChest>, Without<Enemy>)>,
+    mut players: Query<(&Player, &PosBuffer, &DirBuffer, &mut HpBuffer, &mut StoredPowerUps, &PlayerShield, &PlayerDash, &mut Stamina, &mut StatusEffects, &mut Stats, &mut DamageLedger, &mut Respawn), (Without<ItemChest>, Without<Enemy>)>,
+    mut enemies: Query<(&PosBuffer, &mut HpBuffer, &mut LastAttacker, &mut DamageLedger, &mut StatusEffects), With<Enemy>>,
+    mut chest: Query<(&Transform, &mut Health, &ItemChest), Without<Enemy>>,
+    powerup_config: Res<PowerUpConfig>,
+    game_mode: Res<GameMode>,
+    teams: Res<Teams>,
+    round: Res<Round>,
+    mut kill_feed: ResMut<KillFeed>,
 ) {
+    // the round is over and the final stats are on their way out
+    if round.phase == MatchPhase::Ended {
+        attack_reader.clear();
+        return;
+    }
     for ev in &mut attack_reader {
-        for (pl, pb, db, _, spu, shield, mut stats) in &players {
+        for (pl, pb, db, _, spu, shield, _, _, mut effects, mut stats, _, mut respawn) in &mut players {
             if pl.0 != ev.id { continue }
-            if shield.active { continue }
+            // swinging gives up spawn protection even if it misses
+            respawn.protection = 0;
+            if shield.active || effects.active(StatusType::Stun) { continue }
             let sword_angle = db.0.get(ev.seq_num);
             let player_pos = pb.0.get(ev.seq_num);
             if sword_angle.is_none() || player_pos.is_none() { println!("attack_simulate:none"); continue }
             let sword_angle = sword_angle.unwrap();
             let player_pos = player_pos.unwrap();
-            for (enemy_pb, mut enemy_hb, mut last_attacker) in enemies.iter_mut() {
+            for (enemy_pb, mut enemy_hb, mut last_attacker, mut ledger, mut enemy_effects) in enemies.iter_mut() {
                 let enemy_pos = enemy_pb.0.get(ev.seq_num);
                 if enemy_pos.is_none() { println!("attack_simulate:enemynone"); continue }
                 let enemy_pos = enemy_pos.unwrap();
@@ -406,14 +754,18 @@ pub fn attack_simulate(
                 if player_pos.distance(enemy_pos) > SWORD_LENGTH { continue; } // enemy too far
                 if angle_diff.abs() > SWORD_DEGREES.to_radians() { continue; } // enemy not in sector
                 last_attacker.0 = Some(pl.0);
-                let damage = SWORD_DAMAGE.saturating_add(spu.power_ups[PowerUpType::DamageDealtUp as usize].saturating_mul(DAMAGE_DEALT_UP));
+                let damage = sword_damage(&spu, &effects, &powerup_config);
+                ledger.record(pl.0, damage.min(hp));
+                stats.record_damage(damage.min(hp));
                 enemy_hb.0.set(tick.0, Some(hp.saturating_sub(damage)));
+                powerup_config.enchant(&spu, &mut enemy_effects);
                 commands.spawn(AudioBundle {
                     source: asset_server.load("hitHurt.ogg"),
                     ..default()
                 });
             }
-            for (chest_tf, mut chest_hp) in chest.iter_mut() {
+            for (chest_tf, mut chest_hp, item_chest) in chest.iter_mut() {
+                if chest_hp.current == 0 { continue }
                 let chest_pos = chest_tf.translation.truncate();
                 if player_pos.distance(chest_pos) > SWORD_LENGTH { continue; } // chest too far
 
@@ -423,6 +775,10 @@ pub fn attack_simulate(
                 if angle_diff.abs() > SWORD_DEGREES.to_radians() { continue; } // chest not in sector
 
                 chest_hp.current = 0;
+                stats.chests_opened = stats.chests_opened.saturating_add(1);
+                if item_chest.trapped {
+                    effects.apply(StatusType::Poison, STATUS_TICKS[StatusType::Poison as usize]);
+                }
                 /*
                 TODO this only spawns on host?
                 commands.spawn(AudioBundle {
@@ -431,16 +787,20 @@ pub fn attack_simulate(
                 });*/
             }
         }
+        // players downed by this swing, credited once all of its damage is in
+        let mut killed: Vec<u8> = Vec::new();
         let mut combinations = players.iter_combinations_mut();
-        while let Some([(pl, pb, db, _, spu, attacker_shield, mut attacker_stats), (target_pl, target_pb, _, mut target_hb, target_spu, target_shield, mut target_stats)]) = combinations.fetch_next() {
+        while let Some([(pl, pb, db, _, spu, attacker_shield, _, _, mut attacker_effects, mut attacker_stats, _, _), (target_pl, target_pb, _, mut target_hb, _, target_shield, target_dash, mut target_stamina, mut target_effects, mut target_stats, mut target_ledger, target_respawn)]) = combinations.fetch_next() {
             if pl.0 != ev.id { continue }
-            if target_shield.active || attacker_shield.active { continue }
+            if attacker_shield.active || attacker_effects.active(StatusType::Stun) { continue }
+            if target_dash.invulnerable() || target_respawn.protected() { continue }
             let sword_angle = db.0.get(ev.seq_num);
             let player_pos = pb.0.get(ev.seq_num);
             if sword_angle.is_none() || player_pos.is_none() { continue }
             let sword_angle = sword_angle.unwrap();
             let player_pos = player_pos.unwrap();
             if target_pl.0 == ev.id { continue }
+            if !round.phase.can_fight() || !game_mode.can_damage(&teams, pl.0, target_pl.0) { continue }
             let target_pos = target_pb.0.get(ev.seq_num);
             if target_pos.is_none() { continue }
             let target_pos = target_pos.unwrap();
@@ -451,37 +811,35 @@ pub fn attack_simulate(
             let angle_diff = angle_diff.sin().atan2(angle_diff.cos());
             if angle_diff.abs() > SWORD_DEGREES.to_radians() { continue; } // target not in sector
 
-            let damage = SWORD_DAMAGE.saturating_add(spu.power_ups[PowerUpType::DamageDealtUp as usize].saturating_mul(DAMAGE_DEALT_UP));
-            let hp = target_hb.0.get(tick.0).unwrap().saturating_sub(damage);
+            if target_shield.active {
+                block_hit(&mut commands, &asset_server, tick.0, target_shield, &mut target_stamina, &mut target_effects, &mut attacker_effects);
+                continue;
+            }
+
+            let damage = sword_damage(&spu, &attacker_effects, &powerup_config);
+            let prev_hp = target_hb.0.get(tick.0).unwrap();
+            let hp = prev_hp.saturating_sub(damage);
             target_hb.0.set(tick.0, Some(hp));
+            powerup_config.enchant(&spu, &mut target_effects);
+            target_ledger.record(pl.0, damage.min(prev_hp));
+            attacker_stats.record_damage(damage.min(prev_hp));
+            target_stats.record_hurt(damage.min(prev_hp));
             if hp <= 0 {
-                target_stats.deaths = target_stats.deaths.saturating_add(1);
-                if target_stats.deaths != 0 {
-                    target_stats.kd_ratio = target_stats.players_killed as f32 / target_stats.deaths as f32;
-                }
-                else {
-                    target_stats.kd_ratio = target_stats.players_killed as f32;
-                }
-                attacker_stats.players_killed = attacker_stats.players_killed.saturating_add(1);
-                if attacker_stats.deaths != 0 {
-                    attacker_stats.kd_ratio = attacker_stats.players_killed as f32 / attacker_stats.deaths as f32;
-                }
-                else {
-                    attacker_stats.kd_ratio = attacker_stats.players_killed as f32;
-                }
-                attacker_stats.score += 20;
+                killed.push(target_pl.0);
             }
         }
         let mut combinations = players.iter_combinations_mut();
-        while let Some([(target_pl, target_pb, _, mut target_hb, target_spu, target_shield, mut target_stats), (pl, pb, db, _, spu, attacker_shield, mut attacker_stats)]) = combinations.fetch_next() {
+        while let Some([(target_pl, target_pb, _, mut target_hb, _, target_shield, target_dash, mut target_stamina, mut target_effects, mut target_stats, mut target_ledger, target_respawn), (pl, pb, db, _, spu, attacker_shield, _, _, mut attacker_effects, mut attacker_stats, _, _)]) = combinations.fetch_next() {
             if pl.0 != ev.id { continue }
-            if target_shield.active || attacker_shield.active { continue }
+            if attacker_shield.active || attacker_effects.active(StatusType::Stun) { continue }
+            if target_dash.invulnerable() || target_respawn.protected() { continue }
             let sword_angle = db.0.get(ev.seq_num);
             let player_pos = pb.0.get(ev.seq_num);
             if sword_angle.is_none() || player_pos.is_none() { continue }
             let sword_angle = sword_angle.unwrap();
             let player_pos = player_pos.unwrap();
             if target_pl.0 == ev.id { continue }
+            if !round.phase.can_fight() || !game_mode.can_damage(&teams, pl.0, target_pl.0) { continue }
             let target_pos = target_pb.0.get(ev.seq_num);
             if target_pos.is_none() { continue }
             let target_pos = target_pos.unwrap();
@@ -492,27 +850,47 @@ pub fn attack_simulate(
             let angle_diff = angle_diff.sin().atan2(angle_diff.cos());
             if angle_diff.abs() > SWORD_DEGREES.to_radians() { continue; } // target not in sector
 
-            let damage = SWORD_DAMAGE.saturating_add(spu.power_ups[PowerUpType::DamageDealtUp as usize].saturating_mul(DAMAGE_DEALT_UP));
-            let hp = target_hb.0.get(tick.0).unwrap().saturating_sub(damage);
+            if target_shield.active {
+                block_hit(&mut commands, &asset_server, tick.0, target_shield, &mut target_stamina, &mut target_effects, &mut attacker_effects);
+                continue;
+            }
+
+            let damage = sword_damage(&spu, &attacker_effects, &powerup_config);
+            let prev_hp = target_hb.0.get(tick.0).unwrap();
+            let hp = prev_hp.saturating_sub(damage);
             target_hb.0.set(tick.0, Some(hp));
+            powerup_config.enchant(&spu, &mut target_effects);
+            target_ledger.record(pl.0, damage.min(prev_hp));
+            attacker_stats.record_damage(damage.min(prev_hp));
+            target_stats.record_hurt(damage.min(prev_hp));
             if hp <= 0 {
-                target_stats.deaths = target_stats.deaths.saturating_add(1);
-                if target_stats.deaths != 0 {
-                    target_stats.kd_ratio = target_stats.players_killed as f32 / target_stats.deaths as f32;
-                }
-                else {
-                    target_stats.kd_ratio = target_stats.players_killed as f32;
-                }
-                attacker_stats.players_killed = attacker_stats.players_killed.saturating_add(1);
-                if attacker_stats.deaths != 0 {
-                    attacker_stats.kd_ratio = attacker_stats.players_killed as f32 / attacker_stats.deaths as f32;
-                }
-                else {
-                    attacker_stats.kd_ratio = attacker_stats.players_killed as f32;
-                }
-                attacker_stats.score += 20;
+                killed.push(target_pl.0);
+            }
+        }
+        // the kill goes to whoever did the most damage since the victim last died, not to the last hit,
+        // and anyone else who did enough gets an assist
+        let mut assists: Vec<u8> = Vec::new();
+        for victim in killed {
+            let Some((_, _, _, _, victim_spu, _, _, _, _, _, mut ledger, _)) = players.iter_mut().find(|(pl, ..)| pl.0 == victim) else { continue };
+            let Some(killer) = ledger.top() else { continue };
+            assists.extend(ledger.assists(killer));
+            ledger.clear();
+            let mut dropped = victim_spu.clone();
+            let Some((_, _, _, _, mut spu, _, _, _, _, mut stats, _, _)) = players.iter_mut().find(|(pl, ..)| pl.0 == killer) else { continue };
+            stats.record_kill();
+            kill_feed.record(tick.0, killer, victim);
+            game_mode.award(&mut stats, ScoreEvent::PlayerKill, 1.0);
+            powerup_config.transfer_on_death(&mut dropped, &mut spu);
+            if let Some((_, _, _, _, mut victim_spu, ..)) = players.iter_mut().find(|(pl, ..)| pl.0 == victim) {
+                *victim_spu = dropped;
             }
         }
+        for (pl, _, _, _, _, _, _, _, _, mut stats, _, _) in &mut players {
+            let count = assists.iter().filter(|id| **id == pl.0).count() as u16;
+            if count == 0 { continue }
+            stats.assists = stats.assists.saturating_add(count);
+            game_mode.award(&mut stats, ScoreEvent::PlayerAssist, count as f32);
+        }
     }
 }
 
@@ -556,11 +934,11 @@ pub fn animate_sword(
 pub fn shield_input(
     tick: Res<TickNum>,
     mouse_button_inputs: Res<Input<MouseButton>>,
-    mut players: Query<(&mut EventBuffer, &mut PlayerShield), With<LocalPlayer>>
+    mut players: Query<(&mut EventBuffer, &StatusEffects), With<LocalPlayer>>
 ) {
-    for (mut eb, mut shield) in &mut players {
+    for (mut eb, effects) in &mut players {
         let events = if eb.0.get(tick.0).is_some() {eb.0.get(tick.0).unwrap()} else {0};
-        if mouse_button_inputs.pressed(MouseButton::Right) {
+        if mouse_button_inputs.pressed(MouseButton::Right) && !effects.active(StatusType::Stun) {
             eb.0.set(tick.0, Some(events | SHIELD_BITFLAG));
         }
         else {
@@ -571,14 +949,26 @@ pub fn shield_input(
 
 pub fn shield_draw(
     tick: Res<TickNum>,
-    mut players: Query<(&EventBuffer, &mut PlayerShield, &Children)>,
+    mut players: Query<(&EventBuffer, &mut PlayerShield, &StatusEffects, &Children)>,
     mut shields: Query<&mut Visibility, With<Shield>>,
 ) {
-    for (eb, mut ps, children) in &mut players {
+    for (eb, mut ps, effects, children) in &mut players {
         for child in children.iter() {
             let vis = shields.get_mut(*child);
             if let Ok(mut vis) = vis {
-                if eb.0.get(tick.0.saturating_sub(net::DELAY)).unwrap_or(0) & SHIELD_BITFLAG != 0 {
+                // a broken shield stays down until the stun wears off and the shield is pressed again,
+                // otherwise holding it would raise it with no stamina and break it straight away
+                let held = eb.0.get(tick.0.saturating_sub(net::DELAY)).unwrap_or(0) & SHIELD_BITFLAG != 0;
+                if !held {
+                    ps.broken = false;
+                }
+                if ps.active && effects.active(StatusType::Stun) {
+                    ps.broken = true;
+                }
+                if held && !ps.broken && !effects.active(StatusType::Stun) {
+                    if !ps.active {
+                        ps.raised_tick = tick.0;
+                    }
                     ps.active = true;
                     *vis = Visibility::Visible;
                 }
@@ -591,15 +981,77 @@ pub fn shield_draw(
     }
 }
 
+/// blocks a hit on a raised shield. a fresh shield parries and staggers the attacker,
+/// otherwise the block costs stamina
+pub fn block_hit(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    tick: u16,
+    shield: &PlayerShield,
+    stamina: &mut Stamina,
+    effects: &mut StatusEffects,
+    attacker_effects: &mut StatusEffects,
+) {
+    if shield.parrying(tick) {
+        attacker_effects.apply(StatusType::Stun, PARRY_STAGGER_TICKS);
+    }
+    else {
+        drain_stamina(stamina, effects, SHIELD_BLOCK_COST);
+    }
+    commands.spawn(AudioBundle {
+        source: asset_server.load("shield.ogg"),
+        ..default()
+    });
+}
+
+/// takes stamina from a player, breaking their shield and stunning them if it runs out
+pub fn drain_stamina(stamina: &mut Stamina, effects: &mut StatusEffects, amount: u8) {
+    stamina.current = stamina.current.saturating_sub(amount);
+    if stamina.current == 0 {
+        effects.apply(StatusType::Stun, SHIELD_BREAK_STUN_TICKS);
+    }
+}
+
+/// sword damage after damage powerups and weakening statuses
+pub fn sword_damage(spu: &StoredPowerUps, effects: &StatusEffects, powerup_config: &PowerUpConfig) -> u8 {
+    (SWORD_DAMAGE as f32 * powerup_config.damage_mult(spu) * effects.damage_mult()).min(u8::MAX as f32) as u8
+}
+
+/// drains stamina while the shield is held and regenerates it while lowered
+pub fn stamina_simulate(
+    mut players: Query<(&PlayerShield, &mut Stamina, &mut StatusEffects), With<Player>>,
+) {
+    for (shield, mut stamina, mut effects) in &mut players {
+        tick_stamina(shield.active, &mut stamina, &mut effects);
+    }
+}
+
+pub fn tick_stamina(shield_active: bool, stamina: &mut Stamina, effects: &mut StatusEffects) {
+    if shield_active {
+        drain_stamina(stamina, effects, SHIELD_DRAIN);
+    }
+    else {
+        stamina.current = stamina.current.saturating_add(SHIELD_REGEN).min(stamina.max);
+    }
+}
+
 pub fn spawn_simulate(
     tick: Res<TickNum>,
     mut spawn_reader: EventReader<SpawnEvent>,
-    mut players: Query<(&Player, &mut HpBuffer)>
+    mut players: Query<(&Player, &Health, &mut HpBuffer, &mut Stamina, &mut StatusEffects, &mut DamageLedger, &Stats, &mut Respawn)>,
+    game_mode: Res<GameMode>,
 ) {
     for ev in &mut spawn_reader {
-        for (pl, mut hb) in &mut players {
+        for (pl, hp, mut hb, mut stamina, mut effects, mut ledger, stats, mut respawn) in &mut players {
             if pl.0 != ev.id { continue }
+            // only the dead come back, a living player asking would get a free heal
+            if !hp.dead || !respawn.ready() { continue }
+            if !game_mode.can_respawn(stats) { continue }
             hb.0.set(tick.0, Some(PLAYER_DEFAULT_HP));
+            respawn.protection = SPAWN_PROTECTION_TICKS;
+            stamina.current = stamina.max;
+            effects.clear();
+            ledger.clear();
         }
     }
     spawn_reader.clear();
@@ -607,19 +1059,23 @@ pub fn spawn_simulate(
 
 pub fn health_simulate(
     tick: Res<TickNum>,
-    mut players: Query<(&HpBuffer, &mut Health, &mut Visibility, &mut Stats, Option<&LocalPlayer>)>,
+    mut players: Query<(Entity, &HpBuffer, &mut Health, &mut Visibility, &mut Stats, &mut StoredPowerUps, &mut Respawn, Option<&LocalPlayer>)>,
+    is_host: Res<IsHost>,
+    death_penalty: Res<DeathPenalty>,
+    game_mode: Res<GameMode>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut death_writer: EventWriter<LocalPlayerDeathEvent>,
     mut spawn_writer: EventWriter<LocalPlayerSpawnEvent>,
 ) {
-    for (hb, mut hp, mut vis, mut stats, lp) in &mut players {
+    for (e, hb, mut hp, mut vis, mut stats, mut spu, mut respawn, lp) in &mut players {
         let next_hp = hb.0.get(tick.0);
         if next_hp.is_none() { continue }
         hp.current = next_hp.unwrap();
         hp.max = PLAYER_DEFAULT_HP;
         if hp.current > 0 && hp.dead {
             hp.dead = false;
+            commands.entity(e).insert(Joined);
             if lp.is_some() {
                 spawn_writer.send(LocalPlayerSpawnEvent);
             }
@@ -632,10 +1088,19 @@ pub fn health_simulate(
             });
             hp.dead = true;
             *vis = Visibility::Hidden;
+            // counted here rather than on the killing blow so deaths to enemies count too, stats come from the host on clients
+            if is_host.0 {
+                respawn.wait = respawn_delay(stats.kill_streak, tick.0);
+                stats.record_death();
+                death_penalty.apply(&game_mode, &mut stats, &mut spu);
+            }
             if lp.is_some() {
                 death_writer.send(LocalPlayerDeathEvent);
             }
         }
+        if is_host.0 && !hp.dead {
+            stats.ticks_alive = stats.ticks_alive.saturating_add(1);
+        }
     }
 }
 
@@ -658,23 +1123,27 @@ pub fn health_draw(
 pub fn handle_player_ticks(
     tick: Res<TickNum>,
     mut player_reader: EventReader<PlayerTickEvent>,
-    mut player_query: Query<(&Player, &mut PosBuffer, &mut HpBuffer, &mut DirBuffer, &mut EventBuffer, &mut PlayerShield, &mut Stats, &mut StoredPowerUps, &mut Cooldown, Option<&LocalPlayer>)>,
+    mut player_query: Query<(&Player, &mut PosBuffer, &mut HpBuffer, &mut DirBuffer, &mut EventBuffer, &mut PlayerShield, &mut Stats, &mut StoredPowerUps, (&mut Stamina, &mut StatusEffects, &mut Inventory, &mut Respawn), Option<&LocalPlayer>)>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    mut teams: ResMut<Teams>,
 ) {
     for ev in player_reader.iter() {
-        for (pl, mut pb, mut hb, mut db, mut eb, mut shield, mut stats, mut spu, mut cooldown, local) in &mut player_query {
+        for (pl, mut pb, mut hb, mut db, mut eb, mut shield, mut stats, mut spu, (mut stamina, mut effects, mut inv, mut respawn), local) in &mut player_query {
             if pl.0 == ev.tick.id {
                 *stats = ev.tick.stats.clone();
+                if teams.members[pl.0 as usize] != Some(ev.tick.team) {
+                    teams.members[pl.0 as usize] = Some(ev.tick.team);
+                }
+                stamina.current = ev.tick.stamina;
+                *effects = StatusEffects::unpack(ev.tick.status);
+                *inv = ev.tick.inventory.clone();
+                respawn.wait = ev.tick.respawn_wait;
+                respawn.protection = ev.tick.protection;
 
                 let prev = spu.clone();
                 *spu = ev.tick.powerups.clone();
                 if prev != *spu {
-                    if prev.power_ups[PowerUpType::AttackSpeedUp as usize] !=
-                        spu.power_ups[PowerUpType::AttackSpeedUp as usize] {
-                        let updated_duration = DEFAULT_COOLDOWN * (1. / ATTACK_SPEED_UP).powi(spu.power_ups[PowerUpType::AttackSpeedUp as usize] as i32);
-                        cooldown.0.set_duration(Duration::from_secs_f32(updated_duration));
-                    }
                     commands.spawn(AudioBundle {
                         source: asset_server.load("powerup.ogg"),
                         ..default()
@@ -703,29 +1172,54 @@ pub fn handle_id_events(
 ) {
     for ev in &mut id_reader {
         res_id.0 = ev.0;
-        app_state_next_state.set(AppState::Game);
+        app_state_next_state.set(AppState::Lobby);
     }
 }
 
+/// takes in a client's tick. positions are the client's own, except that a dash is checked against
+/// the host's cooldown and on every tick it lasts can't go further than a dash or through walls.
+/// rejected dashes are removed from the event buffer so they aren't sent to other clients
 pub fn handle_usercmd_events(
     mut usercmd_reader: EventReader<UserCmdEvent>,
-    mut player_query: Query<(&Player, &mut PosBuffer, &mut DirBuffer, &mut EventBuffer, &mut PlayerShield)>,
+    mut player_query: Query<(&Player, &mut PosBuffer, &mut DirBuffer, &mut EventBuffer, &mut PlayerShield, &Health, &Respawn, &mut PlayerDash, &StatusEffects)>,
+    map: Res<WorldMap>,
     mut attack_writer: EventWriter<AttackEvent>,
     mut spawn_writer: EventWriter<SpawnEvent>,
+    mut item_writer: EventWriter<ItemUseEvent>,
 ) {
     for ev in usercmd_reader.iter() {
-        for (pl, mut pb, mut db, mut eb, mut shield) in &mut player_query {
+        for (pl, mut pb, mut db, mut eb, mut shield, hp, respawn, mut dash, effects) in &mut player_query {
             if pl.0 == ev.id {
                 pb.0.set_with_time(ev.seq_num, Some(ev.tick.pos), ev.seq_num);
                 db.0.set(ev.seq_num, Some(ev.tick.dir));
-                eb.0.set(ev.seq_num, Some(ev.tick.events));
+                let mut events = ev.tick.events;
+                let dashed = events & DASH_BITFLAG != 0;
+                if dashed && can_dash(&dash, ev.seq_num, hp, &shield, effects) {
+                    dash.take(ev.seq_num);
+                } else {
+                    events &= !DASH_BITFLAG;
+                }
+                if dashed || dash.dashing(ev.seq_num) {
+                    if let (Some(from), Some(to)) = (*pb.0.get(ev.seq_num.wrapping_sub(1)), *pb.0.get(ev.seq_num)) {
+                        let target = dash_step(from, to, dash.max_step(ev.seq_num), &map);
+                        dash.spend(from.distance(target));
+                        if target != to {
+                            pb.0.set(ev.seq_num, Some(target));
+                        }
+                    }
+                }
+                eb.0.set(ev.seq_num, Some(events));
                 if ev.tick.events & ATTACK_BITFLAG != 0 {
                     attack_writer.send(AttackEvent { seq_num: ev.seq_num, id: ev.id });
                 }
-                if ev.tick.events & SPAWN_BITFLAG != 0 {
+                // the client shouldn't ask before its wait is up, but the host gets the final say
+                if ev.tick.events & SPAWN_BITFLAG != 0 && hp.dead && respawn.ready() {
                     spawn_writer.send(SpawnEvent { id: ev.id });
                 }
-                if ev.tick.events & SHIELD_BITFLAG != 0 {
+                if let Some(item) = decode_item(ev.tick.events) {
+                    item_writer.send(ItemUseEvent { seq_num: ev.seq_num, id: ev.id, item });
+                }
+                if ev.tick.events & SHIELD_BITFLAG != 0 && !shield.broken {
                     shield.active = true;
                 }
             }
@@ -740,4 +1234,101 @@ pub fn local_player_dead(health: Query<&Health, With<LocalPlayer>>) -> bool {
     if health.is_err() { return false; }
     let health = health.unwrap();
     return health.dead;
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::map::{Biome, MAPSIZE, TILESIZE};
+
+    #[test]
+    fn dash_cooldown_wraps() {
+        let mut dash = PlayerDash::new();
+        assert!(dash.ready(0));
+        dash.take(u16::MAX - 5);
+        assert!(!dash.ready(u16::MAX - 5));
+        assert!(!dash.ready(DASH_COOLDOWN_TICKS - 7));
+        assert!(dash.ready(DASH_COOLDOWN_TICKS - 6));
+        assert_eq!(dash.iframes, DASH_IFRAME_TICKS);
+    }
+
+    #[test]
+    fn dash_budget_covers_the_whole_dash() {
+        let mut dash = PlayerDash::new();
+        assert_eq!(dash.max_step(10), DASH_SLACK);
+        dash.take(10);
+        // a dash split over its ticks can't add up to more than one dash
+        let mut moved = 0.;
+        for seq in 10..10 + DASH_TICKS {
+            let step = dash.max_step(seq);
+            moved += step;
+            dash.spend(step);
+        }
+        assert!((moved - (DASH_DISTANCE + DASH_SLACK * DASH_TICKS as f32)).abs() < 1e-3);
+        assert_eq!(dash.max_step(10 + DASH_TICKS), DASH_SLACK);
+        // walking on a dash tick leaves the rest for the next one
+        dash.take(100);
+        dash.spend(DASH_SLACK);
+        assert_eq!(dash.max_step(101), DASH_SLACK + DASH_DISTANCE);
+    }
+
+    #[test]
+    fn dash_step_stops_at_walls() {
+        let mut map = WorldMap { map_size: MAPSIZE, tile_size: TILESIZE, biome_map: [[Biome::Ground; MAPSIZE]; MAPSIZE] };
+        let wall_col = MAPSIZE / 2 + 7;
+        for row in map.biome_map.iter_mut() {
+            row[wall_col] = Biome::Wall;
+        }
+        let wall_x = (TILESIZE * 7) as f32;
+        let to = dash_step(Vec2::ZERO, Vec2::new(300., 0.), DASH_SLACK + DASH_DISTANCE, &map);
+        assert!(to.x > 0. && to.x < wall_x);
+        let to = dash_step(Vec2::ZERO, Vec2::new(0., 300.), DASH_SLACK + DASH_DISTANCE, &map);
+        assert!(to.distance(Vec2::new(0., DASH_SLACK + DASH_DISTANCE)) < 1e-3);
+    }
+
+    #[test]
+    fn held_shield_drains_stamina_until_it_breaks() {
+        let mut stamina = Stamina { current: SHIELD_MAX_STAMINA, max: SHIELD_MAX_STAMINA };
+        let mut effects = StatusEffects::default();
+        let mut ticks = 0;
+        while !effects.active(StatusType::Stun) {
+            tick_stamina(true, &mut stamina, &mut effects);
+            ticks += 1;
+        }
+        assert_eq!(ticks, SHIELD_MAX_STAMINA / SHIELD_DRAIN);
+        assert_eq!(stamina.current, 0);
+        assert_eq!(effects.0[StatusType::Stun as usize].ticks, SHIELD_BREAK_STUN_TICKS);
+    }
+
+    #[test]
+    fn lowered_shield_regenerates_up_to_max() {
+        let mut stamina = Stamina { current: SHIELD_MAX_STAMINA - 1, max: SHIELD_MAX_STAMINA };
+        let mut effects = StatusEffects::default();
+        tick_stamina(false, &mut stamina, &mut effects);
+        assert_eq!(stamina.current, SHIELD_MAX_STAMINA);
+        stamina.current = 0;
+        tick_stamina(false, &mut stamina, &mut effects);
+        assert_eq!(stamina.current, SHIELD_REGEN);
+        assert!(!effects.active(StatusType::Stun));
+    }
+
+    #[test]
+    fn blocks_cost_stamina_and_break_the_shield() {
+        let mut stamina = Stamina { current: SHIELD_BLOCK_COST + 1, max: SHIELD_MAX_STAMINA };
+        let mut effects = StatusEffects::default();
+        drain_stamina(&mut stamina, &mut effects, SHIELD_BLOCK_COST);
+        assert_eq!(stamina.current, 1);
+        assert!(!effects.active(StatusType::Stun));
+        drain_stamina(&mut stamina, &mut effects, SHIELD_BLOCK_COST);
+        assert_eq!(stamina.current, 0);
+        assert!(effects.active(StatusType::Stun));
+    }
+
+    #[test]
+    fn parry_window_follows_the_raise() {
+        let shield = PlayerShield { active: true, raised_tick: u16::MAX, broken: false };
+        assert!(shield.parrying(u16::MAX));
+        assert!(shield.parrying(PARRY_WINDOW_TICKS - 1));
+        assert!(!shield.parrying(PARRY_WINDOW_TICKS));
+        assert!(!PlayerShield { active: false, ..shield }.parrying(u16::MAX));
+    }
+}
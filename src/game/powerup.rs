@@ -0,0 +1,217 @@
+use bevy::prelude::*;
+use rand::Rng;
+use crate::game::components::*;
+use crate::game::status::{StatusEffects, StatusType, STATUS_TICKS};
+
+const ENCHANT_STACKS: u8 = 3; // stacks of a powerup before the sword inflicts its status
+/// the status a stacked powerup puts on everything the sword hits, indexed by PowerUpType
+const ENCHANTS: [Option<StatusType>; NUM_POWERUPS] = [None, Some(StatusType::Burn), None, Some(StatusType::Poison), Some(StatusType::Slow)];
+/// the choices on the lobby page, meat is never capped
+pub const POWERUP_CAPS: [u8; 4] = [4, 8, 16, u8::MAX];
+pub const DEATH_DROPS: [f32; 4] = [0., 0.25, 0.5, 1.];
+/// how much faster than the defaults each stack loses value, 0 turns diminishing returns off
+const FALLOFF_STEEPNESS: [(f32, &str); 3] = [(1., "Normal"), (2., "Steep"), (0., "Off")];
+
+/// How stacks of one powerup type turn into a stat bonus
+#[derive(Copy, Clone)]
+pub struct PowerUpCurve {
+    pub cap: u8, // stacks past this aren't picked up
+    pub per_stack: f32, // bonus from the first stack
+    pub falloff: f32, // each stack is worth this much of the one before it, 1 is linear
+    pub weight: u32, // how likely it is to show up in a chest compared to the others
+}
+
+impl PowerUpCurve {
+    /// total bonus from a number of stacks, a geometric series so it approaches per_stack / (1 - falloff)
+    pub fn bonus(&self, stacks: u8) -> f32 {
+        let n = stacks.min(self.cap) as f32;
+        if self.falloff >= 1. {
+            return self.per_stack * n;
+        }
+        self.per_stack * (1. - self.falloff.powf(n)) / (1. - self.falloff)
+    }
+}
+
+/// Tuning for every powerup type, indexed by PowerUpType
+#[derive(Resource, Clone)]
+pub struct PowerUpConfig {
+    pub curves: [PowerUpCurve; NUM_POWERUPS],
+    pub death_drop: f32, // fraction of each stack a killed player loses to their killer, 0 turns it off
+}
+
+impl Default for PowerUpConfig {
+    fn default() -> PowerUpConfig {
+        PowerUpConfig {
+            curves: [
+                PowerUpCurve { cap: u8::MAX, per_stack: 0., falloff: 1., weight: 2 }, // Meat
+                PowerUpCurve { cap: 8, per_stack: 0.25, falloff: 0.85, weight: 1 }, // DamageDealtUp
+                PowerUpCurve { cap: 8, per_stack: 0.11, falloff: 0.85, weight: 1 }, // DamageReductionUp
+                PowerUpCurve { cap: 8, per_stack: 0.1, falloff: 0.9, weight: 1 }, // AttackSpeedUp
+                PowerUpCurve { cap: 8, per_stack: 0.06, falloff: 0.9, weight: 1 }, // MovementSpeedUp
+            ],
+            death_drop: 0.5,
+        }
+    }
+}
+
+impl PowerUpConfig {
+    fn mult(&self, spu: &StoredPowerUps, ptype: PowerUpType) -> f32 {
+        1. + self.curves[ptype as usize].bonus(spu.power_ups[ptype as usize])
+    }
+
+    /// multiplier on damage dealt
+    pub fn damage_mult(&self, spu: &StoredPowerUps) -> f32 {
+        self.mult(spu, PowerUpType::DamageDealtUp)
+    }
+
+    /// multiplier on damage taken
+    pub fn defense_mult(&self, spu: &StoredPowerUps) -> f32 {
+        1. / self.mult(spu, PowerUpType::DamageReductionUp)
+    }
+
+    /// multiplier on attack rate, the cooldown is divided by this
+    pub fn attack_speed_mult(&self, spu: &StoredPowerUps) -> f32 {
+        self.mult(spu, PowerUpType::AttackSpeedUp)
+    }
+
+    pub fn move_speed_mult(&self, spu: &StoredPowerUps) -> f32 {
+        self.mult(spu, PowerUpType::MovementSpeedUp)
+    }
+
+    /// true if another stack of this type would do nothing
+    pub fn capped(&self, spu: &StoredPowerUps, ptype: PowerUpType) -> bool {
+        spu.power_ups[ptype as usize] >= self.curves[ptype as usize].cap
+    }
+
+    /// picks a powerup type using the weights
+    pub fn roll<R: Rng>(&self, rng: &mut R) -> u8 {
+        let total: u32 = self.curves.iter().map(|c| c.weight).sum();
+        let mut roll = rng.gen_range(0..total.max(1));
+        for (i, curve) in self.curves.iter().enumerate() {
+            if roll < curve.weight { return i as u8 }
+            roll -= curve.weight;
+        }
+        0
+    }
+
+    /// puts the statuses the attacker's powerups give their sword on whatever it hit
+    pub fn enchant(&self, spu: &StoredPowerUps, target: &mut StatusEffects) {
+        for (i, status) in ENCHANTS.iter().enumerate() {
+            let Some(status) = status else { continue };
+            if spu.power_ups[i] >= ENCHANT_STACKS {
+                target.apply(*status, STATUS_TICKS[*status as usize]);
+            }
+        }
+    }
+
+    /// the cap on everything but meat, as picked in the lobby
+    pub fn cap(&self) -> u8 {
+        self.curves[PowerUpType::DamageDealtUp as usize].cap
+    }
+
+    pub fn cap_name(&self) -> String {
+        if self.cap() == u8::MAX { "None".to_string() } else { self.cap().to_string() }
+    }
+
+    pub fn next_cap(&mut self) {
+        let i = POWERUP_CAPS.iter().position(|cap| *cap == self.cap()).map_or(0, |i| (i + 1) % POWERUP_CAPS.len());
+        for curve in self.curves.iter_mut().skip(1) {
+            curve.cap = POWERUP_CAPS[i];
+        }
+    }
+
+    /// which FALLOFF_STEEPNESS the curves are on, custom curves count as the first
+    fn steepness(&self) -> usize {
+        let defaults = PowerUpConfig::default().curves;
+        FALLOFF_STEEPNESS.iter().position(|(steepness, _)| (1..NUM_POWERUPS)
+            .all(|i| self.curves[i].falloff == steepen(defaults[i].falloff, *steepness)))
+            .unwrap_or(0)
+    }
+
+    pub fn falloff_name(&self) -> &'static str {
+        FALLOFF_STEEPNESS[self.steepness()].1
+    }
+
+    pub fn next_falloff(&mut self) {
+        let defaults = PowerUpConfig::default().curves;
+        let (steepness, _) = FALLOFF_STEEPNESS[(self.steepness() + 1) % FALLOFF_STEEPNESS.len()];
+        for i in 1..NUM_POWERUPS {
+            self.curves[i].falloff = steepen(defaults[i].falloff, steepness);
+        }
+    }
+
+    pub fn death_drop_name(&self) -> String {
+        format!("{}%", (self.death_drop * 100.).round())
+    }
+
+    pub fn next_death_drop(&mut self) {
+        let i = DEATH_DROPS.iter().position(|drop| *drop == self.death_drop).map_or(0, |i| (i + 1) % DEATH_DROPS.len());
+        self.death_drop = DEATH_DROPS[i];
+    }
+
+    /// moves a share of a killed player's powerups to their killer. meat is skipped since it's already been eaten
+    pub fn transfer_on_death(&self, victim: &mut StoredPowerUps, killer: &mut StoredPowerUps) {
+        for i in 1..NUM_POWERUPS {
+            let lost = (victim.power_ups[i] as f32 * self.death_drop) as u8;
+            victim.power_ups[i] -= lost;
+            killer.power_ups[i] = killer.power_ups[i].saturating_add(lost).min(self.curves[i].cap);
+        }
+    }
+}
+
+/// scales what each stack loses compared to the one before it
+fn steepen(falloff: f32, steepness: f32) -> f32 {
+    if steepness == 1. { return falloff }  // left as is so the defaults compare equal
+    (1. - (1. - falloff) * steepness).max(0.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bonus_stops_at_the_cap_and_falls_off() {
+        let curve = PowerUpCurve { cap: 4, per_stack: 0.5, falloff: 0.5, weight: 1 };
+        assert_eq!(curve.bonus(0), 0.);
+        assert_eq!(curve.bonus(1), 0.5);
+        assert_eq!(curve.bonus(2), 0.75);
+        assert_eq!(curve.bonus(4), curve.bonus(200));
+        let linear = PowerUpCurve { falloff: 1., ..curve };
+        assert_eq!(linear.bonus(3), 1.5);
+    }
+
+    #[test]
+    fn lobby_settings_cycle_back_around() {
+        let mut config = PowerUpConfig::default();
+        let (cap, falloff, drop) = (config.cap_name(), config.falloff_name(), config.death_drop_name());
+        assert_eq!((falloff, drop.as_str()), ("Normal", "50%"));
+        for _ in 0..POWERUP_CAPS.len() {
+            config.next_cap();
+            assert_eq!(config.curves[PowerUpType::Meat as usize].cap, u8::MAX);
+        }
+        assert_eq!(config.cap_name(), cap);
+        config.next_falloff();
+        assert_eq!(config.falloff_name(), "Steep");
+        assert!(config.damage_mult(&StoredPowerUps { power_ups: [0, 8, 0, 0, 0] })
+            < PowerUpConfig::default().damage_mult(&StoredPowerUps { power_ups: [0, 8, 0, 0, 0] }));
+        config.next_falloff();
+        assert_eq!(config.falloff_name(), "Off");
+        assert!(config.curves.iter().skip(1).all(|curve| curve.falloff == 1.));
+        config.next_falloff();
+        assert_eq!(config.falloff_name(), "Normal");
+        for _ in 0..DEATH_DROPS.len() {
+            config.next_death_drop();
+        }
+        assert_eq!(config.death_drop_name(), drop);
+    }
+
+    #[test]
+    fn death_drop_moves_stacks_to_the_killer() {
+        let config = PowerUpConfig::default();
+        let mut victim = StoredPowerUps { power_ups: [5, 4, 3, 8, 1] };
+        let mut killer = StoredPowerUps { power_ups: [0, 6, 0, 0, 0] };
+        config.transfer_on_death(&mut victim, &mut killer);
+        assert!(victim == StoredPowerUps { power_ups: [5, 2, 2, 4, 1] });
+        assert!(killer == StoredPowerUps { power_ups: [0, 8, 1, 4, 0] });
+    }
+}
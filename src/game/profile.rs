@@ -0,0 +1,254 @@
+use std::fs;
+use bevy::prelude::*;
+use crate::AppState;
+use crate::game::components::Player;
+use crate::game::item::HOTBAR_SLOTS;
+use crate::game::movement::KeyBinds;
+use crate::game::player::{MAX_PLAYERS, PLAYER_COLORS};
+use crate::game::report::RESULTS_DIR;
+
+pub const PROFILE_PATH: &str = "profile.txt";
+pub const MAX_NAME_LEN: usize = 16; // in characters, the host adds a " (2)" on top of this for duplicates
+pub const COLOR_NAMES: [&str; MAX_PLAYERS] = ["Blue", "Red", "Green", "Yellow", "Purple", "Orange", "Cyan", "Pink",
+    "Brown", "Lime", "Navy", "Maroon", "Forest", "White", "Olive", "Grey"];
+const NAMEPLATE_OFFSET: f32 = 36.; // above the health bar
+const NAMEPLATE_FONT_SIZE: f32 = 16.;
+/// keys the profile file understands, matched on their debug names
+const BINDABLE_KEYS: [KeyCode; 58] = [
+    KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G, KeyCode::H, KeyCode::I,
+    KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N, KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R,
+    KeyCode::S, KeyCode::T, KeyCode::U, KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+    KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+    KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+    KeyCode::Up, KeyCode::Down, KeyCode::Left, KeyCode::Right,
+    KeyCode::Space, KeyCode::Return, KeyCode::Back, KeyCode::Tab,
+    KeyCode::ShiftLeft, KeyCode::ShiftRight, KeyCode::ControlLeft, KeyCode::ControlRight, KeyCode::AltLeft, KeyCode::AltRight,
+    KeyCode::Comma, KeyCode::Period, KeyCode::Slash, KeyCode::Semicolon, KeyCode::Apostrophe,
+    KeyCode::BracketLeft, KeyCode::BracketRight, KeyCode::Grave,
+];
+
+/// Who the local player wants to be, edited on the profile page and kept in PROFILE_PATH
+/// along with their key binds
+#[derive(Resource, Default)]
+pub struct Profile {
+    pub name: String, // empty goes by "Player N"
+    pub color: u8, // index into PLAYER_COLORS
+    pub results_dir: String, // where the host writes match reports, only set in the file
+}
+
+impl Profile {
+    /// reads the profile file, anything missing or unreadable keeps its default
+    pub fn load() -> (Profile, KeyBinds) {
+        let mut profile = Profile::default();
+        let mut key_binds = KeyBinds::new();
+        let Ok(contents) = fs::read_to_string(PROFILE_PATH) else { return (profile, key_binds) };
+        for line in contents.lines() {
+            let Some((key, value)) = line.split_once('=') else { continue };
+            match key.trim() {
+                "name" => profile.name = sanitize_name(value),
+                "color" => profile.color = value.trim().parse::<u8>().map_or(0, |color| color % MAX_PLAYERS as u8),
+                "results_dir" => profile.results_dir = value.trim().to_string(),
+                bind => if let Some(key_code) = parse_key(value.trim()) {
+                    if let Some(slot) = BindAction::from_name(bind).map(|action| action.key_mut(&mut key_binds)) {
+                        *slot = key_code;
+                    }
+                },
+            }
+        }
+        (profile, key_binds)
+    }
+
+    pub fn save(&self, key_binds: &KeyBinds) -> std::io::Result<()> {
+        let mut contents = format!("name={}\ncolor={}\nresults_dir={}\n", self.name, self.color, self.results_dir());
+        for action in BindAction::ALL {
+            contents.push_str(&format!("{}={:?}\n", action.name(), action.key(key_binds)));
+        }
+        fs::write(PROFILE_PATH, contents)
+    }
+
+    pub fn results_dir(&self) -> &str {
+        if self.results_dir.is_empty() { RESULTS_DIR } else { &self.results_dir }
+    }
+
+    pub fn color_name(&self) -> &'static str {
+        COLOR_NAMES[self.color as usize % MAX_PLAYERS]
+    }
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS.into_iter().find(|key| format!("{:?}", key) == name)
+}
+
+/// only keys the profile file can read back are offered for rebinding
+pub fn is_bindable(key: KeyCode) -> bool {
+    BINDABLE_KEYS.contains(&key)
+}
+
+/// A key the profile page can rebind
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BindAction {
+    Up,
+    Down,
+    Left,
+    Right,
+    Dash,
+    Item(usize),
+}
+
+impl BindAction {
+    pub const ALL: [BindAction; 5 + HOTBAR_SLOTS] = [BindAction::Up, BindAction::Down, BindAction::Left, BindAction::Right, BindAction::Dash,
+        BindAction::Item(0), BindAction::Item(1), BindAction::Item(2), BindAction::Item(3)];
+
+    pub fn from_name(name: &str) -> Option<BindAction> {
+        BindAction::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BindAction::Up => "up",
+            BindAction::Down => "down",
+            BindAction::Left => "left",
+            BindAction::Right => "right",
+            BindAction::Dash => "dash",
+            BindAction::Item(0) => "item1",
+            BindAction::Item(1) => "item2",
+            BindAction::Item(2) => "item3",
+            BindAction::Item(_) => "item4",
+        }
+    }
+
+    /// how the profile page shows the action
+    pub fn title(&self) -> String {
+        match self {
+            BindAction::Item(slot) => format!("Item {}", slot + 1),
+            action => format!("{:?}", action),
+        }
+    }
+
+    pub fn key(&self, key_binds: &KeyBinds) -> KeyCode {
+        match self {
+            BindAction::Up => key_binds.up,
+            BindAction::Down => key_binds.down,
+            BindAction::Left => key_binds.left,
+            BindAction::Right => key_binds.right,
+            BindAction::Dash => key_binds.dash,
+            BindAction::Item(slot) => key_binds.items[*slot],
+        }
+    }
+
+    pub fn key_mut<'a>(&self, key_binds: &'a mut KeyBinds) -> &'a mut KeyCode {
+        match self {
+            BindAction::Up => &mut key_binds.up,
+            BindAction::Down => &mut key_binds.down,
+            BindAction::Left => &mut key_binds.left,
+            BindAction::Right => &mut key_binds.right,
+            BindAction::Dash => &mut key_binds.dash,
+            BindAction::Item(slot) => &mut key_binds.items[*slot],
+        }
+    }
+}
+
+/// Which key the profile page is waiting on, if any
+#[derive(Resource, Default)]
+pub struct Rebinding(pub Option<BindAction>);
+
+/// strips control characters and surrounding whitespace, and cuts the name down to MAX_NAME_LEN
+pub fn sanitize_name(raw: &str) -> String {
+    let name: String = raw.chars().filter(|ch| !ch.is_control()).collect();
+    name.trim().chars().take(MAX_NAME_LEN).collect::<String>().trim_end().to_string()
+}
+
+/// The name and color a player id goes by in this match
+#[derive(Clone, Default, Eq, PartialEq)]
+pub struct PlayerProfile {
+    pub name: String,
+    pub color: u8,
+}
+
+/// Everyone's profile by player id, the host fills it in from connection requests and sends it out
+/// with every lobby and host tick
+#[derive(Resource, Default, Eq, PartialEq)]
+pub struct PlayerProfiles(pub [Option<PlayerProfile>; MAX_PLAYERS]);
+
+impl PlayerProfiles {
+    pub fn clear(&mut self) {
+        *self = PlayerProfiles::default();
+    }
+
+    fn get(&self, id: u8) -> Option<&PlayerProfile> {
+        self.0.get(id as usize).and_then(|profile| profile.as_ref())
+    }
+
+    pub fn name(&self, id: u8) -> String {
+        self.get(id).map_or(format!("Player {}", id as u32 + 1), |profile| profile.name.clone())
+    }
+
+    pub fn color(&self, id: u8) -> Color {
+        PLAYER_COLORS[self.get(id).map_or(id, |profile| profile.color) as usize % MAX_PLAYERS]
+    }
+
+    /// gives id a sanitized version of the name it asked for, with a number on the end if someone else has it
+    pub fn claim(&mut self, id: u8, name: &str, color: u8) {
+        if id as usize >= MAX_PLAYERS { return }
+        let mut name = sanitize_name(name);
+        if name.is_empty() {
+            name = format!("Player {}", id + 1);
+        }
+        let taken = |candidate: &str| self.0.iter().enumerate()
+            .any(|(other, profile)| other != id as usize && profile.as_ref().is_some_and(|profile| profile.name.eq_ignore_ascii_case(candidate)));
+        let name = if !taken(&name) { name } else {
+            (2..).map(|n| format!("{} ({})", name, n)).find(|candidate| !taken(candidate)).unwrap()
+        };
+        self.0[id as usize] = Some(PlayerProfile { name, color: color % MAX_PLAYERS as u8 });
+    }
+}
+
+/// Name shown above a player
+#[derive(Component)]
+pub struct Nameplate;
+
+pub struct ProfilePlugin;
+
+impl Plugin for ProfilePlugin {
+    fn build(&self, app: &mut App) {
+        let (profile, key_binds) = Profile::load();
+        app.add_systems(Update, update_nameplates.run_if(in_state(AppState::Game)))
+            .insert_resource(profile)
+            .insert_resource(key_binds)
+            .init_resource::<Rebinding>()
+            .init_resource::<PlayerProfiles>();
+    }
+}
+
+pub fn spawn_nameplate(commands: &mut Commands, asset_server: &AssetServer, player: Entity) {
+    let nameplate = commands.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: NAMEPLATE_FONT_SIZE,
+                color: Color::WHITE,
+            }).with_alignment(TextAlignment::Center),
+            transform: Transform::from_xyz(0., NAMEPLATE_OFFSET, 3.),
+            ..default()
+        },
+        Nameplate,
+    )).id();
+    commands.entity(player).add_child(nameplate);
+}
+
+pub fn update_nameplates(
+    profiles: Res<PlayerProfiles>,
+    players: Query<(&Player, &Children)>,
+    mut nameplates: Query<&mut Text, With<Nameplate>>,
+) {
+    for (pl, children) in &players {
+        for child in children.iter() {
+            let Ok(mut text) = nameplates.get_mut(*child) else { continue };
+            let name = profiles.name(pl.0);
+            if text.sections[0].value != name {
+                text.sections[0].value = name;
+            }
+            text.sections[0].style.color = profiles.color(pl.0);
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use bevy::prelude::*;
+use crate::AppState;
+use crate::game::bot::Bots;
+use crate::game::buffers::PosBuffer;
+use crate::game::components::{Player, Stats};
+use crate::game::killfeed::KillFeed;
+use crate::game::map::{MapSeed, NumCamps};
+use crate::game::mode::{GameMode, Teams};
+use crate::game::player::{remove_players, MaxPlayers, MAX_PLAYERS};
+use crate::game::profile::{PlayerProfiles, Profile, COLOR_NAMES};
+use crate::game::respawn::DeathPenalty;
+use crate::game::round::{Round, TieBreak, WARMUP_TICKS};
+use crate::net::{self, is_host, TICKRATE, TickNum};
+
+pub const RESULTS_DIR: &str = "results"; // used when the profile doesn't name one
+
+/// Something worth putting on a match's timeline
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TimelineKind {
+    Kill,
+    Death,
+    EnemyKill,
+    CampCapture,
+    ChestOpen,
+}
+
+impl TimelineKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TimelineKind::Kill => "kill",
+            TimelineKind::Death => "death",
+            TimelineKind::EnemyKill => "enemy_kill",
+            TimelineKind::CampCapture => "camp_capture",
+            TimelineKind::ChestOpen => "chest_open",
+        }
+    }
+}
+
+/// One entry on the timeline, other is the victim of a kill or the killer behind a death
+#[derive(Copy, Clone)]
+pub struct TimelineEvent {
+    pub tick: u16,
+    pub kind: TimelineKind,
+    pub player: u8,
+    pub other: Option<u8>,
+    pub pos: Vec2,
+}
+
+/// Counters the timeline is worked out from, as of the last tick
+#[derive(Copy, Clone, Default)]
+struct Counts {
+    deaths: u16,
+    enemies_killed: u16,
+    camps_captured: u16,
+    chests_opened: u16,
+}
+
+impl Counts {
+    fn of(stats: &Stats) -> Counts {
+        Counts {
+            deaths: stats.deaths,
+            enemies_killed: stats.enemies_killed,
+            camps_captured: stats.camps_captured,
+            chests_opened: stats.chests_opened,
+        }
+    }
+}
+
+/// Everything that happened this match, kept by the host and written out on game over
+#[derive(Resource, Default)]
+pub struct MatchLog {
+    pub events: Vec<TimelineEvent>,
+    counts: [Option<Counts>; MAX_PLAYERS], // None until a player is first seen
+}
+
+pub struct ReportPlugin;
+
+impl Plugin for ReportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Game), reset_match_log)
+            .add_systems(FixedUpdate, track_match_events
+                .run_if(in_state(AppState::Game)).run_if(is_host).before(net::host::fixed))
+            .add_systems(OnEnter(AppState::GameOver), write_match_report
+                .run_if(is_host).before(remove_players))
+            .init_resource::<MatchLog>();
+    }
+}
+
+pub fn reset_match_log(mut match_log: ResMut<MatchLog>) {
+    *match_log = MatchLog::default();
+}
+
+/// turns the changes in everyone's stats since the last tick into timeline events,
+/// the kill feed says who was behind a death
+pub fn track_match_events(
+    tick: Res<TickNum>,
+    kill_feed: Res<KillFeed>,
+    mut match_log: ResMut<MatchLog>,
+    players: Query<(&Player, &Stats, &PosBuffer)>,
+) {
+    for (pl, stats, pb) in &players {
+        let now = Counts::of(stats);
+        let Some(last) = match_log.counts[pl.0 as usize].replace(now) else { continue };
+        let pos = pb.0.get(tick.0).unwrap_or_default();
+        let mut log = |kind: TimelineKind, player: u8, other: Option<u8>, times: u16| {
+            for _ in 0..times {
+                match_log.events.push(TimelineEvent { tick: tick.0, kind, player, other, pos });
+            }
+        };
+        if now.deaths > last.deaths {
+            let killer = kill_feed.0.iter().rev()
+                .find(|kill| kill.victim == pl.0 && kill.tick.saturating_add(1) >= tick.0)
+                .map(|kill| kill.killer);
+            if let Some(killer) = killer {
+                log(TimelineKind::Kill, killer, Some(pl.0), 1);
+            }
+            log(TimelineKind::Death, pl.0, killer, now.deaths - last.deaths);
+        }
+        log(TimelineKind::EnemyKill, pl.0, None, now.enemies_killed.saturating_sub(last.enemies_killed));
+        log(TimelineKind::CampCapture, pl.0, None, now.camps_captured.saturating_sub(last.camps_captured));
+        log(TimelineKind::ChestOpen, pl.0, None, now.chests_opened.saturating_sub(last.chests_opened));
+    }
+}
+
+/// the columns every player gets in the report, in leaderboard order
+fn stat_columns(stats: &Stats) -> [(&'static str, String); 12] {
+    [
+        ("score", stats.score.to_string()),
+        ("enemy_kills", stats.enemies_killed.to_string()),
+        ("player_kills", stats.players_killed.to_string()),
+        ("camps_captured", stats.camps_captured.to_string()),
+        ("deaths", stats.deaths.to_string()),
+        ("assists", stats.assists.to_string()),
+        ("damage_dealt", stats.damage_dealt.to_string()),
+        ("damage_taken", stats.damage_taken.to_string()),
+        ("powerups", stats.powerups_collected.to_string()),
+        ("chests", stats.chests_opened.to_string()),
+        ("best_streak", stats.best_streak.to_string()),
+        ("seconds_alive", (stats.ticks_alive / TICKRATE as u32).to_string()),
+    ]
+}
+
+/// quotes and escapes a string for json
+fn json_string(raw: &str) -> String {
+    let mut quoted = String::from("\"");
+    for ch in raw.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            ch if ch.is_control() => quoted.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn json_id(id: Option<u8>) -> String {
+    id.map_or("null".to_string(), |id| id.to_string())
+}
+
+/// A row per player, filled in before the players are removed
+struct PlayerRow {
+    id: u8,
+    name: String,
+    color: &'static str,
+    team: Option<u8>,
+    bot: bool,
+    stats: Stats,
+}
+
+/// what the match was, everything in it is already json
+struct MatchInfo {
+    name: String,
+    settings: Vec<(&'static str, String)>,
+}
+
+fn write_json(path: &Path, info: &MatchInfo, players: &[PlayerRow], events: &[TimelineEvent]) -> std::io::Result<()> {
+    let mut json = format!("{{\n  \"match\": {},\n", json_string(&info.name));
+    for (key, value) in &info.settings {
+        json.push_str(&format!("  \"{}\": {},\n", key, value));
+    }
+    json.push_str("  \"players\": [");
+    for (i, row) in players.iter().enumerate() {
+        let stats: Vec<String> = stat_columns(&row.stats).iter().map(|(key, value)| format!("\"{}\": {}", key, value)).collect();
+        json.push_str(&format!("{}\n    {{\"id\": {}, \"name\": {}, \"color\": {}, \"team\": {}, \"bot\": {}, {}}}",
+            if i == 0 { "" } else { "," }, row.id, json_string(&row.name), json_string(row.color),
+            json_id(row.team.map(|team| team + 1)), row.bot, stats.join(", ")));
+    }
+    json.push_str("\n  ],\n  \"timeline\": [");
+    for (i, ev) in events.iter().enumerate() {
+        json.push_str(&format!("{}\n    {{\"tick\": {}, \"event\": \"{}\", \"player\": {}, \"other\": {}, \"x\": {:.1}, \"y\": {:.1}}}",
+            if i == 0 { "" } else { "," }, ev.tick, ev.kind.name(), ev.player, json_id(ev.other), ev.pos.x, ev.pos.y));
+    }
+    json.push_str("\n  ]\n}\n");
+    fs::write(path, json)
+}
+
+fn write_players_csv(path: &Path, info: &MatchInfo, players: &[PlayerRow]) -> csv::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    let mut header = vec!["match", "id", "name", "color", "team", "bot"];
+    header.extend(stat_columns(&Stats::default()).iter().map(|(key, _)| *key));
+    writer.write_record(&header)?;
+    for row in players {
+        let mut record = vec![info.name.clone(), row.id.to_string(), row.name.clone(), row.color.to_string(),
+            row.team.map_or(String::new(), |team| (team + 1).to_string()), row.bot.to_string()];
+        record.extend(stat_columns(&row.stats).into_iter().map(|(_, value)| value));
+        writer.write_record(&record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_timeline_csv(path: &Path, info: &MatchInfo, events: &[TimelineEvent]) -> csv::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["match", "tick", "event", "player", "other", "x", "y"])?;
+    for ev in events {
+        writer.write_record([info.name.clone(), ev.tick.to_string(), ev.kind.name().to_string(), ev.player.to_string(),
+            ev.other.map_or(String::new(), |id| id.to_string()), format!("{:.1}", ev.pos.x), format!("{:.1}", ev.pos.y)])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// writes the match as json, plus csvs of the players and the timeline, into the profile's results directory
+pub fn write_match_report(
+    match_log: Res<MatchLog>,
+    profile: Res<Profile>,
+    profiles: Res<PlayerProfiles>,
+    players: Query<(&Player, &Stats)>,
+    bots: Res<Bots>,
+    teams: Res<Teams>,
+    round: Res<Round>,
+    settings: (Res<MapSeed>, Res<GameMode>, Res<TieBreak>, Res<NumCamps>, Res<MaxPlayers>, Res<DeathPenalty>),
+) {
+    let (seed, game_mode, tie_break, num_camps, max_players, death_penalty) = settings;
+    let finished = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    let ticks = round.ended_tick.saturating_sub(WARMUP_TICKS);
+    let info = MatchInfo {
+        name: format!("match-{}-{}", finished, seed.0),
+        settings: vec![
+            ("finished_at", finished.to_string()),
+            ("completed", (!round.restarting).to_string()),
+            ("seed", seed.0.to_string()),
+            ("mode", json_string(game_mode.name())),
+            ("tie_break", json_string(tie_break.name())),
+            ("camps", num_camps.0.to_string()),
+            ("max_players", max_players.0.to_string()),
+            ("friendly_fire", teams.friendly_fire.to_string()),
+            ("death_penalty", json_string(death_penalty.name())),
+            ("overtime", round.overtime.to_string()),
+            ("duration_ticks", ticks.to_string()),
+            ("duration_seconds", (ticks / TICKRATE as u16).to_string()),
+        ],
+    };
+    let mut rows: Vec<PlayerRow> = players.iter().map(|(pl, stats)| PlayerRow {
+        id: pl.0,
+        name: profiles.name(pl.0),
+        color: COLOR_NAMES[profiles.0[pl.0 as usize].as_ref().map_or(pl.0, |profile| profile.color) as usize % MAX_PLAYERS],
+        team: game_mode.teams().then(|| teams.of(pl.0)),
+        bot: bots.is_bot(pl.0),
+        stats: stats.clone(),
+    }).collect();
+    rows.sort_by_key(|row| row.id);
+    let dir = Path::new(profile.results_dir());
+    if let Err(e) = fs::create_dir_all(dir) {
+        println!("couldn't make results directory {}: {}", dir.display(), e);
+        return;
+    }
+    if let Err(e) = write_json(&dir.join(format!("{}.json", info.name)), &info, &rows, &match_log.events) {
+        println!("couldn't write match report: {}", e);
+    }
+    if let Err(e) = write_players_csv(&dir.join(format!("{}-players.csv", info.name)), &info, &rows) {
+        println!("couldn't write player stats: {}", e);
+    }
+    if let Err(e) = write_timeline_csv(&dir.join(format!("{}-timeline.csv", info.name)), &info, &match_log.events) {
+        println!("couldn't write match timeline: {}", e);
+    }
+    println!("match report written to {}", dir.display());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a file in the system temp directory that's removed again when the test is done with it
+    struct TempFile(std::path::PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> TempFile {
+            TempFile(std::env::temp_dir().join(format!("jordquest-{}-{}", std::process::id(), name)))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn row(name: &str) -> PlayerRow {
+        PlayerRow { id: 1, name: name.to_string(), color: COLOR_NAMES[0], team: None, bot: false, stats: Stats { score: 12, ..default() } }
+    }
+
+    #[test]
+    fn json_string_escapes_quotes_backslashes_and_control_characters() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(json_string("a\\b"), "\"a\\\\b\"");
+        assert_eq!(json_string("line\nbreak\t\u{7}"), "\"line\\u000abreak\\u0009\\u0007\"");
+        assert_eq!(json_string("ünï ✓"), "\"ünï ✓\"");
+    }
+
+    #[test]
+    fn json_report_keeps_awkward_names_intact() {
+        let file = TempFile::new("report.json");
+        let info = MatchInfo { name: "match \"1\"".to_string(), settings: vec![("seed", "42".to_string())] };
+        let events = [TimelineEvent { tick: 5, kind: TimelineKind::Kill, player: 1, other: None, pos: Vec2::new(1., -2.) }];
+        write_json(&file.0, &info, &[row("{\"evil\": \\ }")], &events).unwrap();
+        let json = fs::read_to_string(&file.0).unwrap();
+        assert!(json.contains("\"match\": \"match \\\"1\\\"\""));
+        assert!(json.contains("\"name\": \"{\\\"evil\\\": \\\\ }\""));
+        assert!(json.contains("\"seed\": 42,"));
+        assert!(json.contains("\"other\": null"));
+    }
+
+    #[test]
+    fn players_csv_quotes_commas_quotes_and_newlines() {
+        let file = TempFile::new("players.csv");
+        let info = MatchInfo { name: "a, b".to_string(), settings: Vec::new() };
+        write_players_csv(&file.0, &info, &[row("Jord, \"the\"\nGreat")]).unwrap();
+        let mut reader = csv::Reader::from_path(&file.0).unwrap();
+        let headers = reader.headers().unwrap().clone();
+        assert_eq!(headers.len(), 6 + stat_columns(&Stats::default()).len());
+        let records: Vec<csv::StringRecord> = reader.records().map(|record| record.unwrap()).collect();
+        assert_eq!(records.len(), 1);
+        assert_eq!(&records[0][0], "a, b");
+        assert_eq!(&records[0][2], "Jord, \"the\"\nGreat");
+        assert_eq!(&records[0][4], "");
+        assert_eq!(&records[0][6], "12");
+    }
+}
@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+use crate::AppState;
+use crate::game::components::*;
+use crate::game::mode::{self, GameMode, ScoreEvent};
+use crate::game::player::LocalPlayer;
+use crate::game::round::WARMUP_TICKS;
+use crate::net::{self, is_host, TICKLEN_S, TICKRATE};
+
+pub const RESPAWN_BASE_TICKS: u16 = 30; // every death costs at least 3 seconds
+const RESPAWN_STREAK_TICKS: u16 = 5; // extra wait per kill in the streak that just ended
+const RESPAWN_MINUTE_TICKS: u16 = 5; // extra wait per minute into the match
+pub const RESPAWN_MAX_TICKS: u8 = 100;
+pub const SPAWN_PROTECTION_TICKS: u8 = 20; // invulnerable this long after spawning, or until attacking
+const PROTECTED_ALPHA: f32 = 0.5;
+
+/// How long until a dead player can spawn and how long a fresh spawn is protected,
+/// counted down by the host and sent out with each player's tick
+#[derive(Component, Default)]
+pub struct Respawn {
+    pub wait: u8,
+    pub protection: u8,
+}
+
+impl Respawn {
+    pub fn ready(&self) -> bool {
+        self.wait == 0
+    }
+
+    pub fn protected(&self) -> bool {
+        self.protection > 0
+    }
+}
+
+/// What a player loses on top of their life when they die, picked on the host page
+#[derive(Resource, Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum DeathPenalty {
+    #[default]
+    Nothing = 0,
+    Score,
+    PowerUp,
+}
+
+impl DeathPenalty {
+    pub fn next(&self) -> DeathPenalty {
+        match self {
+            DeathPenalty::Nothing => DeathPenalty::Score,
+            DeathPenalty::Score => DeathPenalty::PowerUp,
+            DeathPenalty::PowerUp => DeathPenalty::Nothing,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeathPenalty::Nothing => "None",
+            DeathPenalty::Score => "Lose Score",
+            DeathPenalty::PowerUp => "Lose Powerup",
+        }
+    }
+
+    /// takes points or a stack of the player's most stacked powerup, meat doesn't count
+    pub fn apply(&self, game_mode: &GameMode, stats: &mut Stats, spu: &mut StoredPowerUps) {
+        match self {
+            DeathPenalty::Nothing => {}
+            DeathPenalty::Score => game_mode.award(stats, ScoreEvent::Death, 1.),
+            DeathPenalty::PowerUp => {
+                let most = (1..NUM_POWERUPS).max_by_key(|i| spu.power_ups[*i]).unwrap();
+                spu.power_ups[most] = spu.power_ups[most].saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// a longer streak or a later death means a longer wait
+pub fn respawn_delay(streak: u16, tick: u16) -> u8 {
+    let minutes = tick.saturating_sub(WARMUP_TICKS) / (60 * TICKRATE as u16);
+    RESPAWN_BASE_TICKS
+        .saturating_add(streak.saturating_mul(RESPAWN_STREAK_TICKS))
+        .saturating_add(minutes.saturating_mul(RESPAWN_MINUTE_TICKS))
+        .min(RESPAWN_MAX_TICKS as u16) as u8
+}
+
+/// Shows the respawn countdown over the respawn map
+#[derive(Component)]
+pub struct RespawnDisplay;
+
+pub struct RespawnPlugin;
+
+impl Plugin for RespawnPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, respawn_simulate
+                .run_if(in_state(AppState::Game)).run_if(is_host).before(net::host::fixed))
+            .add_systems(Update, (
+                respawn_feedback,
+                protection_feedback.after(mode::team_colors),
+            ).run_if(in_state(AppState::Game)))
+            .init_resource::<DeathPenalty>();
+    }
+}
+
+/// counts down the wait of dead players and the protection of living ones
+pub fn respawn_simulate(
+    mut players: Query<(&Health, &mut Respawn), With<Player>>,
+) {
+    for (hp, mut respawn) in &mut players {
+        if hp.dead {
+            respawn.wait = respawn.wait.saturating_sub(1);
+            respawn.protection = 0;
+        }
+        else {
+            respawn.protection = respawn.protection.saturating_sub(1);
+        }
+    }
+}
+
+pub fn respawn_feedback(
+    game_mode: Res<GameMode>,
+    local_player: Query<(&Health, &Respawn, &Stats), With<LocalPlayer>>,
+    mut respawn_displays: Query<&mut Text, With<RespawnDisplay>>,
+) {
+    let Ok((hp, respawn, stats)) = local_player.get_single() else { return };
+    let value = if !hp.dead || !game_mode.can_respawn(stats) {
+        String::new()
+    } else if !respawn.ready() {
+        format!("Respawn in {}", (respawn.wait as f32 * TICKLEN_S).ceil() as u32)
+    } else {
+        "Click the map to respawn".to_string()
+    };
+    for mut text in &mut respawn_displays {
+        text.sections[0].value = value.clone();
+    }
+}
+
+/// fades players while they're spawn protected
+pub fn protection_feedback(
+    mut players: Query<(&Respawn, &mut TextureAtlasSprite), With<Player>>,
+) {
+    for (respawn, mut sprite) in &mut players {
+        if respawn.protected() {
+            sprite.color.set_a(PROTECTED_ALPHA);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn respawn_delay_grows_with_streak_and_time() {
+        assert_eq!(respawn_delay(0, 0), RESPAWN_BASE_TICKS as u8);
+        assert_eq!(respawn_delay(0, WARMUP_TICKS + 60 * TICKRATE as u16 - 1), RESPAWN_BASE_TICKS as u8);
+        assert_eq!(respawn_delay(0, WARMUP_TICKS + 60 * TICKRATE as u16), (RESPAWN_BASE_TICKS + RESPAWN_MINUTE_TICKS) as u8);
+        assert_eq!(respawn_delay(2, 0), (RESPAWN_BASE_TICKS + 2 * RESPAWN_STREAK_TICKS) as u8);
+    }
+
+    #[test]
+    fn respawn_delay_is_capped() {
+        assert_eq!(respawn_delay(100, 0), RESPAWN_MAX_TICKS);
+        assert_eq!(respawn_delay(u16::MAX, u16::MAX), RESPAWN_MAX_TICKS);
+    }
+
+    #[test]
+    fn powerup_penalty_takes_from_the_biggest_stack() {
+        let mut stats = Stats { score: 10, ..default() };
+        let mut spu = StoredPowerUps { power_ups: [9, 2, 4, 0, 0] }; // the first slot never counts
+        DeathPenalty::PowerUp.apply(&GameMode::FreeForAll, &mut stats, &mut spu);
+        assert_eq!(spu.power_ups[..3], [9, 2, 3]);
+        assert_eq!(stats.score, 10);
+        DeathPenalty::Nothing.apply(&GameMode::FreeForAll, &mut stats, &mut spu);
+        assert_eq!(spu.power_ups[..3], [9, 2, 3]);
+        DeathPenalty::Score.apply(&GameMode::FreeForAll, &mut stats, &mut spu);
+        assert!(stats.score < 10);
+    }
+}
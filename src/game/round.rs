@@ -0,0 +1,411 @@
+use bevy::prelude::*;
+use rand::Rng;
+use crate::AppState;
+use crate::game::ROUND_TIME;
+use crate::game::components::*;
+use crate::game::buffers::{HpBuffer, PosBuffer};
+use crate::game::map::{MapSeed, MAPSIZE, TILESIZE};
+use crate::game::mode::{GameMode, Joined, NUM_TEAMS, Teams};
+use crate::game::movement::{self, hazard_damage};
+use crate::game::player;
+use crate::game::profile::PlayerProfiles;
+use crate::net::{self, is_client, is_host, TICKRATE, TickNum};
+use crate::net::replay::Replay;
+
+pub const WARMUP_TICKS: u16 = 50; // time to pick a spawn before players can hurt each other
+pub const ROUND_TICKS: u16 = ROUND_TIME as u16 * TICKRATE as u16;
+pub const ENDED_TICKS: u16 = 10; // the host keeps sending the final state this long before game over
+pub const HOST_SILENCE_S: f64 = 3.; // a client that hears nothing from the host for this long says so and offers to leave
+pub const OVERTIME_TICKS: u16 = 60 * TICKRATE as u16; // sudden death gives up after this long
+pub const ZONE_START_RADIUS: f32 = (MAPSIZE * TILESIZE) as f32 * 0.71; // covers the corners of the map
+pub const ZONE_END_RADIUS: f32 = (8 * TILESIZE) as f32;
+pub const ZONE_DAMAGE: u8 = 2; // per tick outside the safe zone
+pub const ZONE_COLOR: Color = Color::rgb(1.0, 0.2, 0.2);
+
+/// Where the round is at, only the host moves it forward
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MatchPhase {
+    Warmup = 0,
+    Live,
+    Overtime,
+    Ended,
+}
+
+impl MatchPhase {
+    pub fn from_u8(id: u8) -> Option<MatchPhase> {
+        match id {
+            0 => Some(MatchPhase::Warmup),
+            1 => Some(MatchPhase::Live),
+            2 => Some(MatchPhase::Overtime),
+            3 => Some(MatchPhase::Ended),
+            _ => None,
+        }
+    }
+
+    /// whether players can damage each other
+    pub fn can_fight(&self) -> bool {
+        matches!(self, MatchPhase::Live | MatchPhase::Overtime)
+    }
+}
+
+/// How a tie for first place is settled once time runs out, picked on the host page
+#[derive(Resource, Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum TieBreak {
+    #[default]
+    SuddenDeath = 0,
+    MostKills,
+    FewestDeaths,
+}
+
+impl TieBreak {
+    pub fn from_u8(id: u8) -> Option<TieBreak> {
+        match id {
+            0 => Some(TieBreak::SuddenDeath),
+            1 => Some(TieBreak::MostKills),
+            2 => Some(TieBreak::FewestDeaths),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> TieBreak {
+        TieBreak::from_u8(*self as u8 + 1).unwrap_or(TieBreak::SuddenDeath)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TieBreak::SuddenDeath => "Sudden Death",
+            TieBreak::MostKills => "Most Kills",
+            TieBreak::FewestDeaths => "Fewest Deaths",
+        }
+    }
+}
+
+/// The round's phase and the tick it ends on, sent out with every host tick
+#[derive(Resource)]
+pub struct Round {
+    pub phase: MatchPhase,
+    pub end_tick: u16,
+    pub ended_tick: u16, // when the host ended the round, not sent to clients
+    pub overtime: bool, // whether the round went to sudden death
+    pub restarting: bool, // the host ended it with /restart, so game over goes straight back to the lobby
+    pub heard_at: Option<f64>, // when a client last got a host tick, not sent to clients
+    pub host_lost: bool, // a client hasn't heard from the host in HOST_SILENCE_S, not sent to clients
+}
+
+impl Default for Round {
+    fn default() -> Round {
+        Round {
+            phase: MatchPhase::Warmup,
+            end_tick: WARMUP_TICKS + ROUND_TICKS,
+            ended_tick: 0,
+            overtime: false,
+            restarting: false,
+            heard_at: None,
+            host_lost: false,
+        }
+    }
+}
+
+impl Round {
+    /// radius of the safe zone around the middle of the map, None outside of overtime.
+    /// shrinks as overtime runs out so clients can work it out from the end tick alone
+    pub fn safe_zone(&self, tick: u16) -> Option<f32> {
+        if self.phase != MatchPhase::Overtime { return None }
+        let left = self.end_tick.saturating_sub(tick).min(OVERTIME_TICKS) as f32 / OVERTIME_TICKS as f32;
+        Some(ZONE_END_RADIUS + (ZONE_START_RADIUS - ZONE_END_RADIUS) * left)
+    }
+}
+
+/// A player, or a whole team in team modes, competing for a placement
+pub struct Entrant {
+    pub name: String,
+    pub team: Option<u8>,
+    pub score: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub alive: bool, // still has lives left, only matters in last player standing
+}
+
+/// Everyone's placement at the end of the round and what decided first place
+pub struct Standings {
+    pub placements: Vec<Entrant>,
+    pub places: Vec<usize>, // 1st, 2nd.. for each of placements, entrants that can't be split share one
+    pub winner: Option<usize>, // index into placements, None on a draw
+    pub decided_by: &'static str,
+}
+
+/// groups players into entrants for the mode, players is everyone that has spawned in at least once
+pub fn entrants(game_mode: &GameMode, teams: &Teams, profiles: &PlayerProfiles, players: &[(u8, &Stats)]) -> Vec<Entrant> {
+    if game_mode.teams() {
+        return (0..NUM_TEAMS).map(|team| {
+            let members = players.iter().filter(|(id, _)| teams.of(*id) == team);
+            Entrant {
+                name: format!("Team {}", team + 1),
+                team: Some(team),
+                score: teams.score(players, team),
+                kills: members.clone().map(|(_, stats)| stats.players_killed as u32).sum(),
+                deaths: members.clone().map(|(_, stats)| stats.deaths as u32).sum(),
+                alive: members.clone().any(|(_, stats)| game_mode.can_respawn(stats)),
+            }
+        }).collect();
+    }
+    players.iter().map(|(id, stats)| Entrant {
+        name: profiles.name(*id),
+        team: None,
+        score: stats.score,
+        kills: stats.players_killed as u32,
+        deaths: stats.deaths as u32,
+        alive: game_mode.can_respawn(stats),
+    }).collect()
+}
+
+/// what the mode ranks entrants by before any tie-break
+fn primary(game_mode: &GameMode, entrant: &Entrant) -> (bool, u32) {
+    (*game_mode == GameMode::LastPlayerStanding && entrant.alive, entrant.score)
+}
+
+/// true when the top two entrants can't be told apart by the mode's own ranking
+pub fn leaders_tied(game_mode: &GameMode, entrants: &[Entrant]) -> bool {
+    let mut best: Vec<(bool, u32)> = entrants.iter().map(|e| primary(game_mode, e)).collect();
+    best.sort_by(|a, b| b.cmp(a));
+    best.len() > 1 && best[0] == best[1]
+}
+
+/// ranks entrants, falling back to the tie-break rule when first place is tied
+pub fn standings(game_mode: &GameMode, tie_break: TieBreak, overtime: bool, mut entrants: Vec<Entrant>) -> Standings {
+    let tie_key = |e: &Entrant| match tie_break {
+        TieBreak::SuddenDeath => 0,
+        TieBreak::MostKills => e.kills as i64,
+        TieBreak::FewestDeaths => -(e.deaths as i64),
+    };
+    entrants.sort_by(|a, b| primary(game_mode, b).cmp(&primary(game_mode, a))
+        .then(tie_key(b).cmp(&tie_key(a))));
+    let key = |e: &Entrant| (primary(game_mode, e), tie_key(e));
+    let mut places: Vec<usize> = Vec::new();
+    for i in 0..entrants.len() {
+        let place = if i > 0 && key(&entrants[i]) == key(&entrants[i - 1]) { places[i - 1] } else { i + 1 };
+        places.push(place);
+    }
+    let (winner, decided_by) = if entrants.is_empty() {
+        (None, "Nobody played")
+    } else if !leaders_tied(game_mode, &entrants) {
+        (Some(0), if overtime { TieBreak::SuddenDeath.name() }
+            else if *game_mode == GameMode::LastPlayerStanding { "Last one standing" }
+            else { "Score" })
+    } else if tie_key(&entrants[0]) != tie_key(&entrants[1]) {
+        (Some(0), tie_break.name())
+    } else {
+        (None, "Draw")
+    };
+    Standings { placements: entrants, places, winner, decided_by }
+}
+
+/// sent by network module to disperse the round's state from the host
+#[derive(Event)]
+pub struct RoundTickEvent {
+    pub phase: MatchPhase,
+    pub end_tick: u16,
+}
+
+pub struct RoundPlugin;
+
+impl Plugin for RoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Game), reset_round)
+            .add_systems(OnEnter(AppState::GameOver), restart_match.run_if(is_host))
+            .add_systems(FixedUpdate, round_simulate
+                .run_if(in_state(AppState::Game)).run_if(is_host).before(net::host::fixed))
+            .add_systems(FixedUpdate, safe_zone_simulate
+                .run_if(in_state(AppState::Game)).run_if(is_host)
+                .after(movement::update_buffer).before(player::health_simulate).before(net::host::fixed))
+            .add_systems(Update, handle_round_ticks
+                .run_if(in_state(AppState::Game)).run_if(is_client))
+            .add_systems(Update, draw_safe_zone.run_if(in_state(AppState::Game)))
+            .init_resource::<Round>()
+            .init_resource::<TieBreak>()
+            .add_event::<RoundTickEvent>();
+    }
+}
+
+pub fn reset_round(mut round: ResMut<Round>) {
+    *round = Round::default();
+}
+
+/// moves the round from warmup to live to ended, through sudden death on a tie, then takes everyone to game over
+pub fn round_simulate(
+    tick: Res<TickNum>,
+    mut round: ResMut<Round>,
+    game_mode: Res<GameMode>,
+    teams: Res<Teams>,
+    tie_break: Res<TieBreak>,
+    profiles: Res<PlayerProfiles>,
+    players: Query<(&Player, &Stats), With<Joined>>,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
+) {
+    if round.phase == MatchPhase::Ended {
+        if tick.0 >= round.ended_tick + ENDED_TICKS {
+            app_state_next_state.set(AppState::GameOver);
+        }
+        return;
+    }
+    let players: Vec<(u8, &Stats)> = players.iter().map(|(pl, stats)| (pl.0, stats)).collect();
+    let tied = leaders_tied(&game_mode, &entrants(&game_mode, &teams, &profiles, &players));
+    if round.phase == MatchPhase::Overtime {
+        // first to pull ahead wins, otherwise the tie stands once the zone has closed
+        if !tied || tick.0 >= round.end_tick || game_mode.finished(&players, &teams) {
+            round.phase = MatchPhase::Ended;
+            round.ended_tick = tick.0;
+        }
+    }
+    else if tick.0 >= round.end_tick && tied && *tie_break == TieBreak::SuddenDeath {
+        round.phase = MatchPhase::Overtime;
+        round.overtime = true;
+        round.end_tick = tick.0 + OVERTIME_TICKS;
+    }
+    else if tick.0 >= round.end_tick || game_mode.finished(&players, &teams) {
+        round.phase = MatchPhase::Ended;
+        round.ended_tick = tick.0;
+    }
+    else if round.phase == MatchPhase::Warmup && tick.0 >= WARMUP_TICKS {
+        round.phase = MatchPhase::Live;
+    }
+}
+
+/// skips the results after a /restart, everyone heads back to the lobby on a fresh map like a rematch
+pub fn restart_match(
+    round: Res<Round>,
+    mut seed: ResMut<MapSeed>,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
+) {
+    if !round.restarting { return }
+    seed.0 = rand::thread_rng().gen();
+    app_state_next_state.set(AppState::Lobby);
+}
+
+/// follows the host's round, the stats that came in the same tick are the final ones.
+/// only the host ends the round, a host that's gone quiet is flagged so the player can choose to leave
+pub fn handle_round_ticks(
+    mut round_reader: EventReader<RoundTickEvent>,
+    mut round: ResMut<Round>,
+    replay: Res<Replay>,
+    time: Res<Time>,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
+) {
+    let now = time.elapsed_seconds_f64();
+    if round.heard_at.is_none() || !round_reader.is_empty() {
+        round.heard_at = Some(now);
+        round.host_lost = false;
+    }
+    else if !replay.active && !round.host_lost && round.heard_at.is_some_and(|heard_at| now - heard_at > HOST_SILENCE_S) {
+        println!("haven't heard from the host in {} seconds", HOST_SILENCE_S);
+        round.host_lost = true;
+    }
+    for ev in round_reader.iter() {
+        round.phase = ev.phase;
+        round.end_tick = ev.end_tick;
+        round.overtime |= ev.phase == MatchPhase::Overtime;
+        // a replay stays on the end so it can be scrubbed back through
+        if round.phase == MatchPhase::Ended && !replay.active {
+            app_state_next_state.set(AppState::GameOver);
+        }
+    }
+}
+
+/// hurts anyone caught outside the safe zone during overtime, the same way standing in a wall does
+pub fn safe_zone_simulate(
+    tick: Res<TickNum>,
+    round: Res<Round>,
+    mut players: Query<(&PosBuffer, &mut HpBuffer), With<Player>>,
+) {
+    let Some(radius) = round.safe_zone(tick.0) else { return };
+    for (pb, mut hb) in &mut players {
+        let Some(pos) = pb.0.get(tick.0) else { continue };
+        if hb.0.get(tick.0).unwrap_or(0) > 0 && pos.length() > radius {
+            hazard_damage(&mut hb, tick.0, ZONE_DAMAGE);
+        }
+    }
+}
+
+/// outlines the safe zone so players can see it closing in
+pub fn draw_safe_zone(
+    mut gizmos: Gizmos,
+    tick: Res<TickNum>,
+    round: Res<Round>,
+) {
+    if let Some(radius) = round.safe_zone(tick.0) {
+        gizmos.circle_2d(Vec2::ZERO, radius, ZONE_COLOR);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entrant(name: &str, score: u32, kills: u32, deaths: u32) -> Entrant {
+        Entrant { name: name.to_string(), team: None, score, kills, deaths, alive: true }
+    }
+
+    #[test]
+    fn score_decides_without_a_tie() {
+        let entrants = vec![entrant("a", 10, 0, 0), entrant("b", 30, 0, 0), entrant("c", 20, 0, 0)];
+        assert!(!leaders_tied(&GameMode::FreeForAll, &entrants));
+        let standings = standings(&GameMode::FreeForAll, TieBreak::MostKills, false, entrants);
+        assert_eq!(standings.placements[0].name, "b");
+        assert_eq!(standings.places, vec![1, 2, 3]);
+        assert_eq!(standings.winner, Some(0));
+        assert_eq!(standings.decided_by, "Score");
+    }
+
+    #[test]
+    fn tie_break_settles_a_tied_lead() {
+        let entrants = || vec![entrant("a", 20, 3, 5), entrant("b", 20, 1, 2), entrant("c", 5, 9, 0)];
+        assert!(leaders_tied(&GameMode::FreeForAll, &entrants()));
+        let kills = standings(&GameMode::FreeForAll, TieBreak::MostKills, false, entrants());
+        assert_eq!(kills.placements[0].name, "a");
+        assert_eq!(kills.decided_by, TieBreak::MostKills.name());
+        let deaths = standings(&GameMode::FreeForAll, TieBreak::FewestDeaths, false, entrants());
+        assert_eq!(deaths.placements[0].name, "b");
+        assert_eq!(deaths.places, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn unsettled_ties_share_a_place_and_draw() {
+        let entrants = vec![entrant("a", 20, 1, 1), entrant("b", 20, 1, 1), entrant("c", 5, 0, 0)];
+        let standings = standings(&GameMode::FreeForAll, TieBreak::SuddenDeath, true, entrants);
+        assert_eq!(standings.places, vec![1, 1, 3]);
+        assert_eq!(standings.winner, None);
+        assert_eq!(standings.decided_by, "Draw");
+        let empty = super::standings(&GameMode::FreeForAll, TieBreak::SuddenDeath, false, Vec::new());
+        assert_eq!(empty.winner, None);
+    }
+
+    #[test]
+    fn last_player_standing_ranks_survivors_first() {
+        let mut out = entrant("out", 50, 4, 3);
+        out.alive = false;
+        let entrants = vec![out, entrant("alive", 10, 0, 2)];
+        let standings = standings(&GameMode::LastPlayerStanding, TieBreak::MostKills, false, entrants);
+        assert_eq!(standings.placements[0].name, "alive");
+        assert_eq!(standings.decided_by, "Last one standing");
+    }
+
+    #[test]
+    fn safe_zone_shrinks_through_overtime() {
+        let mut round = Round { phase: MatchPhase::Live, end_tick: 1000, ..default() };
+        assert_eq!(round.safe_zone(0), None);
+        round.phase = MatchPhase::Overtime;
+        assert_eq!(round.safe_zone(1000 - OVERTIME_TICKS), Some(ZONE_START_RADIUS));
+        let half = round.safe_zone(1000 - OVERTIME_TICKS / 2).unwrap();
+        assert!((half - (ZONE_START_RADIUS + ZONE_END_RADIUS) / 2.).abs() < 1.);
+        assert_eq!(round.safe_zone(1000), Some(ZONE_END_RADIUS));
+        assert_eq!(round.safe_zone(2000), Some(ZONE_END_RADIUS));
+    }
+
+    #[test]
+    fn tie_break_cycles() {
+        assert_eq!(TieBreak::SuddenDeath.next(), TieBreak::MostKills);
+        assert_eq!(TieBreak::FewestDeaths.next(), TieBreak::SuddenDeath);
+        assert_eq!(MatchPhase::from_u8(MatchPhase::Ended as u8), Some(MatchPhase::Ended));
+        assert_eq!(MatchPhase::from_u8(4), None);
+    }
+}
@@ -0,0 +1,115 @@
+use bevy::prelude::*;
+use crate::AppState;
+use crate::game::PlayerId;
+use crate::game::camera::{self, SpatialCameraBundle};
+use crate::game::components::{Health, Player};
+use crate::game::mode::Joined;
+use crate::game::profile::PlayerProfiles;
+use crate::net::IsHost;
+
+pub const SPECTATOR_ID: u8 = 0xFF; // the player id handed to spectators, no player entity has it
+const FREE_CAMERA_SPEED: f32 = 600.; // pixels per second
+
+/// Whether the join page connects as a player or a spectator
+#[derive(Resource, Default)]
+pub struct JoinAsSpectator(pub bool);
+
+impl JoinAsSpectator {
+    pub fn name(&self) -> &'static str {
+        if self.0 { "Spectator" } else { "Player" }
+    }
+}
+
+/// Who the spectator camera is following, None for a free camera
+#[derive(Resource, Default)]
+pub struct SpectatorCamera {
+    pub following: Option<u8>,
+}
+
+/// Shows who is being watched and the spectator controls
+#[derive(Component)]
+pub struct SpectatorDisplay;
+
+pub struct SpectatePlugin;
+
+impl Plugin for SpectatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Game), reset_spectator_camera)
+            .add_systems(Update, (
+                spectator_controls,
+                spectator_camera.after(spectator_controls),
+                spectator_feedback,
+            ).run_if(in_state(AppState::Game)).run_if(is_spectator))
+            .init_resource::<JoinAsSpectator>()
+            .init_resource::<SpectatorCamera>();
+    }
+}
+
+// for conditionally running systems
+pub fn is_spectator(is_host: Res<IsHost>, player_id: Res<PlayerId>) -> bool {
+    !is_host.0 && player_id.0 == SPECTATOR_ID
+}
+
+pub fn reset_spectator_camera(mut spectator_camera: ResMut<SpectatorCamera>) {
+    spectator_camera.following = None;
+}
+
+/// space cycles through the players in the round, F frees the camera, WASD moves it when free
+pub fn spectator_controls(
+    input: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    mut spectator_camera: ResMut<SpectatorCamera>,
+    players: Query<(&Player, &Health), With<Joined>>,
+    mut game_camera: Query<&mut Transform, With<SpatialCameraBundle>>,
+) {
+    if input.just_pressed(KeyCode::Space) {
+        let mut alive: Vec<u8> = players.iter().filter(|(_, hp)| !hp.dead).map(|(pl, _)| pl.0).collect();
+        alive.sort();
+        spectator_camera.following = alive.iter().copied()
+            .find(|id| spectator_camera.following.map_or(true, |following| *id > following))
+            .or(alive.first().copied());
+    }
+    if input.just_pressed(KeyCode::F) {
+        spectator_camera.following = None;
+    }
+    if spectator_camera.following.is_some() { return }
+    let mut dir = Vec2::ZERO;
+    if input.pressed(KeyCode::W) || input.pressed(KeyCode::Up) { dir.y += 1. }
+    if input.pressed(KeyCode::S) || input.pressed(KeyCode::Down) { dir.y -= 1. }
+    if input.pressed(KeyCode::A) || input.pressed(KeyCode::Left) { dir.x -= 1. }
+    if input.pressed(KeyCode::D) || input.pressed(KeyCode::Right) { dir.x += 1. }
+    let step = dir.normalize_or_zero() * FREE_CAMERA_SPEED * time.delta_seconds();
+    for mut camera_transform in &mut game_camera {
+        camera_transform.translation += step.extend(0.);
+        camera::clamp_to_map(&mut camera_transform.translation);
+    }
+}
+
+/// keeps the camera on the followed player, falls back to a free camera once they die
+pub fn spectator_camera(
+    mut spectator_camera: ResMut<SpectatorCamera>,
+    players: Query<(&Player, &Health, &Transform), Without<SpatialCameraBundle>>,
+    mut game_camera: Query<&mut Transform, With<SpatialCameraBundle>>,
+) {
+    let Some(following) = spectator_camera.following else { return };
+    let Some((_, _, player_transform)) = players.iter().find(|(pl, hp, _)| pl.0 == following && !hp.dead) else {
+        spectator_camera.following = None;
+        return;
+    };
+    for mut camera_transform in &mut game_camera {
+        camera_transform.translation.x = player_transform.translation.x;
+        camera_transform.translation.y = player_transform.translation.y;
+        camera::clamp_to_map(&mut camera_transform.translation);
+    }
+}
+
+pub fn spectator_feedback(
+    spectator_camera: Res<SpectatorCamera>,
+    profiles: Res<PlayerProfiles>,
+    mut spectator_displays: Query<&mut Text, With<SpectatorDisplay>>,
+) {
+    let watching = spectator_camera.following.map_or("Free camera".to_string(), |id| format!("Following {}", profiles.name(id)));
+    for mut text in &mut spectator_displays {
+        text.sections[0].value = format!("Spectating - {}\nSpace: next player  F: free camera  Tab: leaderboard", watching);
+    }
+}
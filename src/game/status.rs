@@ -0,0 +1,306 @@
+use bevy::prelude::*;
+use crate::AppState;
+use crate::game::buffers::*;
+use crate::game::components::*;
+use crate::game::map::{Biome, WorldMap, get_tile_at_pos};
+use crate::game::{enemy, player};
+use crate::game::enemy::LastAttacker;
+use crate::game::player::LocalPlayer;
+use crate::net::{self, is_host, TickNum};
+
+pub const NUM_STATUS_TYPES: usize = 4;
+pub const MAX_STATUS_STACKS: u8 = 3; // stacks are packed into 2 bits for the network
+/// how long each status lasts in ticks when applied, indexed by StatusType
+pub const STATUS_TICKS: [u8; NUM_STATUS_TYPES] = [30, 20, 20, 10];
+const POISON_DAMAGE: u8 = 1; // per stack per tick
+const BURN_DAMAGE: u8 = 2; // per stack per tick
+const SLOW_FACTOR: f32 = 0.75; // movement and cooldown speed per stack
+const POISON_WEAKEN: f32 = 0.9; // damage dealt per stack
+const ICON_SIZE: Vec2 = Vec2 { x: 8., y: 8. };
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StatusType {
+    Poison = 0,
+    Slow,
+    Burn,
+    Stun,
+}
+
+pub const STATUS_TYPES: [StatusType; NUM_STATUS_TYPES] = [StatusType::Poison, StatusType::Slow, StatusType::Burn, StatusType::Stun];
+
+impl StatusType {
+    pub fn color(&self) -> Color {
+        match self {
+            StatusType::Poison => Color::LIME_GREEN,
+            StatusType::Slow => Color::CYAN,
+            StatusType::Burn => Color::ORANGE_RED,
+            StatusType::Stun => Color::YELLOW,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatusType::Poison => "Poison",
+            StatusType::Slow => "Slow",
+            StatusType::Burn => "Burn",
+            StatusType::Stun => "Stun",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct StatusEffect {
+    pub stacks: u8,
+    pub ticks: u8,
+}
+
+/// Status effects on a player or enemy, indexed by StatusType.
+/// Only the host counts down durations, clients just mirror what the host sends
+#[derive(Component, Clone, Default)]
+pub struct StatusEffects(pub [StatusEffect; NUM_STATUS_TYPES]);
+
+impl StatusEffects {
+    /// adds a stack and refreshes the duration if the new one is longer
+    pub fn apply(&mut self, status: StatusType, ticks: u8) {
+        let effect = &mut self.0[status as usize];
+        effect.stacks = effect.stacks.saturating_add(1).min(MAX_STATUS_STACKS);
+        effect.ticks = effect.ticks.max(ticks);
+    }
+
+    pub fn active(&self, status: StatusType) -> bool {
+        self.0[status as usize].ticks > 0
+    }
+
+    pub fn stacks(&self, status: StatusType) -> u8 {
+        if self.active(status) { self.0[status as usize].stacks } else { 0 }
+    }
+
+    pub fn clear(&mut self) {
+        *self = StatusEffects::default();
+    }
+
+    /// multiplier for movement speed and cooldown recovery
+    pub fn speed_mult(&self) -> f32 {
+        SLOW_FACTOR.powf(self.stacks(StatusType::Slow) as f32)
+    }
+
+    /// multiplier for outgoing damage
+    pub fn damage_mult(&self) -> f32 {
+        POISON_WEAKEN.powf(self.stacks(StatusType::Poison) as f32)
+    }
+
+    /// damage taken every tick from poison and burn
+    pub fn tick_damage(&self) -> u8 {
+        self.stacks(StatusType::Poison).saturating_mul(POISON_DAMAGE)
+            .saturating_add(self.stacks(StatusType::Burn).saturating_mul(BURN_DAMAGE))
+    }
+
+    /// counts every effect down by one tick, dropping its stacks when it runs out
+    pub fn tick(&mut self) {
+        for effect in self.0.iter_mut() {
+            effect.ticks = effect.ticks.saturating_sub(1);
+            if effect.ticks == 0 {
+                effect.stacks = 0;
+            }
+        }
+    }
+
+    /// 2 bits of stacks per status, 0 meaning inactive
+    pub fn pack(&self) -> u8 {
+        let mut bits = 0;
+        for status in STATUS_TYPES {
+            bits |= self.stacks(status) << (status as u8 * 2);
+        }
+        bits
+    }
+
+    /// mirrors packed statuses, every active one lasts until the next update from the host
+    pub fn unpack(bits: u8) -> StatusEffects {
+        let mut effects = StatusEffects::default();
+        for status in STATUS_TYPES {
+            let stacks = (bits >> (status as u8 * 2)) & 0b11;
+            effects.0[status as usize] = StatusEffect { stacks, ticks: if stacks > 0 { 1 } else { 0 } };
+        }
+        effects
+    }
+}
+
+/// Marks a sprite above an entity that is shown while it has the status
+#[derive(Component)]
+pub struct StatusIcon(pub StatusType);
+
+/// Marks the HUD text listing the local player's statuses
+#[derive(Component)]
+pub struct StatusDisplay;
+
+pub struct StatusPlugin;
+
+impl Plugin for StatusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, (
+                terrain_status.before(status_simulate),
+                status_simulate.after(player::attack_simulate).after(enemy::attack_simulate),
+            ).run_if(in_state(AppState::Game)).run_if(is_host).before(net::host::fixed))
+            .add_systems(Update, (
+                status_draw,
+                status_feedback,
+            ).run_if(in_state(AppState::Game)));
+    }
+}
+
+/// spawns a hidden icon for every status as children of the entity, above its health bar
+pub fn spawn_status_icons(commands: &mut Commands, entity: Entity) {
+    for status in STATUS_TYPES {
+        let offset = (status as usize as f32 - (NUM_STATUS_TYPES - 1) as f32 / 2.) * (ICON_SIZE.x + 2.);
+        let icon = commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: status.color(),
+                    custom_size: Some(ICON_SIZE),
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                transform: Transform::from_xyz(offset, 34., 3.),
+                ..default()
+            },
+            StatusIcon(status),
+        )).id();
+        commands.entity(entity).add_child(icon);
+    }
+}
+
+/// applies damage over time and counts down every status.
+/// damage to an enemy goes in its ledger under whoever hit it last, if anyone has
+pub fn status_simulate(
+    tick: Res<TickNum>,
+    mut players: Query<(&Player, &mut HpBuffer, &Health, &mut StatusEffects, &mut Stats), With<Player>>,
+    mut enemies: Query<(&mut HpBuffer, &mut StatusEffects, &LastAttacker, &mut DamageLedger), (With<Enemy>, Without<Player>)>,
+) {
+    for (mut hb, mut effects, last_attacker, mut ledger) in &mut enemies {
+        let cur = hb.0.get(tick.0).unwrap_or(0);
+        let damage = effects.tick_damage().min(cur);
+        if damage > 0 {
+            hb.0.set(tick.0, Some(cur - damage));
+        }
+        if let (true, Some(attacker)) = (damage > 0, last_attacker.0) {
+            ledger.record(attacker, damage);
+            if let Some((.., mut stats)) = players.iter_mut().find(|(pl, ..)| pl.0 == attacker) {
+                stats.record_damage(damage);
+            }
+        }
+        effects.tick();
+    }
+    for (_, mut hb, hp, mut effects, mut stats) in &mut players {
+        if hp.dead {
+            effects.clear();
+            continue;
+        }
+        let cur = hb.0.get(tick.0).unwrap_or(0);
+        let damage = effects.tick_damage().min(cur);
+        if damage > 0 {
+            hb.0.set(tick.0, Some(cur - damage));
+            stats.record_hurt(damage);
+        }
+        effects.tick();
+    }
+}
+
+/// walls set players on fire
+pub fn terrain_status(
+    tick: Res<TickNum>,
+    map: Res<WorldMap>,
+    mut players: Query<(&PosBuffer, &Health, &mut StatusEffects), With<Player>>,
+) {
+    for (pb, hp, mut effects) in &mut players {
+        if hp.dead { continue }
+        let pos = pb.0.get(tick.0);
+        if pos.is_none() { continue }
+        if get_tile_at_pos(&pos.unwrap().extend(0.), &map.biome_map) == Biome::Wall && !effects.active(StatusType::Burn) {
+            effects.apply(StatusType::Burn, STATUS_TICKS[StatusType::Burn as usize]);
+        }
+    }
+}
+
+pub fn status_draw(
+    entities: Query<(&StatusEffects, &Children)>,
+    mut icons: Query<(&mut Visibility, &StatusIcon)>,
+) {
+    for (effects, children) in &entities {
+        for child in children.iter() {
+            if let Ok((mut vis, icon)) = icons.get_mut(*child) {
+                *vis = if effects.active(icon.0) { Visibility::Inherited } else { Visibility::Hidden };
+            }
+        }
+    }
+}
+
+/// lists the local player's statuses and their stacks in the HUD
+pub fn status_feedback(
+    players: Query<&StatusEffects, With<LocalPlayer>>,
+    mut status_displays: Query<&mut Text, With<StatusDisplay>>,
+) {
+    let effects = players.get_single();
+    if effects.is_err() { return }
+    let effects = effects.unwrap();
+    for mut text in &mut status_displays {
+        for status in STATUS_TYPES {
+            let stacks = effects.stacks(status);
+            text.sections[status as usize].value = if stacks > 0 {
+                format!("{} x{}  ", status.name(), stacks)
+            } else {
+                String::new()
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stacks_cap_and_keep_the_longest_duration() {
+        let mut effects = StatusEffects::default();
+        for _ in 0..5 {
+            effects.apply(StatusType::Poison, 5);
+        }
+        effects.apply(StatusType::Poison, 2);
+        assert_eq!(effects.stacks(StatusType::Poison), MAX_STATUS_STACKS);
+        assert_eq!(effects.0[StatusType::Poison as usize].ticks, 5);
+        assert_eq!(effects.tick_damage(), MAX_STATUS_STACKS * POISON_DAMAGE);
+    }
+
+    #[test]
+    fn statuses_run_out_with_their_stacks() {
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusType::Burn, 2);
+        effects.apply(StatusType::Burn, 1);
+        effects.tick();
+        assert_eq!(effects.stacks(StatusType::Burn), 2);
+        effects.tick();
+        assert!(!effects.active(StatusType::Burn));
+        assert_eq!(effects.0[StatusType::Burn as usize].stacks, 0);
+        assert_eq!(effects.tick_damage(), 0);
+    }
+
+    #[test]
+    fn pack_round_trip() {
+        let mut effects = StatusEffects::default();
+        for (i, status) in STATUS_TYPES.into_iter().enumerate() {
+            for _ in 0..i {
+                effects.apply(status, 10);
+            }
+        }
+        let bits = effects.pack();
+        assert_eq!(bits, 0b11_10_01_00);
+        let mirrored = StatusEffects::unpack(bits);
+        for status in STATUS_TYPES {
+            assert_eq!(mirrored.stacks(status), effects.stacks(status));
+        }
+        // mirrored statuses last until the next update from the host
+        let mut mirrored = mirrored;
+        mirrored.tick();
+        assert_eq!(mirrored.pack(), 0);
+        assert_eq!(StatusEffects::unpack(0xff).pack(), 0xff);
+    }
+}
@@ -15,7 +15,10 @@ pub enum AppState {
     MainMenu,
     Hosting,
     Joining,
+    Profile,
+    Replays,
     Controls,
+    Lobby,
     Game,
     GameOver,
     Credits,
@@ -1,6 +1,7 @@
 use bevy::prelude::Component;
 
 use crate::AppState;
+use crate::game::profile::BindAction;
 
 #[derive(Component)]
 pub struct MainMenu;
@@ -17,16 +18,37 @@ pub struct JoinPage;
 #[derive(Component)]
 pub struct ControlsPage;
 
+#[derive(Component)]
+pub struct ProfilePage;
+
+#[derive(Component)]
+pub struct ReplaysPage;
+
 #[derive(Component)]
 pub struct CreditsPage;
 
 #[derive(Component)]
 pub struct ConnectingPage;
 
+#[derive(Component)]
+pub struct LobbyPage;
+
+/// Who the connecting page is waiting on and how many times it's asked
+#[derive(Component)]
+pub struct ConnectingText;
+
+/// Settings, players and countdown shown in the lobby
+#[derive(Component)]
+pub struct LobbyText;
+
 #[derive(Component)]
 pub struct Popup;
 
+/// A popup with an error in it, removed when its timer runs out
 #[derive(Component)]
+pub struct ErrorPopup;
+
+#[derive(Component, Default)]
 pub struct Switch{
     pub host_port: bool,
     pub port: bool,
@@ -37,6 +59,8 @@ pub struct Switch{
     pub enemy_per_camp: bool,
     pub map_seed: bool,
     pub eid_percentage: bool,
+    pub name: bool,
+    pub chat: bool,
 }
 
 pub trait InputType: Component {
@@ -245,6 +269,50 @@ impl InputType for JoinIPInput {
     }
 }
 
+impl InputType for ProfileNameInput {
+    fn push_char(&mut self, ch: char) {
+        self.name.push(ch);
+    }
+
+    fn pop_char(&mut self) {
+        self.name.pop();
+    }
+
+    fn is_empty(&self) -> bool {
+        self.name.is_empty()
+    }
+
+    fn is_active(switch: &Switch) -> bool {
+        switch.name
+    }
+
+    fn is_valid(active: bool) -> bool {
+        active
+    }
+}
+
+impl InputType for ChatInput {
+    fn push_char(&mut self, ch: char) {
+        self.text.push(ch);
+    }
+
+    fn pop_char(&mut self) {
+        self.text.pop();
+    }
+
+    fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    fn is_active(switch: &Switch) -> bool {
+        switch.chat
+    }
+
+    fn is_valid(active: bool) -> bool {
+        active
+    }
+}
+
 pub trait ButtonTypeTrait {
     type Marker: Component;
     fn app_state() -> AppState;
@@ -274,6 +342,31 @@ impl ButtonTypeTrait for BackButtonType {
     }
 }
 
+/// leaves through game over so the round still gets cleaned up
+pub struct LeaveMatchButtonType;
+impl ButtonTypeTrait for LeaveMatchButtonType {
+    type Marker = LeaveMatchButton;
+    fn app_state() -> AppState {
+        AppState::GameOver
+    }
+}
+
+pub struct ProfileButtonType;
+impl ButtonTypeTrait for ProfileButtonType {
+    type Marker = ProfileButton;
+    fn app_state() -> AppState {
+        AppState::Profile
+    }
+}
+
+pub struct ReplaysButtonType;
+impl ButtonTypeTrait for ReplaysButtonType {
+    type Marker = ReplaysButton;
+    fn app_state() -> AppState {
+        AppState::Replays
+    }
+}
+
 pub struct ControlsButtonType;
 impl ButtonTypeTrait for ControlsButtonType {
     type Marker = ControlsButton;
@@ -290,6 +383,14 @@ impl ButtonTypeTrait for CreditsButtonType {
     }
 }
 
+pub struct CancelConnectButtonType;
+impl ButtonTypeTrait for CancelConnectButtonType {
+    type Marker = CancelConnectButton;
+    fn app_state() -> AppState {
+        AppState::Joining
+    }
+}
+
 pub struct QuitButtonType;
 impl ButtonTypeTrait for QuitButtonType {
     type Marker = QuitButton;
@@ -304,6 +405,12 @@ pub struct HostButton;
 #[derive(Component)]
 pub struct JoinButton;
 
+#[derive(Component)]
+pub struct ProfileButton;
+
+#[derive(Component)]
+pub struct ReplaysButton;
+
 #[derive(Component)]
 pub struct ControlsButton;
 
@@ -313,6 +420,9 @@ pub struct CreditsButton;
 #[derive(Component)]
 pub struct QuitButton;
 
+#[derive(Component)]
+pub struct CancelConnectButton;
+
 #[derive(Component)]
 pub struct BackToMainMenu;
 
@@ -322,6 +432,79 @@ pub struct LeaderboardUi;
 #[derive(Component)]
 pub struct LeaderboardUiTitle;
 
+#[derive(Component)]
+pub struct GameModeButton;
+
+#[derive(Component)]
+pub struct TeamButton;
+
+#[derive(Component)]
+pub struct FriendlyFireButton;
+
+#[derive(Component)]
+pub struct TieBreakButton;
+
+#[derive(Component)]
+pub struct DeathPenaltyButton;
+
+#[derive(Component)]
+pub struct AddBotButton;
+
+#[derive(Component)]
+pub struct SpectateButton;
+
+#[derive(Component)]
+pub struct MaxPlayersButton;
+
+#[derive(Component)]
+pub struct RemoveBotButton;
+
+#[derive(Component)]
+pub struct BotDifficultyButton;
+
+#[derive(Component)]
+pub struct PowerUpCapButton;
+
+#[derive(Component)]
+pub struct PowerUpFalloffButton;
+
+#[derive(Component)]
+pub struct PowerUpDropButton;
+
+#[derive(Component)]
+pub struct KickPickButton;
+
+#[derive(Component)]
+pub struct KickButton;
+
+#[derive(Component)]
+pub struct OvertimeBanner;
+
+#[derive(Component)]
+pub struct HostLostBanner;
+
+#[derive(Component)]
+pub struct LeaveMatchButton;
+
+#[derive(Component)]
+pub struct ResultsDisplay;
+
+#[derive(Component)]
+pub struct ReadyButton;
+
+#[derive(Component)]
+pub struct RematchButton;
+
+#[derive(Component)]
+pub struct TeamTotalsDisplay;
+
+/// Holds a leaderboard row for each player in the match
+#[derive(Component)]
+pub struct LeaderboardRows;
+
+#[derive(Component)]
+pub struct LeaderboardRow(pub u8);
+
 #[derive(Component)]
 pub struct PlayerStatDisplay{
     pub player_id: u8,
@@ -406,5 +589,55 @@ pub struct JoinIPInput {
 #[derive(Component)]
 pub struct JoinSaveButton;
 
+/// Holds a button per game found on the LAN
+#[derive(Component)]
+pub struct LanServerList;
+
+/// Joins the game at this index in LanServers
+#[derive(Component)]
+pub struct LanServerButton(pub usize);
+
+#[derive(Component)]
+pub struct ProfileNameButton;
+
+#[derive(Component)]
+pub struct ProfileNameInput {
+    pub name: String,
+}
+
+#[derive(Component)]
+pub struct ProfileColorButton;
+
+/// Waits for a new key for the action when clicked
+#[derive(Component)]
+pub struct RebindButton(pub BindAction);
+
+#[derive(Component)]
+pub struct ProfileSaveButton;
+
+/// Picks the next recorded match when clicked
+#[derive(Component)]
+pub struct ReplayFileButton;
+
+/// Describes the picked replay
+#[derive(Component)]
+pub struct ReplayInfoText;
+
+#[derive(Component)]
+pub struct ReplayWatchButton;
+
+#[derive(Component)]
+pub struct RecordAsClientButton;
+
+/// The line being typed into chat, without the prompt
+#[derive(Component)]
+pub struct ChatInput {
+    pub text: String,
+}
+
+/// The chat input line, only shown while it's open
+#[derive(Component)]
+pub struct ChatInputLine;
+
 #[derive(Component)]
 pub struct Initialized;
\ No newline at end of file
@@ -3,8 +3,24 @@ use bevy::prelude::*;
 use crate::menus::components::*;
 use crate::AppState;
 use crate::game::PlayerId;
-use crate::menus::NetworkAddresses;
+use crate::menus::{KickTarget, NetworkAddresses};
 use crate::game::MapConfig;
+use crate::game::mode::{GameMode, TeamPreference, Teams};
+use crate::game::map::{MapSeed, NumCamps};
+use crate::game::round::TieBreak;
+use crate::game::respawn::DeathPenalty;
+use crate::game::powerup::PowerUpConfig;
+use crate::game::bot::{BotDifficulty, Bots};
+use crate::game::spectate::JoinAsSpectator;
+use crate::game::player::{MaxPlayers, MAX_PLAYERS};
+use crate::game::profile::{self, PlayerProfiles, Profile, Rebinding};
+use crate::game::chat::KickEvent;
+use crate::game::movement::KeyBinds;
+use crate::menus::layout::rebind_label;
+use crate::net::IsHost;
+use crate::net::lobby::Lobby;
+use crate::net::replay::{self, RecordAsClient, Replay, ReplayFiles};
+use crate::net::discovery::LanServers;
 use rand::Rng;
 use bevy::app::AppExit;
 
@@ -56,6 +72,8 @@ pub fn update_input<T: InputType>(
     if let Some(new_char) = new_char {
         for (mut text, mut input_type) in query.iter_mut() {
             if new_char != '\u{8}' && new_char != '\u{7f}' {
+                // enter and the like come through as characters too
+                if new_char.is_control() { continue }
                 text.sections[0].value.push(new_char);
                 input_type.push_char(new_char);
             }
@@ -124,6 +142,22 @@ pub fn update_join_port_input(
     update_input::<JoinPortInput>(char_events, query, Some(switch_query));
 }
 
+pub fn update_profile_name_input(
+    char_events: EventReader<ReceivedCharacter>,
+    query: Query<(&mut Text, &mut ProfileNameInput)>,
+    switch_query: Query<&Switch>,
+) {
+    update_input::<ProfileNameInput>(char_events, query, Some(switch_query));
+}
+
+pub fn update_chat_input(
+    char_events: EventReader<ReceivedCharacter>,
+    query: Query<(&mut Text, &mut ChatInput)>,
+    switch_query: Query<&Switch>,
+) {
+    update_input::<ChatInput>(char_events, query, Some(switch_query));
+}
+
 pub fn update_join_host_port_input(
     char_events: EventReader<ReceivedCharacter>,
     query: Query<(&mut Text, &mut JoinHostPortInput)>,
@@ -181,7 +215,7 @@ pub fn save_host_input(
                     map_config.eid_percentage = input.value.clone();
                     //println!("eid percentage to {:?}", map_config.eid_percentage);
                 }
-                app_state_next_state.set(AppState::Game);
+                app_state_next_state.set(AppState::Lobby);
             }
             Interaction::Hovered => {
                 *background_color = Color::GRAY.into();
@@ -336,6 +370,586 @@ pub fn map_seed_but(
         }
     }
 }
+/// cycles through the game modes, showing the current one on the button
+pub fn game_mode_but(
+    mut game_mode: ResMut<GameMode>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<GameModeButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *game_mode = game_mode.next();
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = format!("Mode: {}", game_mode.name());
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+pub fn ready_but(
+    mut lobby: ResMut<Lobby>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<ReadyButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                lobby.local_ready = !lobby.local_ready;
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = if lobby.local_ready { "Not Ready" } else { "Ready" }.to_string();
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+/// takes everyone back to the lobby with a fresh map, keeping the connections
+pub fn rematch_but(
+    mut seed: ResMut<MapSeed>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<RematchButton>),
+    >,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
+) {
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                seed.0 = rand::thread_rng().gen();
+                app_state_next_state.set(AppState::Lobby);
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+/// cycles through the teams a player can ask to be put on
+pub fn team_but(
+    mut team_preference: ResMut<TeamPreference>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<TeamButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *team_preference = team_preference.next();
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = format!("Team: {}", team_preference.name());
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+/// switches between joining as a player and as a spectator
+pub fn spectate_but(
+    mut join_as_spectator: ResMut<JoinAsSpectator>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<SpectateButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                join_as_spectator.0 = !join_as_spectator.0;
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = format!("Join as: {}", join_as_spectator.name());
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+/// goes through the recorded matches, loading each one to say what it was
+pub fn replay_file_but(
+    mut files: ResMut<ReplayFiles>,
+    mut replay: ResMut<Replay>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<ReplayFileButton>),
+    >,
+    mut text_query: Query<&mut Text, Without<ReplayInfoText>>,
+    mut info_query: Query<&mut Text, With<ReplayInfoText>>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                files.next();
+                replay::select_replay(&files, &mut replay);
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = files.label();
+                    }
+                }
+                for mut text in &mut info_query {
+                    text.sections[0].value = if files.paths.is_empty() { String::new() } else { replay.describe() };
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+/// starts watching the loaded replay as a spectator
+pub fn watch_replay_but(
+    mut replay: ResMut<Replay>,
+    mut is_host: ResMut<IsHost>,
+    mut player_id: ResMut<PlayerId>,
+    settings: (ResMut<MapSeed>, ResMut<GameMode>, ResMut<TieBreak>, ResMut<NumCamps>),
+    mut lobby: ResMut<Lobby>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<ReplayWatchButton>),
+    >,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
+) {
+    let (mut seed, mut game_mode, mut tie_break, mut num_camps) = settings;
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                if replay::watch_replay(&mut replay, &mut is_host, &mut player_id,
+                    (&mut seed, &mut game_mode, &mut tie_break, &mut num_camps), &mut lobby) {
+                    app_state_next_state.set(AppState::Game);
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+/// switches whether matches played as a client get recorded too
+pub fn record_as_client_but(
+    mut record_as_client: ResMut<RecordAsClient>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<RecordAsClientButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                record_as_client.0 = !record_as_client.0;
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = format!("Record as client: {}", record_as_client.name());
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+/// cycles through how many players the host lets in
+pub fn max_players_but(
+    mut max_players: ResMut<MaxPlayers>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<MaxPlayersButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *max_players = max_players.next();
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = format!("Max Players: {}", max_players.0);
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+/// cycles through the rules for settling a tie for first
+pub fn tie_break_but(
+    mut tie_break: ResMut<TieBreak>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<TieBreakButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *tie_break = tie_break.next();
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = format!("Tie-break: {}", tie_break.name());
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+/// cycles through what players lose when they die
+pub fn death_penalty_but(
+    mut death_penalty: ResMut<DeathPenalty>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<DeathPenaltyButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *death_penalty = death_penalty.next();
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = format!("Death Penalty: {}", death_penalty.name());
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+/// fills the first free slot with a bot at the picked difficulty
+pub fn add_bot_but(
+    mut bots: ResMut<Bots>,
+    mut teams: ResMut<Teams>,
+    lobby: Res<Lobby>,
+    bot_difficulty: Res<BotDifficulty>,
+    max_players: Res<MaxPlayers>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<AddBotButton>),
+    >,
+) {
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                if let Some(id) = bots.add(bot_difficulty.0, &lobby.connected, max_players.0) {
+                    teams.assign(id, None);
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+pub fn remove_bot_but(
+    mut bots: ResMut<Bots>,
+    mut teams: ResMut<Teams>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<RemoveBotButton>),
+    >,
+) {
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                if let Some(id) = bots.remove() {
+                    teams.members[id as usize] = None;
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+/// cycles the difficulty of bots added from now on
+pub fn bot_difficulty_but(
+    mut bot_difficulty: ResMut<BotDifficulty>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<BotDifficultyButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                bot_difficulty.0 = bot_difficulty.0.next();
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = format!("Bots: {}", bot_difficulty.0.name());
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+pub fn powerup_cap_but(
+    mut powerup_config: ResMut<PowerUpConfig>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<PowerUpCapButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                powerup_config.next_cap();
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = format!("Powerup Cap: {}", powerup_config.cap_name());
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+pub fn powerup_falloff_but(
+    mut powerup_config: ResMut<PowerUpConfig>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<PowerUpFalloffButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                powerup_config.next_falloff();
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = format!("Diminishing Returns: {}", powerup_config.falloff_name());
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+pub fn powerup_drop_but(
+    mut powerup_config: ResMut<PowerUpConfig>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<PowerUpDropButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                powerup_config.next_death_drop();
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = format!("Dropped on Death: {}", powerup_config.death_drop_name());
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+/// cycles through the players in the lobby that can be kicked, and forgets one that left on their own
+pub fn kick_pick_but(
+    mut kick_target: ResMut<KickTarget>,
+    lobby: Res<Lobby>,
+    profiles: Res<PlayerProfiles>,
+    mut button_query: Query<(Ref<Interaction>, &mut BackgroundColor, &Children), With<KickPickButton>>,
+    mut text_query: Query<&mut Text>,
+) {
+    let kickable = |id: usize| id != 0 && lobby.connected[id] && !lobby.bots[id];
+    if kick_target.0.is_some_and(|id| !kickable(id as usize)) {
+        kick_target.0 = None;
+    }
+    let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() else { return };
+    if interaction.is_changed() {
+        match *interaction {
+            Interaction::Pressed => {
+                // after the last one it goes back to nobody
+                let from = kick_target.0.map_or(1, |id| id as usize + 1);
+                kick_target.0 = (from..MAX_PLAYERS).find(|id| kickable(*id)).map(|id| id as u8);
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+    if !kick_target.is_changed() && !profiles.is_changed() { return }
+    let name = kick_target.0.map_or("Nobody".to_string(), |id| profiles.name(id));
+    for child in children.iter() {
+        if let Ok(mut text) = text_query.get_mut(*child) {
+            text.sections[0].value = format!("Kick: {}", name);
+        }
+    }
+}
+
+pub fn kick_but(
+    mut kick_target: ResMut<KickTarget>,
+    mut kick_writer: EventWriter<KickEvent>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<KickButton>),
+    >,
+) {
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                if let Some(id) = kick_target.0.take() {
+                    kick_writer.send(KickEvent(id));
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+pub fn friendly_fire_but(
+    mut teams: ResMut<Teams>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<FriendlyFireButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                teams.friendly_fire = !teams.friendly_fire;
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = format!("Friendly Fire: {}", if teams.friendly_fire { "On" } else { "Off" });
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
 pub fn eid_percentage_but(
     mut button_query: Query<
         (&Interaction, &mut BackgroundColor),
@@ -478,6 +1092,41 @@ pub fn save_join_input(
     }
 }
 
+/// joins a game found on the LAN, using the typed client port if there is one
+pub fn lan_server_but(
+    mut is_host: ResMut<crate::net::IsHost>,
+    mut net_address: ResMut<NetworkAddresses>,
+    servers: Res<LanServers>,
+    join_port_query: Query<&JoinPortInput>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &LanServerButton),
+        Changed<Interaction>,
+    >,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
+) {
+    for (interaction, mut background_color, button) in &mut button_query {
+        match *interaction {
+            Interaction::Pressed => {
+                let Some(server) = servers.0.get(button.0) else { continue };
+                if !server.compatible { continue }
+                net_address.ip = server.addr.ip().to_string();
+                net_address.host_port = server.addr.port().to_string();
+                for join_port_input in join_port_query.iter() {
+                    net_address.client_port = join_port_input.port.clone();
+                }
+                is_host.0 = false;
+                app_state_next_state.set(AppState::Connecting);
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
 pub fn init_input_system_with_default<T: InputType>(
     default_value: &str,
     mut commands: Commands,
@@ -508,13 +1157,6 @@ pub fn init_join_host_port_input_system(
     init_input_system_with_default::<JoinHostPortInput>("8085", commands, join_host_port_query);
 }
 
-pub fn init_join_port_input_system(
-    commands: Commands,
-    join_port_query: Query<(Entity, &mut Text, &mut JoinPortInput), Without<Initialized>>,
-) {
-    init_input_system_with_default::<JoinPortInput>("8086", commands, join_port_query);
-}
-
 pub fn init_join_ip_input_system(
     commands: Commands,
     join_ip_query: Query<(Entity, &mut Text, &mut JoinIPInput), Without<Initialized>>,
@@ -564,4 +1206,150 @@ pub fn init_eid_percentage_input_system(
 
 pub fn exit_system(mut exit: EventWriter<AppExit>) {
     exit.send(AppExit);
-}
\ No newline at end of file
+}
+pub fn init_profile_name_input_system(
+    profile: Res<Profile>,
+    commands: Commands,
+    profile_name_query: Query<(Entity, &mut Text, &mut ProfileNameInput), Without<Initialized>>,
+) {
+    init_input_system_with_default::<ProfileNameInput>(&profile.name, commands, profile_name_query);
+}
+
+pub fn profile_name_but(
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<ProfileNameButton>),
+    >,
+    mut switch_query: Query<&mut Switch>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                rebinding.0 = None;
+                for mut switch in switch_query.iter_mut() {
+                    switch.name = true;
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+/// cycles through the player colors
+pub fn profile_color_but(
+    mut profile: ResMut<Profile>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<ProfileColorButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                profile.color = (profile.color + 1) % MAX_PLAYERS as u8;
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = format!("Color: {}", profile.color_name());
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+/// starts listening for a new key for the button's action
+pub fn rebind_but(
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &RebindButton),
+        Changed<Interaction>,
+    >,
+    mut switch_query: Query<&mut Switch>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    for (interaction, mut background_color, rebind) in &mut button_query {
+        match *interaction {
+            Interaction::Pressed => {
+                rebinding.0 = Some(rebind.0);
+                // typing the key shouldn't also go into the name
+                for mut switch in switch_query.iter_mut() {
+                    switch.name = false;
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
+
+/// binds the next key pressed to whatever the profile page is waiting on, and keeps the labels up to date
+pub fn capture_rebind(
+    input: Res<Input<KeyCode>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut key_binds: ResMut<KeyBinds>,
+    button_query: Query<(&RebindButton, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    if let Some(action) = rebinding.0 {
+        if let Some(key) = input.get_just_pressed().find(|key| profile::is_bindable(**key)) {
+            *action.key_mut(&mut key_binds) = *key;
+            rebinding.0 = None;
+        }
+    }
+    if !rebinding.is_changed() && !key_binds.is_changed() { return }
+    for (rebind, children) in &button_query {
+        for child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                text.sections[0].value = rebind_label(rebind.0, &key_binds, rebinding.0 == Some(rebind.0));
+            }
+        }
+    }
+}
+
+/// keeps the name and key binds, writes them to the profile file and goes back to the main menu
+pub fn save_profile_but(
+    mut profile: ResMut<Profile>,
+    key_binds: Res<KeyBinds>,
+    name_query: Query<&ProfileNameInput>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<ProfileSaveButton>),
+    >,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
+) {
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                for name_input in name_query.iter() {
+                    profile.name = profile::sanitize_name(&name_input.name);
+                }
+                if let Err(e) = profile.save(&key_binds) {
+                    println!("couldn't save profile: {}", e);
+                }
+                app_state_next_state.set(AppState::MainMenu);
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.into();
+            }
+            Interaction::None => {
+                *background_color = Color:: rgb(0.15, 0.15, 0.15).into();
+            }
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use bevy::ecs::system::EntityCommands;
 use crate::menus::components::*;
+use crate::menus::KickTarget;
 use bevy::prelude::Deref;
 use bevy::prelude::DerefMut;
 use bevy::prelude::Timer;
@@ -7,12 +8,35 @@ use bevy::prelude::*;
 use crate::game::camera::SpatialCameraBundle;
 use crate::game::components::*;
 use crate::game::ROUND_TIME;
+use crate::game::status::{StatusDisplay, STATUS_TYPES};
+use crate::game::item::{HotbarSlot, HotbarText, HOTBAR_SLOTS};
+use crate::game::mode::{GameMode, Joined, ModeDisplay, NUM_TEAMS, TEAM_COLORS, TeamPreference, Teams};
+use crate::game::map::{MapSeed, NumCamps};
+use crate::game::player::{self, MaxPlayers, MAX_PLAYERS};
+use crate::game::PlayerId;
+use crate::net::lobby::Lobby;
+use crate::game::round::{self, MatchPhase, Round, TieBreak, WARMUP_TICKS};
+use crate::game::respawn::{DeathPenalty, RespawnDisplay};
+use crate::game::powerup::PowerUpConfig;
+use crate::game::bot::BotDifficulty;
+use crate::game::spectate::{JoinAsSpectator, SpectatorDisplay, SPECTATOR_ID};
+use crate::game::profile::{BindAction, PlayerProfiles, Profile, Rebinding};
+use crate::game::movement::KeyBinds;
+use crate::game::killfeed::KillFeedDisplay;
+use crate::game::chat::{ChatLogDisplay, CHAT_PROMPT};
 use crate::AppState;
-use crate::net::{TICKLEN_S, TickNum};
+use crate::net::{IsHost, TICKLEN_S, TickNum};
+use crate::net::replay::{RecordAsClient, Replay, ReplayDisplay, ReplayFiles};
+use crate::net::discovery::LanServers;
+use crate::net::client::{ConnectAttempt, CONNECT_TRIES};
 
 pub const SCREEN_WIDTH: f32 = 1280.0;
 pub const SCREEN_HEIGHT: f32 = 720.0;
 pub const PADDING: f32 = 20.0;
+const POPUP_S: f32 = 4.0;
+const MAX_LAN_SERVERS_SHOWN: usize = 6; // as many as fit down the join page
+pub const LEADERBOARD_MEASURES: [&str; 14] = ["Player", "Score", "Enemy Kills", "Player Kills", "Camps Captured", "Deaths", "KD", "Assists",
+    "Damage Dealt", "Damage Taken", "Powerups", "Chests", "Best Streak", "Time Alive"];
 
 #[derive(Component, Deref, DerefMut)]
 pub struct PopupTimer(Timer);
@@ -22,19 +46,61 @@ pub struct GameTimer {
     remaining_time: f32, // time in seconds
 }
 
+/// Something that went wrong, shown across the top of the screen for a few seconds
+#[derive(Event)]
+pub struct PopupEvent(pub String);
+
+/// credits slides come to the front when their timer is up, error popups go away
 pub fn show_popup(
+    mut commands: Commands,
     time: Res<Time>,
-    mut popup: Query<(&mut PopupTimer,
-                      &mut Transform)>
+    mut popup: Query<(Entity,
+                      &mut PopupTimer,
+                      &mut Transform,
+                      Option<&ErrorPopup>)>
 ) {
-    for (mut timer, mut transform) in popup.iter_mut() {
+    for (entity, mut timer, mut transform, error) in popup.iter_mut() {
         timer.tick(time.delta());
         if timer.just_finished() {
-            transform.translation.z += 10.;
+            if error.is_some() {
+                commands.entity(entity).despawn_recursive();
+            } else {
+                transform.translation.z += 10.;
+            }
         }
     }
 }
 
+pub fn spawn_error_popups(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut popup_reader: EventReader<PopupEvent>,
+) {
+    for ev in popup_reader.iter() {
+        println!("{}", ev.0);
+        commands.spawn((NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(SCREEN_WIDTH / 2.0 - 300.0),
+                top: Val::Px(PADDING),
+                width: Val::Px(600.0),
+                padding: UiRect::all(Val::Px(8.)),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            background_color: Color::rgb(0.6, 0.1, 0.1).into(),
+            z_index: ZIndex::Global(10),
+            ..default()
+        }, ErrorPopup, PopupTimer(Timer::from_seconds(POPUP_S, TimerMode::Once)))).with_children(|parent| {
+            parent.spawn(TextBundle::from_section(ev.0.clone(), TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 24.0,
+                color: Color::WHITE,
+            }).with_text_alignment(TextAlignment::Center));
+        });
+    }
+}
+
 fn spawn_title(
     parent: &mut EntityCommands,
     font: &Handle<Font>,
@@ -198,6 +264,8 @@ fn spawn_input<T: Bundle, U: Bundle>(
             enemy_per_camp: false,
             map_seed: false,
             eid_percentage: false,
+            name: false,
+            chat: false,
         },
         button,
     )).id();
@@ -285,6 +353,8 @@ pub fn spawn_main_menu(
     main_menu.add_child(shadow);
     spawn_button(&mut main_menu, &font, HostButton, "Host");
     spawn_button(&mut main_menu, &font, JoinButton, "Join");
+    spawn_button(&mut main_menu, &font, ProfileButton, "Profile");
+    spawn_button(&mut main_menu, &font, ReplaysButton, "Replays");
     spawn_button(&mut main_menu, &font, ControlsButton, "Controls");
     spawn_button(&mut main_menu, &font, CreditsButton, "Credits");
 }
@@ -372,7 +442,13 @@ pub fn despawn_host_page(
 
 pub fn spawn_host_page(
     mut commands: Commands,
-    asset_server: Res<AssetServer>
+    asset_server: Res<AssetServer>,
+    game_mode: Res<GameMode>,
+    team_preference: Res<TeamPreference>,
+    teams: Res<Teams>,
+    tie_break: Res<TieBreak>,
+    death_penalty: Res<DeathPenalty>,
+    max_players: Res<MaxPlayers>,
 ) {
     let font = asset_server.load("fonts/FiraSans-Bold.ttf");
     let host_page_id = spawn_flex_column(&mut commands, HostPage);
@@ -387,11 +463,17 @@ pub fn spawn_host_page(
     spawn_input(&mut host_page_left, &font, NumCampsButton, NumCampsInput { value: String::new() }, "Number of Camps: ");
     spawn_input(&mut host_page_left, &font, NumChestsButton, NumChestsInput { value: String::new() }, "Number of Chests: ");
     spawn_input(&mut host_page_left, &font, EnemiesPerCampButton, EnemiesPerCampInput { value: String::new() }, "Number of Enemies Per Camp: ");
+    spawn_button(&mut host_page_left, &font, MaxPlayersButton, &format!("Max Players: {}", max_players.0));
     let host_page_right_id = spawn_flex_column(&mut commands, ());
     commands.entity(host_page_row_id).add_child(host_page_right_id);
     let mut host_page_right = commands.entity(host_page_right_id);
     spawn_input(&mut host_page_right, &font, MapSeedButton, MapSeedInput { value: String::new() }, "Map Seed: ");
     spawn_input(&mut host_page_right, &font, EidPercentageButton, EidPercentageInput { value: String::new() }, "EID Percentage: ");
+    spawn_button(&mut host_page_right, &font, GameModeButton, &format!("Mode: {}", game_mode.name()));
+    spawn_button(&mut host_page_right, &font, TeamButton, &format!("Team: {}", team_preference.name()));
+    spawn_button(&mut host_page_right, &font, FriendlyFireButton, &format!("Friendly Fire: {}", if teams.friendly_fire { "On" } else { "Off" }));
+    spawn_button(&mut host_page_right, &font, TieBreakButton, &format!("Tie-break: {}", tie_break.name()));
+    spawn_button(&mut host_page_right, &font, DeathPenaltyButton, &format!("Death Penalty: {}", death_penalty.name()));
     spawn_button(&mut host_page_right, &font, HostPortSaveButton, "Host Now");
     spawn_button(&mut host_page_right, &font, BackToMainMenu, "Back");
 }
@@ -408,17 +490,142 @@ pub fn despawn_join_page(
 
 pub fn spawn_join_page(
     mut commands: Commands,
-    asset_server: Res<AssetServer>
+    asset_server: Res<AssetServer>,
+    team_preference: Res<TeamPreference>,
+    join_as_spectator: Res<JoinAsSpectator>,
 ) {
     let font = asset_server.load("fonts/FiraSans-Bold.ttf");
     let join_page_id = spawn_flex_column(&mut commands, JoinPage);
-    let mut join_page = commands.entity(join_page_id);
-    spawn_title(&mut join_page, &font, "Join a game");
-    spawn_input(&mut join_page, &font, JoinPortButton, JoinPortInput { port: String::new() }, "Your Port: ");
+    spawn_title(&mut commands.entity(join_page_id), &font, "Join a game");
+    let join_page_row_id = spawn_flex_row(&mut commands, ());
+    commands.entity(join_page_id).add_child(join_page_row_id);
+    let join_page_left_id = spawn_flex_column(&mut commands, ());
+    commands.entity(join_page_row_id).add_child(join_page_left_id);
+    let mut join_page = commands.entity(join_page_left_id);
+    spawn_input(&mut join_page, &font, JoinPortButton, JoinPortInput { port: String::new() }, "Your Port (optional): ");
     spawn_input(&mut join_page, &font, JoinHostPortButton, JoinHostPortInput { port: String::new() }, "Host Port: ");
-    spawn_input(&mut join_page, &font, JoinIpButton, JoinIPInput { ip: String::new() }, "Host IP: ");
+    spawn_input(&mut join_page, &font, JoinIpButton, JoinIPInput { ip: String::new() }, "Host Address: ");
+    spawn_button(&mut join_page, &font, TeamButton, &format!("Team: {}", team_preference.name()));
+    spawn_button(&mut join_page, &font, SpectateButton, &format!("Join as: {}", join_as_spectator.name()));
     spawn_button(&mut join_page, &font, JoinSaveButton, "Join Now");
     spawn_button(&mut join_page, &font, BackToMainMenu, "Back");
+    // games found on the LAN, filled in by update_lan_server_list
+    let lan_list_id = spawn_flex_column(&mut commands, LanServerList);
+    commands.entity(join_page_row_id).add_child(lan_list_id);
+}
+
+/// a button per game found on the LAN, rebuilt whenever the list changes
+pub fn update_lan_server_list(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    servers: Res<LanServers>,
+    lan_lists: Query<Entity, With<LanServerList>>,
+) {
+    if !servers.is_changed() { return }
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    for lan_list_id in &lan_lists {
+        let mut lan_list = commands.entity(lan_list_id);
+        lan_list.despawn_descendants();
+        let header = if servers.0.is_empty() { "Looking for games on the LAN..." } else { "Games on the LAN" };
+        let header = lan_list.commands().spawn(TextBundle::from_section(
+            header,
+            TextStyle {
+                font: font.clone(),
+                font_size: 24.0,
+                color: Color::BLACK,
+            }
+        ).with_style(Style { margin: UiRect::bottom(Val::Px(8.)), ..default() })).id();
+        lan_list.add_child(header);
+        for (i, server) in servers.0.iter().take(MAX_LAN_SERVERS_SHOWN).enumerate() {
+            spawn_button(&mut lan_list, &font, LanServerButton(i), &server.label());
+        }
+    }
+}
+
+pub fn despawn_profile_page(
+    mut commands: Commands,
+    profile_page_entity: Query<Entity, With<ProfilePage>>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    rebinding.0 = None;
+    if let Ok(profile_page_entity) = profile_page_entity.get_single() {
+        commands.entity(profile_page_entity).despawn_recursive();
+    }
+}
+
+/// name and color on the left, key binds on the right
+pub fn spawn_profile_page(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    profile: Res<Profile>,
+    key_binds: Res<KeyBinds>,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let profile_page_id = spawn_flex_column(&mut commands, ProfilePage);
+    spawn_title(&mut commands.entity(profile_page_id), &font, "Profile");
+    let profile_page_row_id = spawn_flex_row(&mut commands, ());
+    commands.entity(profile_page_id).add_child(profile_page_row_id);
+    let profile_page_left_id = spawn_flex_column(&mut commands, ());
+    commands.entity(profile_page_row_id).add_child(profile_page_left_id);
+    let mut profile_page_left = commands.entity(profile_page_left_id);
+    spawn_input(&mut profile_page_left, &font, ProfileNameButton, ProfileNameInput { name: String::new() }, "Name: ");
+    spawn_button(&mut profile_page_left, &font, ProfileColorButton, &format!("Color: {}", profile.color_name()));
+    spawn_button(&mut profile_page_left, &font, ProfileSaveButton, "Save");
+    spawn_button(&mut profile_page_left, &font, BackToMainMenu, "Back");
+    let profile_page_right_id = spawn_flex_column(&mut commands, ());
+    commands.entity(profile_page_row_id).add_child(profile_page_right_id);
+    let mut profile_page_right = commands.entity(profile_page_right_id);
+    for action in BindAction::ALL {
+        spawn_button(&mut profile_page_right, &font, RebindButton(action), &rebind_label(action, &key_binds, false));
+    }
+}
+
+/// what a rebind button says, waiting is true while it's listening for a key
+pub fn rebind_label(action: BindAction, key_binds: &KeyBinds, waiting: bool) -> String {
+    if waiting {
+        format!("{}: press a key", action.title())
+    } else {
+        format!("{}: {:?}", action.title(), action.key(key_binds))
+    }
+}
+
+pub fn despawn_replays_page(
+    mut commands: Commands,
+    replays_page_entity: Query<Entity, With<ReplaysPage>>,
+) {
+    if let Ok(replays_page_entity) = replays_page_entity.get_single() {
+        commands.entity(replays_page_entity).despawn_recursive();
+    }
+}
+
+/// what the last recorded match was, if there is one
+pub fn spawn_replays_page(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    files: Res<ReplayFiles>,
+    replay: Res<Replay>,
+    record_as_client: Res<RecordAsClient>,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let info = if files.paths.is_empty() { String::new() } else { replay.describe() };
+    let text = commands.spawn((TextBundle::from_section(
+        info,
+        TextStyle {
+            font: font.clone(),
+            font_size: 24.0,
+            color: Color::BLACK,
+        }
+    ).with_text_alignment(TextAlignment::Center), ReplayInfoText)).id();
+    let replays_page_id = spawn_flex_column(&mut commands, ReplaysPage);
+    let mut replays_page = commands.entity(replays_page_id);
+    spawn_title(&mut replays_page, &font, "Replays");
+    spawn_button(&mut replays_page, &font, ReplayFileButton, &files.label());
+    replays_page.add_child(text);
+    if !files.paths.is_empty() {
+        spawn_button(&mut replays_page, &font, ReplayWatchButton, "Watch");
+    }
+    spawn_button(&mut replays_page, &font, RecordAsClientButton, &format!("Record as client: {}", record_as_client.name()));
+    spawn_button(&mut replays_page, &font, BackToMainMenu, "Back");
 }
 
 pub fn despawn_controls_page(
@@ -438,7 +645,11 @@ pub fn spawn_controls_page(
     let text = commands.spawn(TextBundle::from_section(
         "Movement - WASD\n\
         Attack - Left Click\n\
+        Shield - Right Click (raise just before a hit to parry)\n\
+        Dash - Space\n\
+        Use Item - 1-4\n\
         Interact - E\n\
+        Ping - Middle Click (Shift for danger, Ctrl for a chest)\n\
         Quit Game - Esc",
         TextStyle {
             font: font.clone(),
@@ -457,7 +668,7 @@ pub fn despawn_in_game_ui(
     mut commands: Commands, 
     in_game_ui: Query<Entity, With<InGameUi>>
 ) {
-    if let Ok(in_game_ui) = in_game_ui.get_single() {
+    for in_game_ui in &in_game_ui {
         commands.entity(in_game_ui).despawn_recursive();
     }
 }
@@ -509,6 +720,313 @@ pub fn spawn_in_game_ui(
         },
         InGameUi));
 
+    // Overtime Banner
+    commands.spawn((TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(SCREEN_WIDTH / 2.0 - 200.0),
+            top: Val::Px(PADDING + 72.0),
+            ..Default::default()
+        },
+        text: Text::from_section(
+            "OVERTIME - stay inside the zone",
+            TextStyle {
+                font: font.clone(),
+                font_size: 32.0,
+                color: round::ZONE_COLOR,
+            }
+        ).with_alignment(TextAlignment::Center),
+        visibility: Visibility::Hidden,
+        ..Default::default()},
+        OvertimeBanner,
+        InGameUi));
+
+    // Host Lost Banner, the round carries on if the host comes back
+    let host_lost_id = commands.spawn((NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(SCREEN_WIDTH / 2.0 - 258.0),
+            top: Val::Px(PADDING + 170.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            padding: UiRect::all(Val::Px(8.)),
+            ..Default::default()
+        },
+        background_color: Color::rgba(0., 0., 0., 0.6).into(),
+        visibility: Visibility::Hidden,
+        ..Default::default()},
+        HostLostBanner,
+        InGameUi)).id();
+    let host_lost_text = commands.spawn(TextBundle::from_section(
+        "Lost contact with the host",
+        TextStyle {
+            font: font.clone(),
+            font_size: 32.0,
+            color: Color::WHITE,
+        }
+    ).with_text_alignment(TextAlignment::Center)
+        .with_style(Style { margin: UiRect::bottom(Val::Px(8.)), ..default() })).id();
+    let mut host_lost = commands.entity(host_lost_id);
+    host_lost.add_child(host_lost_text);
+    spawn_button(&mut host_lost, &font, LeaveMatchButton, "Leave");
+
+    // Respawn Countdown
+    commands.spawn((TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(SCREEN_WIDTH / 2.0 - 150.0),
+            top: Val::Px(PADDING + 110.0),
+            ..Default::default()
+        },
+        text: Text::from_section(
+            "",
+            TextStyle {
+                font: font.clone(),
+                font_size: 40.0,
+                color: Color::WHITE,
+            }
+        ).with_alignment(TextAlignment::Center),
+        ..Default::default()},
+        RespawnDisplay,
+        InGameUi));
+
+    // Spectator Controls
+    commands.spawn((TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(SCREEN_WIDTH / 2.0 - 250.0),
+            top: Val::Px(SCREEN_HEIGHT - PADDING - 64.0),
+            ..Default::default()
+        },
+        text: Text::from_section(
+            "",
+            TextStyle {
+                font: font.clone(),
+                font_size: 24.0,
+                color: Color::WHITE,
+            }
+        ),
+        ..Default::default()},
+        SpectatorDisplay,
+        InGameUi));
+
+    // Replay Controls, only filled in while watching one
+    commands.spawn((TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(SCREEN_WIDTH / 2.0 - 250.0),
+            top: Val::Px(PADDING + 150.0),
+            ..Default::default()
+        },
+        text: Text::from_section(
+            "",
+            TextStyle {
+                font: font.clone(),
+                font_size: 20.0,
+                color: Color::WHITE,
+            }
+        ),
+        ..Default::default()},
+        ReplayDisplay,
+        InGameUi));
+
+    // Kill Feed, under the minimap
+    commands.spawn((TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(PADDING),
+            top: Val::Px(PADDING + 300.0),
+            ..Default::default()
+        },
+        text: Text::from_section(
+            "",
+            TextStyle {
+                font: font.clone(),
+                font_size: 20.0,
+                color: Color::WHITE,
+            }
+        ).with_alignment(TextAlignment::Right),
+        ..Default::default()},
+        KillFeedDisplay,
+        InGameUi));
+
+    // Chat Log, growing up from the bottom right corner
+    commands.spawn((TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(PADDING),
+            bottom: Val::Px(PADDING + 32.0),
+            max_width: Val::Px(420.0),
+            ..Default::default()
+        },
+        text: Text::from_section(
+            "",
+            TextStyle {
+                font: font.clone(),
+                font_size: 20.0,
+                color: Color::WHITE,
+            }
+        ),
+        ..Default::default()},
+        ChatLogDisplay,
+        InGameUi));
+
+    // Chat Input, opened with enter
+    commands.spawn((NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(PADDING),
+            bottom: Val::Px(PADDING),
+            width: Val::Px(420.0),
+            padding: UiRect::horizontal(Val::Px(4.)),
+            ..default()
+        },
+        background_color: Color::rgba(0.15, 0.15, 0.15, 0.6).into(),
+        visibility: Visibility::Hidden,
+        ..default()
+    }, Switch::default(), ChatInputLine, InGameUi)).with_children(|parent| {
+        parent.spawn((
+            TextBundle::from_section(CHAT_PROMPT, TextStyle {
+                font: font.clone(),
+                font_size: 20.0,
+                color: Color::WHITE,
+            }),
+            ChatInput { text: String::new() },
+        ));
+    });
+
+    // Mode Display
+    commands.spawn((TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(PADDING),
+            top: Val::Px(PADDING),
+            ..Default::default()
+        },
+        text: Text::from_sections([
+            TextSection::new("", TextStyle {
+                font: font.clone(),
+                font_size: 32.0,
+                color: Color::WHITE,
+            }),
+            TextSection::new("", TextStyle {
+                font: font.clone(),
+                font_size: 24.0,
+                color: Color::WHITE,
+            }),
+        ]),
+        ..Default::default()},
+        ModeDisplay,
+        InGameUi));
+
+    // Cooldown Display
+    commands.spawn((NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(SCREEN_WIDTH / 2.0 - 150.0),
+            top: Val::Px(SCREEN_HEIGHT - PADDING - 32.0),
+            column_gap: Val::Px(40.),
+            flex_direction: FlexDirection::Row,
+            ..default()
+        },
+        ..default()
+    }, InGameUi)).with_children(|parent| {
+        for (name, ability) in [("Attack: ", AbilityType::Attack), ("Dash: ", AbilityType::Dash)] {
+            let style = TextStyle {
+                font: font.clone(),
+                font_size: 32.0,
+                color: Color::WHITE,
+            };
+            parent.spawn((
+                TextBundle::from_sections([
+                    TextSection::new(name, style.clone()),
+                    TextSection::new("Ready", style),
+                ]),
+                CooldownDisplayText(ability),
+            ));
+        }
+    });
+
+    // Hotbar Display
+    commands.spawn((NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(SCREEN_WIDTH / 2.0 - 140.0),
+            top: Val::Px(SCREEN_HEIGHT - PADDING - 96.0),
+            column_gap: Val::Px(8.),
+            flex_direction: FlexDirection::Row,
+            ..default()
+        },
+        ..default()
+    }, InGameUi)).with_children(|parent| {
+        for i in 0..HOTBAR_SLOTS {
+            let style = TextStyle {
+                font: font.clone(),
+                font_size: 16.0,
+                color: Color::WHITE,
+            };
+            parent.spawn((NodeBundle {
+                style: Style {
+                    width: Val::Px(64.),
+                    height: Val::Px(48.),
+                    padding: UiRect::all(Val::Px(4.)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.15, 0.15, 0.15, 0.6).into(),
+                ..default()
+            }, HotbarSlot(i))).with_children(|slot| {
+                slot.spawn((
+                    TextBundle::from_sections([
+                        TextSection::new(format!("{}\n", i + 1), style.clone()),
+                        TextSection::new("", style),
+                    ]),
+                    HotbarText(i),
+                ));
+            });
+        }
+    });
+
+    // Stamina Display
+    commands.spawn((NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(PADDING),
+            top: Val::Px(SCREEN_HEIGHT - PADDING - 88.0),
+            width: Val::Px(200.),
+            height: Val::Px(16.),
+            ..default()
+        },
+        background_color: Color::DARK_GRAY.into(),
+        ..default()
+    }, InGameUi)).with_children(|parent| {
+        parent.spawn((NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                ..default()
+            },
+            background_color: Color::YELLOW.into(),
+            ..default()
+        }, StaminaBar));
+    });
+
+    // Status Display
+    commands.spawn((TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(PADDING),
+            top: Val::Px(SCREEN_HEIGHT - PADDING - 120.0),
+            ..default()
+        },
+        text: Text::from_sections(STATUS_TYPES.map(|status| TextSection::new("", TextStyle {
+            font: font.clone(),
+            font_size: 24.0,
+            color: status.color(),
+        }))),
+        ..default()},
+        StatusDisplay,
+        InGameUi));
+
     // Powerup Display
      commands.spawn((ImageBundle {
         style: Style {
@@ -549,21 +1067,169 @@ pub fn spawn_in_game_ui(
     });
 }
 
+// Counts down to the round's end tick, the host decides when it's actually over
 pub fn update_time_remaining_system(
     mut game_timer: Query<(&mut GameTimer, &mut Text)>,
+    mut overtime_banner: Query<&mut Visibility, With<OvertimeBanner>>,
+    mut host_lost_banner: Query<&mut Visibility, (With<HostLostBanner>, Without<OvertimeBanner>)>,
     tick: Res<TickNum>,
-    mut app_state_next_state: ResMut<NextState<AppState>>,
+    round: Res<Round>,
 ) {
+    for mut vis in &mut overtime_banner {
+        *vis = if round.phase == MatchPhase::Overtime { Visibility::Inherited } else { Visibility::Hidden };
+    }
+    for mut vis in &mut host_lost_banner {
+        *vis = if round.host_lost { Visibility::Inherited } else { Visibility::Hidden };
+    }
     for (mut timer, mut text) in &mut game_timer {
-        if timer.remaining_time > 0.0 {
-            timer.remaining_time = ROUND_TIME - (tick.0 as f32 * TICKLEN_S);
-            let minutes = (timer.remaining_time / 60.0) as i32;
-            let seconds = (timer.remaining_time % 60.0) as i32;
-
-            text.sections[0].value = format!("{:02}:{:02}", minutes, seconds);
-        } else {
-            app_state_next_state.set(AppState::GameOver);
+        if round.phase == MatchPhase::Warmup {
+            let warmup = WARMUP_TICKS.saturating_sub(tick.0) as f32 * TICKLEN_S;
+            text.sections[0].value = format!("Warmup {}", warmup.ceil() as i32);
+            continue;
         }
+        timer.remaining_time = round.end_tick.saturating_sub(tick.0) as f32 * TICKLEN_S;
+        let minutes = (timer.remaining_time / 60.0) as i32;
+        let seconds = (timer.remaining_time % 60.0) as i32;
+
+        text.sections[0].value = format!("{:02}:{:02}", minutes, seconds);
+    }
+}
+
+/// puts the winner in the game over title and lists everyone's placement under it
+pub fn show_results(
+    game_mode: Res<GameMode>,
+    teams: Res<Teams>,
+    tie_break: Res<TieBreak>,
+    round: Res<Round>,
+    profiles: Res<PlayerProfiles>,
+    stats_query: Query<(&Player, &Stats), With<Joined>>,
+    mut title_query: Query<&mut Text, (With<LeaderboardUiTitle>, Without<ResultsDisplay>)>,
+    mut results_query: Query<&mut Text, With<ResultsDisplay>>,
+) {
+    let players: Vec<(u8, &Stats)> = stats_query.iter().map(|(pl, stats)| (pl.0, stats)).collect();
+    let entrants = round::entrants(&game_mode, &teams, &profiles, &players);
+    let standings = round::standings(&game_mode, *tie_break, round.overtime, entrants);
+    for mut text in &mut title_query {
+        text.sections[0].value = match standings.winner {
+            Some(i) => format!("{} Wins!", standings.placements[i].name),
+            None => "Draw".to_string(),
+        };
+        if let Some(team) = standings.winner.and_then(|i| standings.placements[i].team) {
+            text.sections[0].style.color = TEAM_COLORS[team as usize];
+        }
+    }
+    let mut placements = String::new();
+    for (entrant, place) in standings.placements.iter().zip(standings.places.iter()) {
+        placements.push_str(&format!("{}. {} - {} pts, {} kills, {} deaths\n", place, entrant.name, entrant.score, entrant.kills, entrant.deaths));
+    }
+    for mut text in &mut results_query {
+        text.sections[0].value = format!("Decided by: {}\n", standings.decided_by);
+        text.sections[1].value = placements.clone();
+    }
+}
+
+pub fn despawn_lobby_page(
+    mut commands: Commands,
+    lobby_page_entity: Query<Entity, With<LobbyPage>>
+) {
+    if let Ok(lobby_page_entity) = lobby_page_entity.get_single() {
+        commands.entity(lobby_page_entity).despawn_recursive();
+    }
+}
+
+pub fn spawn_lobby_page(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    is_host: Res<IsHost>,
+    player_id: Res<PlayerId>,
+    bot_difficulty: Res<BotDifficulty>,
+    powerup_config: Res<PowerUpConfig>,
+    mut kick_target: ResMut<KickTarget>,
+) {
+    kick_target.0 = None;
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let style = TextStyle {
+        font: font.clone(),
+        font_size: 28.0,
+        color: Color::BLACK,
+    };
+    let text = commands.spawn((TextBundle::from_sections([
+        TextSection::new("", style.clone()),
+        TextSection::new("", style.clone()),
+        TextSection::new("", TextStyle { color: Color::RED, ..style }),
+    ]).with_text_alignment(TextAlignment::Center), LobbyText)).id();
+    let lobby_page_id = spawn_flex_column(&mut commands, LobbyPage);
+    let mut lobby_page = commands.entity(lobby_page_id);
+    spawn_title(&mut lobby_page, &font, "Lobby");
+    lobby_page.add_child(text);
+    if player_id.0 != SPECTATOR_ID {
+        spawn_button(&mut lobby_page, &font, ReadyButton, "Ready");
+    }
+    if is_host.0 {
+        let bot_row_id = spawn_flex_row(&mut commands, ());
+        commands.entity(lobby_page_id).add_child(bot_row_id);
+        let mut bot_row = commands.entity(bot_row_id);
+        spawn_button(&mut bot_row, &font, AddBotButton, "Add Bot");
+        spawn_button(&mut bot_row, &font, RemoveBotButton, "Remove Bot");
+        spawn_button(&mut bot_row, &font, BotDifficultyButton, &format!("Bots: {}", bot_difficulty.0.name()));
+        let powerup_row_id = spawn_flex_row(&mut commands, ());
+        commands.entity(lobby_page_id).add_child(powerup_row_id);
+        let mut powerup_row = commands.entity(powerup_row_id);
+        spawn_button(&mut powerup_row, &font, PowerUpCapButton, &format!("Powerup Cap: {}", powerup_config.cap_name()));
+        spawn_button(&mut powerup_row, &font, PowerUpFalloffButton, &format!("Diminishing Returns: {}", powerup_config.falloff_name()));
+        spawn_button(&mut powerup_row, &font, PowerUpDropButton, &format!("Dropped on Death: {}", powerup_config.death_drop_name()));
+        let kick_row_id = spawn_flex_row(&mut commands, ());
+        commands.entity(lobby_page_id).add_child(kick_row_id);
+        let mut kick_row = commands.entity(kick_row_id);
+        spawn_button(&mut kick_row, &font, KickPickButton, "Kick: Nobody");
+        spawn_button(&mut kick_row, &font, KickButton, "Kick");
+    }
+    let mut lobby_page = commands.entity(lobby_page_id);
+    spawn_button(&mut lobby_page, &font, BackToMainMenu, "Leave");
+}
+
+/// shows the settings for the next round, who is ready, and the countdown once everyone is
+pub fn update_lobby_page(
+    lobby: Res<Lobby>,
+    game_mode: Res<GameMode>,
+    teams: Res<Teams>,
+    seed: Res<MapSeed>,
+    num_camps: Res<NumCamps>,
+    player_id: Res<PlayerId>,
+    tie_break: Res<TieBreak>,
+    max_players: Res<MaxPlayers>,
+    powerup_config: Res<PowerUpConfig>,
+    profiles: Res<PlayerProfiles>,
+    mut lobby_text: Query<&mut Text, With<LobbyText>>,
+) {
+    let mut entries: Vec<String> = Vec::new();
+    for id in 0..MAX_PLAYERS {
+        if !lobby.connected[id] { continue }
+        let you = if id as u8 == player_id.0 { " (you)" } else if lobby.bots[id] { " (bot)" } else { "" };
+        let team = if game_mode.teams() { format!(" - Team {}", teams.of(id as u8) + 1) } else { String::new() };
+        let ready = if lobby.ready[id] { "Ready" } else { "Not ready" };
+        entries.push(format!("{}{}{} - {}", profiles.name(id as u8), you, team, ready));
+    }
+    // two to a line once there are too many to fit on the page
+    let per_line = if entries.len() > 8 { 2 } else { 1 };
+    let mut players = format!("Players ({}/{})\n", entries.len(), max_players.0);
+    for line in entries.chunks(per_line) {
+        players.push_str(&line.join("     "));
+        players.push('\n');
+    }
+    if lobby.spectators > 0 {
+        let you = if player_id.0 == SPECTATOR_ID { " (including you)" } else { "" };
+        players.push_str(&format!("Spectators: {}{}\n", lobby.spectators, you));
+    }
+    for mut text in &mut lobby_text {
+        text.sections[0].value = format!("{}  |  tie-break: {}  |  {} camps  |  seed {}\npowerup cap: {}  |  diminishing returns: {}  |  dropped on death: {}\n\n",
+            game_mode.name(), tie_break.name(), num_camps.0, seed.0,
+            powerup_config.cap_name(), powerup_config.falloff_name(), powerup_config.death_drop_name());
+        text.sections[1].value = players.clone();
+        text.sections[2].value = match lobby.countdown {
+            Some(ticks) => format!("\nStarting in {}", (ticks as f32 * TICKLEN_S).ceil() as u32),
+            None => "\nWaiting for everyone to ready up".to_string(),
+        };
     }
 }
 
@@ -581,9 +1247,35 @@ pub fn spawn_connecting_page(
     asset_server: Res<AssetServer>
 ) {
     let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let text = commands.spawn((TextBundle::from_section(
+        "",
+        TextStyle {
+            font: font.clone(),
+            font_size: 24.0,
+            color: Color::BLACK,
+        }
+    ).with_text_alignment(TextAlignment::Center)
+        .with_style(Style { margin: UiRect::bottom(Val::Px(16.)), ..default() }), ConnectingText)).id();
     let connecting_id = spawn_flex_column(&mut commands, ConnectingPage);
     let mut connecting = commands.entity(connecting_id);
     spawn_title(&mut connecting, &font, "Connecting...");
+    connecting.add_child(text);
+    spawn_button(&mut connecting, &font, CancelConnectButton, "Cancel");
+}
+
+pub fn update_connecting_page(
+    attempt: Res<ConnectAttempt>,
+    mut connecting_text: Query<&mut Text, With<ConnectingText>>,
+) {
+    if !attempt.is_changed() { return }
+    let value = match attempt.host_addr {
+        Some(host_addr) => format!("Waiting for {} (try {} of {})", host_addr, attempt.tries, CONNECT_TRIES),
+        None if attempt.looking_up() => "Looking up the host".to_string(),
+        None => return,
+    };
+    for mut text in &mut connecting_text {
+        text.sections[0].value = value.clone();
+    }
 }
 
 pub fn spawn_leaderboard_ui(
@@ -591,7 +1283,8 @@ pub fn spawn_leaderboard_ui(
     asset_server: Res<AssetServer>,
 ) {
     let font = asset_server.load("fonts/FiraSans-Bold.ttf");
-    let measure_names = ["Player", "Score", "Enemy Kills", "Player Kills", "Camps Captured", "Deaths", "KD"];
+    let measure_names = LEADERBOARD_MEASURES;
+    let column_width = 100.0 / (measure_names.len() * 3) as f32;
     let leaderboard_entity = commands
         .spawn((NodeBundle {
             style: Style {
@@ -623,11 +1316,37 @@ pub fn spawn_leaderboard_ui(
             },
         ), LeaderboardUiTitle)).id();
     commands.entity(leaderboard_entity).push_children(&[title_entity]);
+    // team totals, left empty outside of team modes
+    let team_totals_entity = commands
+        .spawn((TextBundle::from_sections((0..NUM_TEAMS).map(|team| TextSection::new(
+            "",
+            TextStyle {
+                font: font.clone(),
+                font_size: 40.0,
+                color: TEAM_COLORS[team as usize],
+            },
+        ))), TeamTotalsDisplay)).id();
+    commands.entity(leaderboard_entity).push_children(&[team_totals_entity]);
+    // placements, filled in at game over
+    let results_entity = commands
+        .spawn((TextBundle::from_sections([
+            TextSection::new("", TextStyle {
+                font: font.clone(),
+                font_size: 32.0,
+                color: Color::YELLOW,
+            }),
+            TextSection::new("", TextStyle {
+                font: font.clone(),
+                font_size: 28.0,
+                color: Color::WHITE,
+            }),
+        ]).with_text_alignment(TextAlignment::Center), ResultsDisplay)).id();
+    commands.entity(leaderboard_entity).push_children(&[results_entity]);
     // field names
     let measures_entity = commands
         .spawn(NodeBundle {
             style: Style {
-                width: Val::Percent(70.0),
+                width: Val::Percent(90.0),
                 height: Val::Percent(20.0),
                 flex_direction: FlexDirection::Row,
                 align_items: AlignItems::Center,
@@ -655,15 +1374,15 @@ pub fn spawn_leaderboard_ui(
                     measure_names[i],
                     TextStyle {
                         font: font.clone(),
-                        font_size: 28.0,
+                        font_size: 20.0,
                         color: Color::WHITE,
                     },
                 )
                 .with_style(Style {
-                    width: Val::Percent(100.0 / 21.0),
+                    width: Val::Percent(column_width),
                     margin: UiRect {
-                        left: Val::Percent(100.0 / 21.0),
-                        right: Val::Percent(100.0 / 21.0),
+                        left: Val::Percent(column_width),
+                        right: Val::Percent(column_width),
                         top: Val::Px(0.),
                         bottom: Val::Px(0.),
                     },
@@ -672,37 +1391,76 @@ pub fn spawn_leaderboard_ui(
             }
         }).id();
     commands.entity(leaderboard_entity).push_children(&[measures_entity]);
-    // player stats
-    let player_icons = vec!["jordan_icon.png", "ian_icon.png", "sam_icon.png", "kevin_icon.png"];
-    for i in 0..4 {
+    // player stats, a row for each player in the match
+    let rows_entity = commands
+        .spawn((NodeBundle {
+            style: Style {
+                width: Val::Percent(90.0),
+                flex_grow: 1.0,
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        }, LeaderboardRows)).id();
+    commands.entity(leaderboard_entity).push_children(&[rows_entity]);
+}
+
+/// rebuilds the leaderboard's rows whenever someone joins, leaves or gets renamed, shrinking them to fit everyone
+pub fn sync_leaderboard_rows(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    players: Query<(&Player, &Stats)>,
+    profiles: Res<PlayerProfiles>,
+    rows_query: Query<(Entity, Option<&Children>), With<LeaderboardRows>>,
+    row_query: Query<&LeaderboardRow>,
+) {
+    let Ok((rows_entity, rows)) = rows_query.get_single() else { return };
+    let mut ids: Vec<u8> = players.iter().map(|(pl, _)| pl.0).collect();
+    ids.sort();
+    let mut shown: Vec<u8> = rows.map_or(Vec::new(), |rows| rows.iter().filter_map(|row| row_query.get(*row).ok()).map(|row| row.0).collect());
+    shown.sort();
+    if ids == shown && !profiles.is_changed() { return }
+    commands.entity(rows_entity).despawn_descendants();
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let player_icons = ["jordan_icon.png", "ian_icon.png", "sam_icon.png", "kevin_icon.png"];
+    let column_width = 100.0 / (LEADERBOARD_MEASURES.len() * 3) as f32;
+    let crowded = ids.len() > player_icons.len();
+    let row_height = 100.0 / ids.len().max(player_icons.len()) as f32;
+    let font_size = (24.0 * player_icons.len() as f32 / ids.len() as f32).clamp(12.0, 24.0);
+    let padding = if crowded { 4. } else { 20. };
+    for (id, stats) in ids.iter().filter_map(|id| players.iter().find(|(pl, _)| pl.0 == *id)).map(|(pl, stats)| (pl.0, stats)) {
         let player_stats_entity = commands
-        .spawn(NodeBundle {
+        .spawn((NodeBundle {
             style: Style {
-                width: Val::Percent(70.0),
-                height: Val::Percent(15.0),
+                width: Val::Percent(100.0),
+                height: Val::Percent(row_height),
                 flex_direction: FlexDirection::Row,
                 align_items: AlignItems::Center,
                 justify_content: JustifyContent::SpaceEvenly,
                 padding: UiRect {
                     left: Val::Px(20.),
                     right: Val::Px(20.),
-                    top: Val::Px(20.),
-                    bottom: Val::Px(20.),
+                    top: Val::Px(padding),
+                    bottom: Val::Px(padding),
                 },
                 ..default()
             },
             background_color: BackgroundColor(Color::rgba(0.5, 0.5, 0.5, 0.5)),
             ..default()
-        })
+        }, LeaderboardRow(id)))
         .with_children(|parent| {
-            parent.spawn((ImageBundle {
-                image: asset_server.load(player_icons[i]).into(),
+            // players past the icons reuse one with the same tint as their sprite, their name goes under it
+            parent.spawn((NodeBundle {
                 style: Style {
-                    width: Val::Percent(100.0 / 21.0),
+                    width: Val::Percent(column_width),
                     max_height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
                     margin: UiRect {
-                        left: Val::Percent(100.0 / 21.0),
-                        right: Val::Percent(100.0 / 21.0),
+                        left: Val::Percent(column_width),
+                        right: Val::Percent(column_width),
                         top: Val::Px(0.),
                         bottom: Val::Px(0.),
                     },
@@ -711,35 +1469,55 @@ pub fn spawn_leaderboard_ui(
                 ..default()
             },
             PlayerStatDisplay {
-                player_id: i as u8,
+                player_id: id,
                 stat_id: 0,
-            }));
-            for j in 1..7 {
+            })).with_children(|cell| {
+                cell.spawn(ImageBundle {
+                    image: asset_server.load(player_icons[player::sprite_column(id) as usize]).into(),
+                    background_color: BackgroundColor(player::sprite_tint(id)),
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        min_height: Val::Px(0.),
+                        flex_shrink: 1.0,
+                        ..default()
+                    },
+                    ..default()
+                });
+                cell.spawn(TextBundle::from_section(
+                    profiles.name(id),
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: font_size * 0.75,
+                        color: profiles.color(id),
+                    },
+                ).with_no_wrap());
+            });
+            for j in 1..LEADERBOARD_MEASURES.len() {
                 parent.spawn((TextBundle::from_section(
-                    j.to_string(),
+                    stat_value(stats, j as u8),
                     TextStyle {
                         font: font.clone(),
-                        font_size: 32.0,
+                        font_size,
                         color: Color::WHITE,
                     },
                 )
                 .with_style(Style {
-                    width: Val::Percent(100.0 / 21.0),
+                    width: Val::Percent(column_width),
                     margin: UiRect {
-                        left: Val::Percent(100.0 / 21.0),
-                        right: Val::Percent(100.0 / 21.0),
+                        left: Val::Percent(column_width),
+                        right: Val::Percent(column_width),
                         top: Val::Px(0.),
                         bottom: Val::Px(0.),
                     },
                     ..default()
                 }),
                 PlayerStatDisplay {
-                    player_id: i as u8,
+                    player_id: id,
                     stat_id: j as u8,
                 }));
             }
         }).id();
-        commands.entity(leaderboard_entity).push_children(&[player_stats_entity]);
+        commands.entity(rows_entity).push_children(&[player_stats_entity]);
     }
 }
 
@@ -761,6 +1539,7 @@ pub fn toggle_leaderboard(
     mut leaderboard_query: Query<(Entity, &mut Style), (With<LeaderboardUi>, Without<InGameUi>)>,
     mut leaderboard_title_query: Query<&mut Text, With<LeaderboardUiTitle>>,
     app_state_current_state: ResMut<State<AppState>>,
+    is_host: Res<IsHost>,
 ) {
     if input.just_pressed(KeyCode::Tab) || *app_state_current_state.get() == AppState::GameOver {
         for mut style in &mut in_game_ui_query.iter_mut() {
@@ -780,6 +1559,9 @@ pub fn toggle_leaderboard(
             for (leaderboard_id, _) in &mut leaderboard_query.iter_mut() {
                 let font = asset_server.load("fonts/FiraSans-Bold.ttf");
                 let mut leaderboard_entity = commands.entity(leaderboard_id);
+                if is_host.0 {
+                    spawn_button(&mut leaderboard_entity, &font, RematchButton, "Rematch");
+                }
                 spawn_button(&mut leaderboard_entity, &font, CreditsButton, "Credits");
             }
         }
@@ -798,6 +1580,22 @@ pub fn toggle_leaderboard(
     }
 }
 
+/// shows each team's combined score above the player rows
+pub fn update_team_totals(
+    game_mode: Res<GameMode>,
+    teams: Res<Teams>,
+    stats_query: Query<(&Player, &Stats), With<Joined>>,
+    mut totals_query: Query<&mut Text, With<TeamTotalsDisplay>>,
+) {
+    if !game_mode.teams() { return }
+    let players: Vec<(u8, &Stats)> = stats_query.iter().map(|(pl, stats)| (pl.0, stats)).collect();
+    for mut text in &mut totals_query {
+        for team in 0..NUM_TEAMS {
+            text.sections[team as usize].value = format!("Team {}: {}    ", team + 1, teams.score(&players, team));
+        }
+    }
+}
+
 pub fn update_leaderboard(
     stats_query: Query<(&Player, &Stats), (Without<PlayerStatDisplay>, Changed<Stats>)>,
     mut leaderboard_query: Query<(&mut Text, &PlayerStatDisplay), With<PlayerStatDisplay>>,
@@ -805,16 +1603,33 @@ pub fn update_leaderboard(
     for (player, stats) in &stats_query {
         for (mut text, identifier) in &mut leaderboard_query {
             if identifier.player_id == player.0 {
-                match identifier.stat_id {
-                    1 => text.sections[0].value = stats.score.to_string(),
-                    2 => text.sections[0].value = stats.enemies_killed.to_string(),
-                    3 => text.sections[0].value = stats.players_killed.to_string(),
-                    4 => text.sections[0].value = stats.camps_captured.to_string(),
-                    5 => text.sections[0].value = stats.deaths.to_string(),
-                    6 => text.sections[0].value = stats.kd_ratio.to_string(),
-                    _ => {}
+                if identifier.stat_id > 0 {
+                    text.sections[0].value = stat_value(stats, identifier.stat_id);
                 }
             }
         }
     }
+}
+
+/// what goes in a leaderboard column, stat_id indexes LEADERBOARD_MEASURES
+fn stat_value(stats: &Stats, stat_id: u8) -> String {
+    match stat_id {
+        1 => stats.score.to_string(),
+        2 => stats.enemies_killed.to_string(),
+        3 => stats.players_killed.to_string(),
+        4 => stats.camps_captured.to_string(),
+        5 => stats.deaths.to_string(),
+        6 => format!("{:.2}", stats.kd_ratio()),
+        7 => stats.assists.to_string(),
+        8 => stats.damage_dealt.to_string(),
+        9 => stats.damage_taken.to_string(),
+        10 => stats.powerups_collected.to_string(),
+        11 => stats.chests_opened.to_string(),
+        12 => stats.best_streak.to_string(),
+        13 => {
+            let seconds = (stats.ticks_alive as f32 * TICKLEN_S) as u32;
+            format!("{}:{:02}", seconds / 60, seconds % 60)
+        }
+        _ => String::new(),
+    }
 }
\ No newline at end of file
@@ -9,6 +9,7 @@ use layout::*;
 use interactions::*;
 use crate::menus::components::*;
 use crate::game::player::{spawn_players, remove_players};
+use crate::net::replay::load_replay;
 
 #[derive(Component)]
 struct InGameAmbientAudio;
@@ -21,6 +22,10 @@ pub struct NetworkAddresses {
     pub ip: String,
 }
 
+/// the player the host has picked to kick from the lobby
+#[derive(Resource, Default)]
+pub struct KickTarget(pub Option<u8>);
+
 pub struct MainMenuPlugin;
 
 impl Plugin for MainMenuPlugin{
@@ -28,34 +33,61 @@ impl Plugin for MainMenuPlugin{
         app
         .add_systems(OnEnter(AppState::MainMenu), spawn_main_menu)
         .add_systems(OnExit(AppState::MainMenu), despawn_main_menu)
-        .add_systems(Update, show_popup)
+        .add_systems(Update, (show_popup, spawn_error_popups))
+        .add_event::<PopupEvent>()
         .add_systems(OnEnter(AppState::Credits), spawn_credits_page)
         .add_systems(OnExit(AppState::Credits), despawn_credits_page)
         .add_systems(OnEnter(AppState::Connecting), spawn_connecting_page)
         .add_systems(OnExit(AppState::Connecting), despawn_connecting_page)
+        .add_systems(Update, (update_connecting_page, interact_with_button::<CancelConnectButtonType>)
+            .run_if(in_state(AppState::Connecting)))
+        .add_systems(OnEnter(AppState::Lobby), spawn_lobby_page)
+        .add_systems(OnExit(AppState::Lobby), despawn_lobby_page)
+        .add_systems(Update, update_lobby_page.run_if(in_state(AppState::Lobby)))
+        .add_systems(Update, interact_with_button::<BackButtonType>)
         .add_systems(OnEnter(AppState::Hosting), spawn_host_page)
         .add_systems(OnExit(AppState::Hosting), despawn_host_page)
         .add_systems(OnEnter(AppState::Joining), spawn_join_page)
         .add_systems(OnExit(AppState::Joining), despawn_join_page)
+        .add_systems(Update, (update_lan_server_list, lan_server_but).run_if(in_state(AppState::Joining)))
+        .add_systems(OnEnter(AppState::Profile), spawn_profile_page)
+        .add_systems(OnExit(AppState::Profile), despawn_profile_page)
+        .add_systems(Update, (
+            profile_name_but,
+            update_profile_name_input,
+            init_profile_name_input_system,
+            profile_color_but,
+            rebind_but,
+            capture_rebind,
+            save_profile_but,
+        ).run_if(in_state(AppState::Profile)))
+        .add_systems(OnEnter(AppState::Replays), spawn_replays_page.after(load_replay))
+        .add_systems(OnExit(AppState::Replays), despawn_replays_page)
+        .add_systems(Update, (replay_file_but, watch_replay_but, record_as_client_but).run_if(in_state(AppState::Replays)))
         .add_systems(OnEnter(AppState::Controls), spawn_controls_page)
         .add_systems(OnExit(AppState::Controls), despawn_controls_page)
         .add_systems(OnEnter(AppState::Game), spawn_in_game_ui)
         .add_systems(OnExit(AppState::Game), despawn_in_game_ui)
         .add_systems(OnEnter(AppState::Game), spawn_leaderboard_ui.after(spawn_players))
         .add_systems(OnEnter(AppState::GameOver), update_leaderboard.before(remove_players))
+        .add_systems(OnEnter(AppState::GameOver), update_team_totals.before(remove_players))
         .add_systems(OnEnter(AppState::GameOver), toggle_leaderboard.before(remove_players))
+        .add_systems(OnEnter(AppState::GameOver), show_results.after(toggle_leaderboard).before(remove_players))
         .add_systems(OnExit(AppState::GameOver), despawn_leaderboard_ui)
         .add_systems(OnEnter(AppState::Quitting), exit_system)
         .add_systems(OnEnter(AppState::Game), play_ambient)
         .add_systems(OnExit(AppState::Game), despawn_ambient_audio)
         .add_systems(Update, interact_with_button::<HostButtonType>.run_if(in_state(AppState::MainMenu)))
         .add_systems(Update, interact_with_button::<JoinButtonType>.run_if(in_state(AppState::MainMenu)))
+        .add_systems(Update, interact_with_button::<ProfileButtonType>.run_if(in_state(AppState::MainMenu)))
+        .add_systems(Update, interact_with_button::<ReplaysButtonType>.run_if(in_state(AppState::MainMenu)))
         .add_systems(Update, interact_with_button::<ControlsButtonType>.run_if(in_state(AppState::MainMenu)))
         .add_systems(Update, interact_with_button::<CreditsButtonType>.run_if(in_state(AppState::MainMenu)))
         .add_systems(Update, toggle_leaderboard.run_if(in_state(AppState::Game)))
+        .add_systems(Update, sync_leaderboard_rows.run_if(in_state(AppState::Game)))
         .add_systems(Update, update_leaderboard.run_if(in_state(AppState::Game)))
+        .add_systems(Update, update_team_totals.run_if(in_state(AppState::Game)))
         .add_systems(Update, interact_with_button::<CreditsButtonType>.run_if(in_state(AppState::GameOver)))
-        .add_systems(Update, interact_with_button::<BackButtonType>)
         .add_systems(Update, interact_with_button::<QuitButtonType>.run_if(in_state(AppState::Credits)))
         .add_systems(Update, update_host_input)
         .add_systems(Update, update_num_camps_input)
@@ -64,6 +96,8 @@ impl Plugin for MainMenuPlugin{
         .add_systems(Update, update_map_seed_input)
         .add_systems(Update, update_eid_percentage_input)
         .add_systems(Update, update_time_remaining_system.run_if(in_state(AppState::Game)))
+        .add_systems(Update, interact_with_button::<LeaveMatchButtonType>.run_if(in_state(AppState::Game)))
+        .add_systems(Update, update_chat_input.run_if(in_state(AppState::Game)))
         .add_systems(Update, save_host_input)
         .add_systems(Update, update_join_port_input)
         .add_systems(Update, update_join_host_port_input)
@@ -73,6 +107,21 @@ impl Plugin for MainMenuPlugin{
         .add_systems(Update, enemy_per_camp_but)
         .add_systems(Update, map_seed_but)
         .add_systems(Update, eid_percentage_but)
+        .add_systems(Update, game_mode_but)
+        .add_systems(Update, ready_but)
+        .add_systems(Update, rematch_but)
+        .add_systems(Update, team_but)
+        .add_systems(Update, spectate_but)
+        .add_systems(Update, friendly_fire_but)
+        .add_systems(Update, tie_break_but)
+        .add_systems(Update, max_players_but)
+        .add_systems(Update, death_penalty_but)
+        .add_systems(Update, add_bot_but.run_if(in_state(AppState::Lobby)))
+        .add_systems(Update, remove_bot_but.run_if(in_state(AppState::Lobby)))
+        .add_systems(Update, bot_difficulty_but)
+        .add_systems(Update, (powerup_cap_but, powerup_falloff_but, powerup_drop_but).run_if(in_state(AppState::Lobby)))
+        .add_systems(Update, (kick_pick_but, kick_but.after(kick_pick_but)).run_if(in_state(AppState::Lobby)))
+        .init_resource::<KickTarget>()
         .add_systems(Update, update_join_ip_input)
         .add_systems(Update, join_port_but)
         .add_systems(Update, host_port_but)
@@ -80,7 +129,6 @@ impl Plugin for MainMenuPlugin{
         .add_systems(Update, save_join_input)
         .add_systems(Update, init_host_port_input_system)
         .add_systems(Update, init_join_host_port_input_system)
-        .add_systems(Update, init_join_port_input_system)
         .add_systems(Update, init_join_ip_input_system)
         .add_systems(Update, init_num_camps_input_system)
         .add_systems(Update, init_num_chests_input_system)
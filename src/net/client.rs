@@ -1,45 +1,231 @@
 use std::net::*;
+use std::thread::{self, JoinHandle};
 use std::str::FromStr;
 use bevy::prelude::*;
 use crate::{menus, net};
 use crate::game::buffers::{DirBuffer, EventBuffer, PosBuffer};
-use crate::game::components::{Camp, CampEnemies, CampStatus, Health, ItemChest, PowerUp};
-use crate::game::map::MapSeed;
-use crate::game::player::{LocalPlayer, SetIdEvent};
+use crate::game::components::{Camp, CampEnemies, CampOwner, CampStatus, Health, ItemChest, PowerUp};
+use crate::game::map::{MapSeed, NumCamps};
+use crate::game::player::{LocalPlayer, Roster, SetIdEvent};
 use crate::game::PowerupAtlas;
+use crate::game::item::{spawn_item_pickup, ItemPickup};
+use crate::game::mode::{GameMode, TeamPreference, Teams};
+use crate::net::lobby::LobbyTickEvent;
+use crate::game::round::{MatchPhase, RoundTickEvent, TieBreak};
+use crate::game::spectate::JoinAsSpectator;
+use crate::game::profile::{PlayerProfiles, Profile};
+use crate::game::killfeed::KillFeed;
+use crate::game::chat::{ChatLog, ChatOutbox};
+use crate::game::ping::Pings;
+use crate::net::replay::ReplayRecorder;
+use crate::AppState;
+use crate::menus::layout::PopupEvent;
 use crate::net::{MAGIC_NUMBER, MAX_DATAGRAM_SIZE};
 use crate::net::packets::*;
 
+pub const CONNECT_TRIES: u8 = 5;
+const CONNECT_RETRY_S: f32 = 1.;
+
+/// The ConnectionRequest sent from the connecting page and how many times it's gone out
+#[derive(Resource, Default)]
+pub struct ConnectAttempt {
+    pub host_addr: Option<SocketAddr>,
+    /// resolved addresses not tried yet, each gets CONNECT_TRIES before the next one's tried
+    candidates: Vec<SocketAddr>,
+    /// looking up a hostname can block for seconds, so it runs on its own thread
+    lookup: Option<JoinHandle<Result<Vec<SocketAddr>, String>>>,
+    client_port: u16,
+    request: Vec<u8>,
+    pub tries: u8,
+    timer: Timer,
+}
+
+impl ConnectAttempt {
+    pub fn looking_up(&self) -> bool {
+        self.lookup.is_some()
+    }
+
+    /// binds a socket for the next address the host might be at and sends it the request. addresses the
+    /// machine can't reach (like IPv6 ones without an IPv6 route) are skipped
+    fn next_host(&mut self) -> Result<UdpSocket, String> {
+        let mut err = "Couldn't find the host".to_string();
+        while !self.candidates.is_empty() {
+            let host_addr = self.candidates.remove(0);
+            // listening on every interface works for hosts on loopback and on the LAN alike
+            let client_ip = if host_addr.is_ipv6() { IpAddr::from(Ipv6Addr::UNSPECIFIED) } else { IpAddr::from(Ipv4Addr::UNSPECIFIED) };
+            let client_addr = SocketAddr::new(client_ip, self.client_port);
+            match UdpSocket::bind(client_addr).and_then(|host| host.connect(host_addr).map(|_| host)) {
+                Ok(host) => {
+                    host.set_nonblocking(true).expect("can't set nonblocking");
+                    // a lost request gets sent again by retry_connect
+                    let _ = send_buf(self.request.as_slice(), &host, &host_addr);
+                    self.host_addr = Some(host_addr);
+                    self.tries = 1;
+                    self.timer = Timer::from_seconds(CONNECT_RETRY_S, TimerMode::Repeating);
+                    return Ok(host);
+                }
+                Err(e) => {
+                    err = if self.client_port == 0 { format!("Couldn't reach {}: {}", host_addr, e) } else { format!("Couldn't use port {}: {}", self.client_port, e) };
+                }
+            }
+        }
+        Err(err)
+    }
+}
+
+/// the port the client listens on, no port picked means any free one
+pub fn parse_client_port(port: &str) -> Result<u16, String> {
+    let port = port.trim();
+    if port.is_empty() { return Ok(0) }
+    u16::from_str(port).map_err(|_| format!("\"{}\" isn't a port", port))
+}
+
+/// works out where the host is from the join page. the address can be IPv4, IPv6 or a hostname the system
+/// can resolve, with the port on the end ("host:port", "[::1]:port") or in its own field. a hostname can
+/// resolve to several addresses, which come back in the order the system prefers
+pub fn resolve_host(host: &str, port: &str) -> Result<Vec<SocketAddr>, String> {
+    let host = host.trim();
+    if host.is_empty() { return Err("Enter the host's address".to_string()) }
+    if let Ok(addr) = SocketAddr::from_str(host) { return Ok(vec![addr]) }
+    let parse_port = |port: &str| -> Result<u16, String> {
+        let port = port.trim();
+        if port.is_empty() { return Err("Enter the host's port".to_string()) }
+        u16::from_str(port).map_err(|_| format!("\"{}\" isn't a port", port))
+    };
+    if let Ok(ip) = IpAddr::from_str(host.trim_start_matches('[').trim_end_matches(']')) {
+        return Ok(vec![SocketAddr::new(ip, parse_port(port)?)]);
+    }
+    let (name, port) = match host.rsplit_once(':') {
+        Some((name, host_port)) => (name, parse_port(host_port)?),
+        None => (host, parse_port(port)?),
+    };
+    let mut addrs: Vec<SocketAddr> = Vec::new();
+    for addr in (name, port).to_socket_addrs().into_iter().flatten() {
+        if !addrs.contains(&addr) { addrs.push(addr) }
+    }
+    if addrs.is_empty() { return Err(format!("Couldn't find a host called \"{}\"", name)) }
+    Ok(addrs)
+}
+
 pub fn connect(
     addresses: Res<menus::NetworkAddresses>,
-    mut sock: ResMut<net::Socket>
+    team_preference: Res<TeamPreference>,
+    join_as_spectator: Res<JoinAsSpectator>,
+    mut teams: ResMut<Teams>,
+    profile: Res<Profile>,
+    mut profiles: ResMut<PlayerProfiles>,
+    mut chat: (ResMut<ChatLog>, ResMut<ChatOutbox>),
+    mut attempt: ResMut<ConnectAttempt>,
+    mut popup_writer: EventWriter<PopupEvent>,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
 ) {
-    // I think if you communicate over LAN, you have to use local ip rather than loopback ip
-    let client_ip = Ipv4Addr::new(0,0,0,0);
-    let client_port = u16::from_str(&addresses.client_port).expect("bad client port");
-    let client_addr = SocketAddr::new(IpAddr::from(client_ip), client_port);
-    sock.0 = Some(UdpSocket::bind(client_addr).expect("client port in use"));
-    sock.0.as_mut().unwrap().set_nonblocking(true).expect("can't set nonblocking");
-    let host_ip = Ipv4Addr::from_str(&addresses.ip).expect("bad ip");
-    let host_port = u16::from_str(&addresses.host_port).expect("bad host port");
-    let host_addr = SocketAddr::new(IpAddr::from(host_ip), host_port);
-    let host = sock.0.as_mut().unwrap();
-    host.connect(host_addr).expect("can't connect to host");
-    send_empty_packet(PacketType::ConnectionRequest, host, &host_addr).expect("failed to request connection");
+    teams.clear();
+    profiles.clear();
+    // a new host numbers its chat from the start
+    chat.0.reset();
+    *chat.1 = ChatOutbox::default();
+    *attempt = ConnectAttempt::default();
+    let client_port = match parse_client_port(&addresses.client_port) {
+        Ok(client_port) => client_port,
+        Err(e) => {
+            popup_writer.send(PopupEvent(e));
+            app_state_next_state.set(AppState::Joining);
+            return;
+        }
+    };
+    let mut bytes: Vec<u8> = Vec::new();
+    ConnectionRequest {
+        team: team_preference.0,
+        spectate: join_as_spectator.0,
+        name: profile.name.clone(),
+        color: profile.color,
+    }.to_buf(&mut bytes);
+    let (host, host_port) = (addresses.ip.clone(), addresses.host_port.clone());
+    *attempt = ConnectAttempt {
+        lookup: Some(thread::spawn(move || resolve_host(&host, &host_port))),
+        client_port,
+        request: bytes,
+        ..default()
+    };
+}
+
+/// waits on the host lookup, then sends the ConnectionRequest again every so often, moving on to the
+/// host's next address if it never answers and giving up once they've all been tried
+pub fn retry_connect(
+    time: Res<Time>,
+    mut sock: ResMut<net::Socket>,
+    mut attempt: ResMut<ConnectAttempt>,
+    mut popup_writer: EventWriter<PopupEvent>,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
+) {
+    if attempt.lookup.as_ref().is_some_and(|lookup| lookup.is_finished()) {
+        let lookup = attempt.lookup.take().unwrap();
+        let host = lookup.join().unwrap_or(Err("Couldn't look up the host".to_string()))
+            .and_then(|addrs| {
+                attempt.candidates = addrs;
+                attempt.next_host()
+            });
+        match host {
+            Ok(host) => sock.0 = Some(host),
+            Err(e) => {
+                popup_writer.send(PopupEvent(e));
+                app_state_next_state.set(AppState::Joining);
+            }
+        }
+        return;
+    }
+    let (Some(_), Some(host_addr)) = (sock.0.as_ref(), attempt.host_addr) else { return };
+    if !attempt.timer.tick(time.delta()).just_finished() { return }
+    if attempt.tries >= CONNECT_TRIES {
+        if attempt.candidates.is_empty() {
+            popup_writer.send(PopupEvent(format!("{} didn't answer, is the game hosted there?", host_addr)));
+            app_state_next_state.set(AppState::Joining);
+            return;
+        }
+        match attempt.next_host() {
+            Ok(host) => sock.0 = Some(host),
+            Err(e) => {
+                popup_writer.send(PopupEvent(e));
+                app_state_next_state.set(AppState::Joining);
+            }
+        }
+        return;
+    }
+    attempt.tries += 1;
+    let _ = send_buf(attempt.request.as_slice(), sock.0.as_ref().unwrap(), &host_addr);
+}
+
+/// closes the socket once a send to the host fails, it's gone so there's nothing to tell it
+pub fn lose_host(
+    sock: &mut net::Socket,
+    e: std::io::Error,
+    popup_writer: &mut EventWriter<PopupEvent>,
+    next_state: &mut NextState<AppState>,
+    to: AppState,
+) {
+    sock.0 = None;
+    popup_writer.send(PopupEvent(format!("Lost the connection to the host: {}", e)));
+    next_state.set(to);
 }
 
 pub fn disconnect(mut sock: ResMut<net::Socket>) {
-    sock.0.take();
+    if let Some(sock) = sock.0.take() {
+        if let Ok(host_addr) = sock.peer_addr() {
+            let _ = send_empty_packet(PacketType::Disconnect, &sock, &host_addr);
+        }
+    }
 }
 
 pub fn fixed(
-    mut sock: ResMut<net::Socket>,
+    mut socket: ResMut<net::Socket>,
     tick: Res<net::TickNum>,
     players: Query<(&PosBuffer, &EventBuffer, &DirBuffer), With<LocalPlayer>>,
     ack: Res<net::Ack>,
+    mut popup_writer: EventWriter<PopupEvent>,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
 ) {
-    if sock.0.is_none() { return }
-    let sock = sock.0.as_mut().unwrap();
+    if socket.0.is_none() { return }
+    let sock = socket.0.as_mut().unwrap();
     let player = players.get_single();
     if player.is_err() { return }
     let (pb, eb, db) = player.unwrap();
@@ -61,29 +247,31 @@ pub fn fixed(
     };
     let mut bytes: Vec<u8> = Vec::new();
     packet.to_buf(&mut bytes);
-    send_buf(bytes.as_slice(), sock, &sock.peer_addr().expect("Sock not connected during fixed")).expect("ClientTick send failed");
+    // the round still needs cleaning up, so a lost host ends it rather than going straight back to the join page
+    if let Err(e) = sock.peer_addr().and_then(|host_addr| send_buf(bytes.as_slice(), sock, &host_addr)) {
+        lose_host(&mut socket, e, &mut popup_writer, &mut app_state_next_state, AppState::GameOver);
+    }
 }
 
 pub fn update(
-    mut commands: Commands,
     mut sock: ResMut<net::Socket>,
-    mut player_writer: EventWriter<PlayerTickEvent>,
-    mut enemy_writer: EventWriter<EnemyTickEvent>,
     mut id_writer: EventWriter<SetIdEvent>,
-    mut tick_num: ResMut<net::TickNum>,
-    mut seed: ResMut<MapSeed>,
-    powerup_atlas: Res<PowerupAtlas>,
-    mut powerups: Query<Entity, With<PowerUp>>,
-    mut camps: Query<(&Camp, &mut CampStatus, &mut CampEnemies)>,
-    mut chests: Query<(&ItemChest, &mut Health)>
-
+    // what a ConnectionResponse tells us about the match, there are too many params to take these one by one
+    mut settings: (ResMut<MapSeed>, ResMut<GameMode>, ResMut<TieBreak>, ResMut<NumCamps>),
+    mut lobby_writer: EventWriter<LobbyTickEvent>,
+    mut host_tick_writer: EventWriter<HostTickEvent>,
+    mut outbox: ResMut<ChatOutbox>,
+    mut recorder: ResMut<ReplayRecorder>,
+    mut popup_writer: EventWriter<PopupEvent>,
+    app_state: Res<State<AppState>>,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
 ) {
     if sock.0.is_none() { return }
     let sock = sock.0.as_mut().unwrap();
     loop {
         let mut buf = [0; MAX_DATAGRAM_SIZE];
         if sock.peek(&mut buf).is_err() { break }
-        sock.recv(&mut buf).unwrap();
+        let len = sock.recv(&mut buf).unwrap();
         let magic = u16::from_be_bytes(buf[0..2].try_into().unwrap());
         if magic != MAGIC_NUMBER { break; }
         let pt = u8::from_be_bytes(buf[2..3].try_into().unwrap());
@@ -96,7 +284,11 @@ pub fn update(
                 }
                 let packet = packet.unwrap();
                 println!("ConnectionResponse received");
+                let (seed, game_mode, tie_break, num_camps) = &mut settings;
                 seed.0 = packet.seed;
+                **game_mode = GameMode::from_u8(packet.mode).unwrap_or(GameMode::FreeForAll);
+                **tie_break = TieBreak::from_u8(packet.tie_break).unwrap_or_default();
+                num_camps.0 = packet.num_camps;
                 id_writer.send(SetIdEvent(packet.player_id));
             },
             pt if pt == PacketType::HostTick as u8 => {
@@ -105,64 +297,200 @@ pub fn update(
                     println!("Malformed HostTick Received!");
                     continue;
                 }
-                let packet = packet.unwrap();
-                for tick in packet.players {
-                    player_writer.send(PlayerTickEvent {
-                        seq_num: packet.seq_num,
-                        tick
-                    })
-                }
-                for tick in packet.enemies {
-                    enemy_writer.send(EnemyTickEvent {
-                        seq_num: packet.seq_num,
-                        tick
-                    })
-                }
-                for e in &mut powerups {
-                    commands.entity(e).despawn();
+                recorder.record(&buf[..len]);
+                host_tick_writer.send(HostTickEvent(packet.unwrap()));
+            },
+            pt if pt == PacketType::LobbyTick as u8 => {
+                let packet = LobbyTick::from_buf(&buf[3..]);
+                if packet.is_err() {
+                    println!("Malformed LobbyTick Received!");
+                    continue;
                 }
-                for (ptype, pos) in packet.powerups {
-                    commands.spawn((
-                        SpriteSheetBundle{
-                            texture_atlas: powerup_atlas.handle.clone(),
-                            sprite: TextureAtlasSprite {
-                                index: powerup_atlas.coord_to_index(0, ptype as i32),
-                                ..Default::default()
-                            },
-                            transform: Transform {
-                                translation: Vec3 { x: pos.x, y: pos.y, z: 0.0 },
-                                ..Default::default()
-                            },
-                            ..Default::default()
-                        },
-                        PowerUp(ptype),
-                        ));
+                lobby_writer.send(LobbyTickEvent(packet.unwrap()));
+            },
+            pt if pt == PacketType::ServerFull as u8 => {
+                if *app_state.get() == AppState::Connecting {
+                    popup_writer.send(PopupEvent("That game is full".to_string()));
+                    app_state_next_state.set(AppState::Joining);
                 }
-                for (camp_id, count) in packet.camps {
-                    for (camp, mut status, mut campcount) in camps.iter_mut() {
-                        if camp.0 == camp_id {
-                            status.0 = true;
-                            campcount.current_enemies = count;
-                        }
-                    }
+            },
+            pt if pt == PacketType::ChatAck as u8 => {
+                let packet = ChatAck::from_buf(&buf[3..]);
+                if packet.is_err() {
+                    println!("Malformed ChatAck Received!");
+                    continue;
                 }
-                for (net_ic, net_hp) in packet.chests {
-                    for (ic, mut hp) in &mut chests {
-                        if ic.id == net_ic {
-                            hp.current = net_hp;
-                        }
+                outbox.ack(packet.unwrap().seq);
+            },
+            pt if pt == PacketType::Disconnect as u8 => {
+                println!("Kicked by the host");
+                app_state_next_state.set(AppState::MainMenu);
+            },
+            _ => {
+                println!("Ignoring packet type {} from the host", pt);
+                continue;
+            }
+        }
+    }
+}
+
+/// applies a host tick, from the host or from a replay
+pub fn handle_host_ticks(
+    mut commands: Commands,
+    mut host_ticks: ResMut<Events<HostTickEvent>>,
+    mut player_writer: EventWriter<PlayerTickEvent>,
+    mut enemy_writer: EventWriter<EnemyTickEvent>,
+    mut round_writer: EventWriter<RoundTickEvent>,
+    mut tick_num: ResMut<net::TickNum>,
+    powerup_atlas: Res<PowerupAtlas>,
+    mut powerups: Query<Entity, With<PowerUp>>,
+    items: Query<Entity, With<ItemPickup>>,
+    mut camps: Query<(&Camp, &mut CampStatus, &mut CampEnemies, &mut CampOwner)>,
+    mut chests: Query<(&ItemChest, &mut Health)>,
+    // who a HostTick says is in the match and what they've been up to, there are too many params to take these one by one
+    mut players: (ResMut<Roster>, ResMut<PlayerProfiles>, ResMut<KillFeed>, ResMut<Pings>),
+    mut chat_log: ResMut<ChatLog>,
+    time: Res<Time>,
+) {
+    // drained rather than read so the packets can be taken apart without copying them
+    for HostTickEvent(packet) in host_ticks.drain() {
+        round_writer.send(RoundTickEvent {
+            phase: MatchPhase::from_u8(packet.phase).unwrap_or(MatchPhase::Live),
+            end_tick: packet.end_tick,
+        });
+        let (roster, profiles, kill_feed, pings) = &mut players;
+        if roster.0 != packet.roster {
+            roster.0 = packet.roster;
+        }
+        if profiles.0 != packet.profiles {
+            profiles.0 = packet.profiles;
+        }
+        if kill_feed.0 != packet.kills {
+            kill_feed.0 = packet.kills;
+        }
+        if pings.active != packet.pings {
+            pings.active = packet.pings;
+        }
+        for line in packet.chat {
+            chat_log.receive(line, time.elapsed_seconds());
+        }
+        for tick in packet.players {
+            player_writer.send(PlayerTickEvent {
+                seq_num: packet.seq_num,
+                tick
+            })
+        }
+        for tick in packet.enemies {
+            enemy_writer.send(EnemyTickEvent {
+                seq_num: packet.seq_num,
+                tick
+            })
+        }
+        for e in &mut powerups {
+            commands.entity(e).despawn();
+        }
+        for (ptype, pos) in packet.powerups {
+            commands.spawn((
+                SpriteSheetBundle{
+                    texture_atlas: powerup_atlas.handle.clone(),
+                    sprite: TextureAtlasSprite {
+                        index: powerup_atlas.coord_to_index(0, ptype as i32),
+                        ..Default::default()
+                    },
+                    transform: Transform {
+                        translation: Vec3 { x: pos.x, y: pos.y, z: 0.0 },
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                PowerUp(ptype),
+                ));
+        }
+        for e in &items {
+            commands.entity(e).despawn();
+        }
+        for (item, pos) in packet.items {
+            spawn_item_pickup(&mut commands, item, pos);
+        }
+        for camp_tick in packet.camps {
+            for (camp, mut status, mut campcount, mut control) in camps.iter_mut() {
+                if camp.0 == camp_tick.id {
+                    status.0 = camp_tick.active;
+                    campcount.current_enemies = camp_tick.enemies;
+                    if control.owner != camp_tick.owner {
+                        control.owner = camp_tick.owner;
                     }
                 }
-                if packet.seq_num > tick_num.0 {
-                    println!("re-syncing: changing tick from {} to {}", tick_num.0, packet.seq_num);
-                    tick_num.0 = packet.seq_num;
+            }
+        }
+        for (net_ic, net_hp) in packet.chests {
+            for (ic, mut hp) in &mut chests {
+                if ic.id == net_ic {
+                    hp.current = net_hp;
                 }
-            },
-            pt if pt == PacketType::ServerFull as u8 => {
-                println!("Server is full!");
-                // TODO stop trying to connect?
-            },
-            _ => panic!("Server sent some wacky packet that doesn't make sense")
+            }
+        }
+        if packet.seq_num > tick_num.0 {
+            println!("re-syncing: changing tick from {} to {}", tick_num.0, packet.seq_num);
+            tick_num.0 = packet.seq_num;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_host_takes_addresses_with_or_without_a_port() {
+        let v4: SocketAddr = "192.168.1.5:9000".parse().unwrap();
+        let v6: SocketAddr = "[::1]:9000".parse().unwrap();
+        assert_eq!(resolve_host("192.168.1.5:9000", ""), Ok(vec![v4]));
+        assert_eq!(resolve_host(" 192.168.1.5 ", "9000"), Ok(vec![v4]));
+        assert_eq!(resolve_host("[::1]:9000", "1234"), Ok(vec![v6]));
+        assert_eq!(resolve_host("[::1]", "9000"), Ok(vec![v6]));
+        assert_eq!(resolve_host("::1", "9000"), Ok(vec![v6]));
+    }
+
+    #[test]
+    fn resolve_host_explains_bad_input() {
+        assert_eq!(resolve_host("  ", "9000"), Err("Enter the host's address".to_string()));
+        assert_eq!(resolve_host("192.168.1.5", ""), Err("Enter the host's port".to_string()));
+        assert_eq!(resolve_host("192.168.1.5", "port"), Err("\"port\" isn't a port".to_string()));
+        assert_eq!(resolve_host("localhost:70000", ""), Err("\"70000\" isn't a port".to_string()));
+    }
+
+    #[test]
+    fn resolve_host_returns_every_address_for_a_name() {
+        let addrs = resolve_host("localhost", "9000").unwrap();
+        assert!(!addrs.is_empty());
+        assert!(addrs.iter().all(|addr| addr.port() == 9000 && addr.ip().is_loopback()));
+        assert!(addrs.iter().enumerate().all(|(i, addr)| !addrs[..i].contains(addr)));
+        assert_eq!(resolve_host("localhost:9000", "1234"), Ok(addrs));
+    }
+
+    #[test]
+    fn client_port_defaults_to_any() {
+        assert_eq!(parse_client_port(""), Ok(0));
+        assert_eq!(parse_client_port(" 9001 "), Ok(9001));
+        assert_eq!(parse_client_port("8086x"), Err("\"8086x\" isn't a port".to_string()));
+    }
+
+    #[test]
+    fn next_host_binds_a_free_port_and_reports_taken_ones() {
+        let host_addr: SocketAddr = "127.0.0.1:9".parse().unwrap();
+        let mut attempt = ConnectAttempt { candidates: vec![host_addr], ..default() };
+        let sock = attempt.next_host().unwrap();
+        assert_ne!(sock.local_addr().unwrap().port(), 0);
+        assert_eq!(attempt.host_addr, Some(host_addr));
+        assert_eq!(attempt.tries, 1);
+        assert_eq!(attempt.next_host().err(), Some("Couldn't find the host".to_string()));
+
+        let held = UdpSocket::bind("0.0.0.0:0").unwrap();
+        let taken = held.local_addr().unwrap().port();
+        let mut attempt = ConnectAttempt { candidates: vec![host_addr], client_port: taken, ..default() };
+        let err = attempt.next_host().err().unwrap();
+        assert!(err.starts_with(&format!("Couldn't use port {}:", taken)), "{}", err);
+        assert_eq!(attempt.host_addr, None);
+    }
+}
@@ -0,0 +1,217 @@
+use std::net::*;
+use bevy::prelude::*;
+use rand::Rng;
+use crate::AppState;
+use crate::game::bot::Bots;
+use crate::game::map::MapSeed;
+use crate::game::mode::GameMode;
+use crate::game::player::MaxPlayers;
+use crate::game::profile::PlayerProfiles;
+use crate::net::{self, is_host, MAGIC_NUMBER, MAX_DATAGRAM_SIZE, PROTOCOL_VERSION};
+use crate::net::host::Connections;
+use crate::net::packets::*;
+
+pub const DISCOVERY_PORT: u16 = 24835;
+pub const DISCOVERY_PORTS: u16 = 8; // hosts take the first free port from DISCOVERY_PORT on, so several can run on one machine
+const QUERY_INTERVAL_S: f64 = 1.;
+const SERVER_TIMEOUT_S: f64 = 4.; // servers that stop answering drop off the list
+const SENT_QUERIES: usize = 8; // queries remembered for timing replies
+
+/// The host's socket for answering discovery queries, open while hosting
+#[derive(Resource, Default)]
+pub struct DiscoveryResponder {
+    pub sock: Option<UdpSocket>,
+    pub host_id: u32,
+}
+
+/// The join page's socket for finding hosts, and when its recent queries went out
+#[derive(Resource, Default)]
+pub struct Discovery {
+    pub sock: Option<UdpSocket>,
+    next_query: u16,
+    last_query: f64,
+    sent: Vec<(u16, f64)>,
+}
+
+/// A host that answered a discovery query
+#[derive(Clone)]
+pub struct LanServer {
+    pub host_id: u32,
+    pub addr: SocketAddr, // the host's game port
+    pub name: String,
+    pub players: u8,
+    pub max_players: u8,
+    pub mode: GameMode,
+    pub seed: u64,
+    pub in_game: bool,
+    pub compatible: bool, // speaks the same protocol version
+    pub ping_ms: u32,
+    pub last_seen: f64,
+}
+
+impl LanServer {
+    /// what the server's button on the join page says
+    pub fn label(&self) -> String {
+        let state = if !self.compatible { " - different version" } else if self.in_game { " - in game" } else { "" };
+        format!("{} - {} - {}/{}\nseed {} - {} ms{}", self.name, self.mode.name(), self.players, self.max_players,
+            self.seed, self.ping_ms, state)
+    }
+}
+
+/// Hosts found by the join page, in the order they were found
+#[derive(Resource, Default)]
+pub struct LanServers(pub Vec<LanServer>);
+
+pub struct DiscoveryPlugin;
+
+impl Plugin for DiscoveryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Lobby), open_responder.run_if(is_host))
+            .add_systems(OnEnter(AppState::MainMenu), close_responder)
+            .add_systems(OnEnter(AppState::Credits), close_responder)
+            .add_systems(OnEnter(AppState::Hosting), close_responder)
+            .add_systems(Update, answer_queries.run_if(is_host))
+            .add_systems(OnEnter(AppState::Joining), start_discovery)
+            .add_systems(OnExit(AppState::Joining), stop_discovery)
+            .add_systems(Update, discover_servers.run_if(in_state(AppState::Joining)))
+            .init_resource::<DiscoveryResponder>()
+            .init_resource::<Discovery>()
+            .init_resource::<LanServers>();
+    }
+}
+
+/// listens on the first free discovery port, stays open through rematches
+pub fn open_responder(mut responder: ResMut<DiscoveryResponder>) {
+    if responder.sock.is_some() { return }
+    let sock = (DISCOVERY_PORT..DISCOVERY_PORT + DISCOVERY_PORTS)
+        .find_map(|port| UdpSocket::bind(SocketAddr::new(IpAddr::from(Ipv4Addr::UNSPECIFIED), port)).ok());
+    let Some(sock) = sock else {
+        println!("every discovery port is taken, this game won't show up on the LAN");
+        return;
+    };
+    sock.set_nonblocking(true).expect("can't set nonblocking");
+    responder.sock = Some(sock);
+    responder.host_id = rand::thread_rng().gen();
+}
+
+pub fn close_responder(mut responder: ResMut<DiscoveryResponder>) {
+    responder.sock = None;
+}
+
+/// tells whoever asked about the game being hosted
+pub fn answer_queries(
+    responder: Res<DiscoveryResponder>,
+    sock: Res<net::Socket>,
+    conns: Res<Connections>,
+    bots: Res<Bots>,
+    max_players: Res<MaxPlayers>,
+    game_mode: Res<GameMode>,
+    seed: Res<MapSeed>,
+    profiles: Res<PlayerProfiles>,
+    app_state: Res<State<AppState>>,
+) {
+    let Some(responder_sock) = responder.sock.as_ref() else { return };
+    let Some(port) = sock.0.as_ref().and_then(|sock| sock.local_addr().ok()).map(|addr| addr.port()) else { return };
+    loop {
+        let mut buf = [0; MAX_DATAGRAM_SIZE];
+        let Ok((len, origin)) = responder_sock.recv_from(&mut buf) else { break };
+        if len < 3 || u16::from_be_bytes(buf[0..2].try_into().unwrap()) != MAGIC_NUMBER { continue }
+        if buf[2] != PacketType::DiscoveryQuery as u8 { continue }
+        let Ok(query) = DiscoveryQuery::from_buf(&buf[3..len]) else { continue };
+        let players = 1 + conns.0.iter().flatten().count() + bots.0.iter().flatten().count();
+        let mut bytes: Vec<u8> = Vec::new();
+        DiscoveryReply {
+            version: PROTOCOL_VERSION,
+            query_id: query.query_id,
+            host_id: responder.host_id,
+            port,
+            players: players as u8,
+            max_players: max_players.0,
+            mode: *game_mode as u8,
+            in_game: *app_state.get() != AppState::Lobby,
+            seed: seed.0,
+            name: profiles.name(0),
+        }.to_buf(&mut bytes);
+        let _ = send_buf(bytes.as_slice(), responder_sock, &origin);
+    }
+}
+
+pub fn start_discovery(
+    mut discovery: ResMut<Discovery>,
+    mut servers: ResMut<LanServers>,
+) {
+    servers.0.clear();
+    *discovery = Discovery::default();
+    let sock = UdpSocket::bind(SocketAddr::new(IpAddr::from(Ipv4Addr::UNSPECIFIED), 0));
+    let Ok(sock) = sock else {
+        println!("couldn't open a socket to look for LAN games");
+        return;
+    };
+    sock.set_nonblocking(true).expect("can't set nonblocking");
+    if let Err(e) = sock.set_broadcast(true) {
+        println!("can't broadcast, only looking for games on this machine: {}", e);
+    }
+    discovery.sock = Some(sock);
+    discovery.last_query = f64::NEG_INFINITY;
+}
+
+pub fn stop_discovery(mut discovery: ResMut<Discovery>) {
+    discovery.sock = None;
+}
+
+/// asks the LAN and this machine for games every so often, and keeps the list of whoever answered
+pub fn discover_servers(
+    mut discovery: ResMut<Discovery>,
+    mut servers: ResMut<LanServers>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let discovery = &mut *discovery;
+    let Some(sock) = discovery.sock.as_ref() else { return };
+    if now - discovery.last_query >= QUERY_INTERVAL_S {
+        discovery.last_query = now;
+        let query_id = discovery.next_query;
+        discovery.next_query = query_id.wrapping_add(1);
+        discovery.sent.push((query_id, now));
+        if discovery.sent.len() > SENT_QUERIES {
+            discovery.sent.remove(0);
+        }
+        let mut bytes: Vec<u8> = Vec::new();
+        DiscoveryQuery { version: PROTOCOL_VERSION, query_id }.to_buf(&mut bytes);
+        for port in DISCOVERY_PORT..DISCOVERY_PORT + DISCOVERY_PORTS {
+            // broadcasts don't always come back to this machine, so ask loopback directly too
+            for ip in [Ipv4Addr::BROADCAST, Ipv4Addr::LOCALHOST] {
+                let _ = send_buf(bytes.as_slice(), sock, &SocketAddr::new(IpAddr::from(ip), port));
+            }
+        }
+    }
+    loop {
+        let mut buf = [0; MAX_DATAGRAM_SIZE];
+        let Ok((len, origin)) = sock.recv_from(&mut buf) else { break };
+        if len < 3 || u16::from_be_bytes(buf[0..2].try_into().unwrap()) != MAGIC_NUMBER { continue }
+        if buf[2] != PacketType::DiscoveryReply as u8 { continue }
+        let Ok(reply) = DiscoveryReply::from_buf(&buf[3..len]) else { continue };
+        let Some(sent) = discovery.sent.iter().find(|(id, _)| *id == reply.query_id).map(|(_, sent)| *sent) else { continue };
+        let server = LanServer {
+            host_id: reply.host_id,
+            addr: SocketAddr::new(origin.ip(), reply.port),
+            name: reply.name,
+            players: reply.players,
+            max_players: reply.max_players,
+            mode: GameMode::from_u8(reply.mode).unwrap_or(GameMode::FreeForAll),
+            seed: reply.seed,
+            in_game: reply.in_game,
+            compatible: reply.version == PROTOCOL_VERSION,
+            ping_ms: ((now - sent) * 1000.).round() as u32,
+            last_seen: now,
+        };
+        // the same host answers once per address it was asked on, keep whichever came first
+        match servers.0.iter_mut().find(|known| known.host_id == server.host_id) {
+            Some(known) => *known = LanServer { addr: known.addr, ..server },
+            None => servers.0.push(server),
+        }
+    }
+    if servers.0.iter().any(|server| now - server.last_seen > SERVER_TIMEOUT_S) {
+        servers.0.retain(|server| now - server.last_seen <= SERVER_TIMEOUT_S);
+    }
+}
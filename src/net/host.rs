@@ -1,137 +1,281 @@
+use std::io;
 use std::net::*;
 use std::str::FromStr;
 use bevy::math::Vec3Swizzles;
 use bevy::prelude::*;
 use crate::game::{Chests, player};
-use crate::{menus, net};
+use crate::game::player::{MaxPlayers, Roster};
+use crate::{menus, net, AppState};
+use crate::menus::layout::PopupEvent;
 use crate::game::buffers::{DirBuffer, EventBuffer, HpBuffer, PosBuffer};
 use crate::components::*;
 use crate::game::map::MapSeed;
+use crate::game::status::StatusEffects;
+use crate::game::item::{Inventory, ItemPickup, ItemType};
+use crate::game::mode::{GameMode, TeamPreference, Teams};
+use crate::net::lobby::Lobby;
+use crate::game::round::Round;
+use crate::game::respawn::Respawn;
+use crate::game::bot::Bots;
+use crate::game::spectate::SPECTATOR_ID;
+use crate::game::map::NumCamps;
+use crate::game::round::TieBreak;
+use crate::game::profile::{PlayerProfiles, Profile};
+use crate::game::killfeed::KillFeed;
+use crate::game::chat::{ChatEvent, ChatRelay, KickEvent};
+use crate::game::ping::{PingEvent, Pings, PingType};
 use crate::net::packets::*;
+use crate::net::replay::ReplayRecorder;
 use crate::net::{MAGIC_NUMBER, MAX_DATAGRAM_SIZE};
 
 pub const RENDER_DISTANCE: f32 = 640.;
+pub const MAX_SPECTATORS: usize = 8;
+const KICK_RESEND_TICKS: u8 = 10; // Disconnect isn't acked, so a kicked player is told this many times
+pub const PEER_TIMEOUT_S: f64 = 10.; // a connection that sends nothing for this long is dropped
 
 #[derive(Copy, Clone)]
 pub struct Connection {
     pub addr: SocketAddr,
     pub player_id: u8,
     pub rmt_num: u16,  // if the ack is older than TIMEOUT ticks ago, disconnect the player
-    pub ack: u32
+    pub ack: u32,
+    pub chat_seq: u16,  // the last ChatMessage taken from this connection
+    pub ping_seq: u16,  // the last PingRequest taken from this connection
+    pub heard_at: f64,  // when anything last came in from this connection, in seconds since startup
 }
 
 #[derive(Resource)]
 pub struct Connections(pub [Option<Connection>; player::MAX_PLAYERS-1]); // -1 because host not included
 
+/// Connections that watch without a player slot, their player_id is always SPECTATOR_ID
+#[derive(Resource)]
+pub struct Spectators(pub [Option<Connection>; MAX_SPECTATORS]);
+
+/// Kicked players and how many more times they'll be sent a Disconnect
+#[derive(Resource, Default)]
+pub struct Kicked(pub Vec<(SocketAddr, u8)>);
+
 pub fn startup(mut commands: Commands) {
     commands.insert_resource(Connections { 0: [None; player::MAX_PLAYERS-1] });
+    commands.insert_resource(Spectators([None; MAX_SPECTATORS]));
 }
 
 pub fn connect(addresses: Res<menus::NetworkAddresses>,
-    mut sock: ResMut<net::Socket>
+    mut sock: ResMut<net::Socket>,
+    team_preference: Res<TeamPreference>,
+    mut teams: ResMut<Teams>,
+    profile: Res<Profile>,
+    mut profiles: ResMut<PlayerProfiles>,
+    mut popup_writer: EventWriter<PopupEvent>,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
 ) {
-    let host_ip = Ipv4Addr::new(0,0,0,0);
-    let host_port = u16::from_str(&addresses.host_port).expect("bad host port");
-    let host_addr = SocketAddr::new(IpAddr::from(host_ip), host_port);
-    sock.0 = Some(UdpSocket::bind(host_addr).expect("host port in use"));
-    sock.0.as_mut().unwrap().set_nonblocking(true).expect("can't set nonblocking");
+    if sock.0.is_some() { return }  // still open from the last round on a rematch
+    let Ok(host_port) = u16::from_str(addresses.host_port.trim()) else {
+        popup_writer.send(PopupEvent(format!("\"{}\" isn't a port", addresses.host_port.trim())));
+        app_state_next_state.set(AppState::Hosting);
+        return;
+    };
+    teams.clear();
+    teams.assign(0, team_preference.0);
+    profiles.clear();
+    profiles.claim(0, &profile.name, profile.color);
+    // machines without IPv6 fall back to IPv4
+    let host_sock = bind_dual_stack(host_port)
+        .or_else(|_| UdpSocket::bind(SocketAddr::new(IpAddr::from(Ipv4Addr::UNSPECIFIED), host_port)));
+    let host_sock = match host_sock {
+        Ok(host_sock) => host_sock,
+        Err(e) => {
+            popup_writer.send(PopupEvent(format!("Couldn't host on port {}: {}", host_port, e)));
+            app_state_next_state.set(AppState::Hosting);
+            return;
+        }
+    };
+    host_sock.set_nonblocking(true).expect("can't set nonblocking");
+    sock.0 = Some(host_sock);
+}
+
+/// binds the IPv6 wildcard with IPV6_V6ONLY off so IPv4 clients get in too. std leaves that flag to the
+/// OS default, which is on for Windows, so it has to be set through socket2 before binding
+fn bind_dual_stack(port: u16) -> io::Result<UdpSocket> {
+    let sock = socket2::Socket::new(socket2::Domain::IPV6, socket2::Type::DGRAM, Some(socket2::Protocol::UDP))?;
+    sock.set_only_v6(false)?;
+    sock.bind(&SocketAddr::new(IpAddr::from(Ipv6Addr::UNSPECIFIED), port).into())?;
+    Ok(sock.into())
 }
 
 pub fn disconnect(
     mut sock: ResMut<net::Socket>,
-    mut conns: ResMut<Connections>
+    mut conns: ResMut<Connections>,
+    mut spectators: ResMut<Spectators>,
+    mut kicked: ResMut<Kicked>,
 ) {
     sock.0.take();
-    for conn in conns.0.iter_mut() {
+    kicked.0.clear();
+    for conn in conns.0.iter_mut().chain(spectators.0.iter_mut()) {
         conn.take();
     }
 }
 
 pub fn fixed(
     tick: Res<net::TickNum>,
-    conns: Res<Connections>,
+    peers: (Res<Connections>, Res<Spectators>),
     sock: Res<net::Socket>,
-    player_query: Query<(&PosBuffer, &HpBuffer, &Player, &EventBuffer, &DirBuffer, &Stats, &StoredPowerUps)>,
-    enemy_query: Query<(&PosBuffer, &Health, &Enemy, &EventBuffer)>,
+    mut recorder: ResMut<ReplayRecorder>,
+    player_query: Query<(&PosBuffer, &HpBuffer, &Player, &EventBuffer, &DirBuffer, &Stats, &StoredPowerUps, &Stamina, &StatusEffects, &Inventory, &Respawn)>,
+    enemy_query: Query<(&PosBuffer, &Health, &Enemy, &EventBuffer, &StatusEffects)>,
     powerups_query: Query<(&PowerUp, &Transform)>,
-    camp_query: Query<(&Camp, &CampStatus, &CampEnemies)>,
-    chests_query: Query<(&ItemChest, &Health)>
+    items_query: Query<(&ItemPickup, &Transform)>,
+    camp_query: Query<(&Camp, &CampStatus, &CampEnemies, &CampOwner)>,
+    chests_query: Query<(&ItemChest, &Health)>,
+    teams: Res<Teams>,
+    game_mode: Res<GameMode>,
+    round: Res<Round>,
+    roster: Res<Roster>,
+    profiles: Res<PlayerProfiles>,
+    // what's been going on lately, too many params to take these one by one
+    feeds: (Res<KillFeed>, Res<ChatRelay>, Res<Pings>),
 ) {
     if sock.0.is_none() { return }
+    let (conns, spectators) = peers;
+    let (kill_feed, relay, pings) = feeds;
     let sock = sock.0.as_ref().unwrap();
-    for conn in conns.0.iter() {
-        if conn.is_none() { continue; }
-        let conn = conn.unwrap();
-        for (lp_pb, _, lp_pl, _, _, _, _) in &player_query {
-            if conn.player_id == lp_pl.0 {
-                // for "this" player, add self, then calculate who is close and add them.
-                let lp_pos = lp_pb.0.get(tick.0);
-                let mut players: Vec<PlayerTick> = Vec::new();
-                for (pb, hb, pl, eb, db, stats, powerups) in &player_query {
-                    let pos = pb.0.get(tick.0);
-                    let hp = hb.0.get(tick.0);
-                    let dir = db.0.get(tick.0);
-                    let events = eb.0.get(tick.0);
-                    if pos.is_none() || hp.is_none() || dir.is_none() || events.is_none() { continue }
-                    let pos = pos.unwrap();
-                    let hp = hp.unwrap();
-                    let dir = dir.unwrap();
-                    let events = events.unwrap();
-                    players.push(PlayerTick {
-                        id: pl.0,
-                        pos,
-                        dir,
-                        hp,
-                        events,
-                        stats: stats.clone(),
-                        powerups: powerups.clone(),
-                    });
-                }
-                let mut enemies: Vec<EnemyTick> = Vec::new();
-                if lp_pos.is_some() {
-                    let lp_pos = lp_pos.unwrap();
-                    for (pb, hp, en, eb) in &enemy_query {
-                        let pos = pb.0.get(tick.0).unwrap();
-                        if pos.distance(lp_pos) < RENDER_DISTANCE {
-                            enemies.push(EnemyTick {
-                                id: en.0,
-                                pos,
-                                hp: hp.current,
-                                events: eb.0.get(tick.0).unwrap_or(0),
-                            });
-                        }
-                    }
-                }
-                let mut powerups: Vec<(PowerUpType, Vec2)> = Vec::new();
-                for (pu, pos) in &powerups_query {
-                    powerups.push((pu.0, pos.translation.xy()));
-                }
-                let mut camps = Vec::new();
-                for (camp, status, enemies) in &camp_query {
-                    if status.0 {
-                        camps.push((camp.0, enemies.current_enemies));
-                    }
-                }
-                let mut chests: Vec<(u8, u8)> = Vec::new();
-                for (id, hp) in &chests_query {
-                    chests.push((id.id, hp.current));
-                }
-                let packet = HostTick {
-                    seq_num: tick.0,
-                    rmt_num: conn.rmt_num,
-                    ack: conn.ack,
-                    enemies,
-                    players,
-                    powerups,
-                    camps,
-                    chests
-                };
-                let peer = conn.addr;
-                let mut bytes: Vec<u8> = Vec::new();
-                packet.to_buf(&mut bytes);
-                send_buf(bytes.as_slice(), &sock, &peer).expect(&*format!("failed to send HostTick to {:?}", peer));
-            }
+    // everything but the enemies is the same for everyone
+    let mut players: Vec<PlayerTick> = Vec::new();
+    for (pb, hb, pl, eb, db, stats, powerups, stamina, effects, inventory, respawn) in &player_query {
+        let pos = pb.0.get(tick.0);
+        let hp = hb.0.get(tick.0);
+        let dir = db.0.get(tick.0);
+        let events = eb.0.get(tick.0);
+        if pos.is_none() || hp.is_none() || dir.is_none() || events.is_none() { continue }
+        let pos = pos.unwrap();
+        let hp = hp.unwrap();
+        let dir = dir.unwrap();
+        let events = events.unwrap();
+        players.push(PlayerTick {
+            id: pl.0,
+            pos,
+            dir,
+            hp,
+            events,
+            stamina: stamina.current,
+            status: effects.pack(),
+            team: teams.of(pl.0),
+            respawn_wait: respawn.wait,
+            protection: respawn.protection,
+            stats: stats.clone(),
+            powerups: powerups.clone(),
+            inventory: inventory.clone(),
+        });
+    }
+    let mut powerups: Vec<(PowerUpType, Vec2)> = Vec::new();
+    for (pu, pos) in &powerups_query {
+        powerups.push((pu.0, pos.translation.xy()));
+    }
+    let mut items: Vec<(ItemType, Vec2)> = Vec::new();
+    for (item, pos) in &items_query {
+        items.push((item.0, pos.translation.xy()));
+    }
+    let mut camps = Vec::new();
+    for (camp, status, enemies, control) in &camp_query {
+        camps.push(CampTick {
+            id: camp.0,
+            active: status.0,
+            enemies: enemies.current_enemies,
+            owner: control.owner,
+        });
+    }
+    let mut chests: Vec<(u8, u8)> = Vec::new();
+    for (id, hp) in &chests_query {
+        chests.push((id.id, hp.current));
+    }
+    // enemies within RENDER_DISTANCE of near, or all of them if near is None
+    let enemies_near = |near: Option<Vec2>| -> Vec<EnemyTick> {
+        let mut enemies: Vec<EnemyTick> = Vec::new();
+        for (pb, hp, en, eb, effects) in &enemy_query {
+            let pos = pb.0.get(tick.0).unwrap();
+            if near.is_some_and(|near| pos.distance(near) >= RENDER_DISTANCE) { continue }
+            enemies.push(EnemyTick {
+                id: en.0,
+                pos,
+                hp: hp.current,
+                events: eb.0.get(tick.0).unwrap_or(0),
+                status: effects.pack(),
+            });
         }
+        enemies
+    };
+    let mut packet = HostTick {
+        seq_num: tick.0,
+        rmt_num: 0,
+        ack: 0,
+        phase: round.phase as u8,
+        end_tick: round.end_tick,
+        roster: roster.0,
+        profiles: profiles.0.clone(),
+        kills: kill_feed.0.clone(),
+        chat: Vec::new(),
+        pings: Vec::new(),
+        enemies: Vec::new(),
+        players,
+        powerups,
+        items,
+        camps,
+        chests
+    };
+    for conn in conns.0.iter().flatten() {
+        // players only hear about the enemies close to them
+        let lp_pos = player_query.iter()
+            .find(|(_, _, pl, ..)| pl.0 == conn.player_id)
+            .and_then(|(pb, ..)| *pb.0.get(tick.0));
+        packet.enemies = lp_pos.map_or(Vec::new(), |lp_pos| enemies_near(Some(lp_pos)));
+        packet.chat = relay.lines_for(conn.player_id);
+        packet.pings = pings.visible_to(conn.player_id, &game_mode, &teams);
+        send_host_tick(&mut packet, conn, sock);
+    }
+    for conn in spectators.0.iter().flatten() {
+        // spectators can look anywhere
+        packet.enemies = enemies_near(None);
+        packet.chat = relay.lines_for(SPECTATOR_ID);
+        packet.pings = pings.visible_to(SPECTATOR_ID, &game_mode, &teams);
+        send_host_tick(&mut packet, conn, sock);
+    }
+    // a replay sees what a spectator would
+    if recorder.recording() {
+        packet.rmt_num = 0;
+        packet.ack = 0;
+        packet.enemies = enemies_near(None);
+        packet.chat = relay.lines_for(SPECTATOR_ID);
+        packet.pings = pings.visible_to(SPECTATOR_ID, &game_mode, &teams);
+        if let Some(bytes) = host_tick_bytes(&mut packet) {
+            recorder.record(&bytes);
+        }
+    }
+}
+
+/// the packet as it goes out, chat is left off if it won't fit in a datagram
+/// and if it's still too big the tick isn't sent at all
+fn host_tick_bytes(packet: &mut HostTick) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    packet.to_buf(&mut bytes);
+    if bytes.len() > MAX_DATAGRAM_SIZE && !packet.chat.is_empty() {
+        packet.chat.clear();
+        bytes.clear();
+        packet.to_buf(&mut bytes);
+    }
+    if bytes.len() > MAX_DATAGRAM_SIZE {
+        println!("HostTick {} is {} bytes, too big to send", packet.seq_num, bytes.len());
+        return None;
+    }
+    Some(bytes)
+}
+
+fn send_host_tick(packet: &mut HostTick, conn: &Connection, sock: &UdpSocket) {
+    packet.rmt_num = conn.rmt_num;
+    packet.ack = conn.ack;
+    let peer = conn.addr;
+    let Some(bytes) = host_tick_bytes(packet) else { return };
+    if let Err(e) = send_buf(bytes.as_slice(), sock, &peer) {
+        println!("failed to send HostTick to {:?}: {}", peer, e);
     }
 }
 
@@ -149,21 +293,17 @@ fn get_id_of_origin(conns: &Connections, origin: &SocketAddr) -> Option<u8> {
     return None;
 }
 
-/// tries to add a connection using the given origin
+/// tries to add a connection using the given origin, handing out the lowest id
+/// below the player cap that no connection or bot has, so ids freed by leaving players get reused
 /// returns Some(player id) if successful, otherwise None
-fn add_connection(conns: &mut Connections, origin: &SocketAddr) -> Option<u8> {
-    let mut unused = [false; player::MAX_PLAYERS - 1];
-    for conn in &conns.0 {
-        if let Some(conn) = conn {
-            unused[conn.player_id as usize] = true;
-        }
-    }
-    let mut fresh_id: u8 = 1;
-    for (i, b) in unused.into_iter().enumerate() {
-        if i == fresh_id as usize && b {
-            fresh_id += 1;
-        }
+fn add_connection(conns: &mut Connections, bots: &Bots, max_players: u8, origin: &SocketAddr, now: f64) -> Option<u8> {
+    let mut taken = [false; player::MAX_PLAYERS];
+    taken[0] = true;
+    for conn in conns.0.iter().flatten() {
+        taken[conn.player_id as usize] = true;
     }
+    let cap = (max_players as usize).min(player::MAX_PLAYERS);
+    let fresh_id = (1..cap).find(|id| !taken[*id] && !bots.is_bot(*id as u8))? as u8;
     for conn in &mut conns.0 {
         if conn.is_none() {
             let _ = conn.insert(Connection {
@@ -171,6 +311,9 @@ fn add_connection(conns: &mut Connections, origin: &SocketAddr) -> Option<u8> {
                 player_id: fresh_id,
                 rmt_num: 0,
                 ack: 0,
+                chat_seq: 0,
+                ping_seq: 0,
+                heard_at: now,
             });
             return Some(fresh_id);
         }
@@ -178,41 +321,99 @@ fn add_connection(conns: &mut Connections, origin: &SocketAddr) -> Option<u8> {
     return None;
 }
 
+/// tries to add a spectator using the given origin
+/// returns true if successful, or if they were already watching
+fn add_spectator(spectators: &mut Spectators, origin: &SocketAddr, now: f64) -> bool {
+    if spectators.0.iter().flatten().any(|conn| conn.addr == *origin) { return true }
+    let Some(slot) = spectators.0.iter_mut().find(|conn| conn.is_none()) else { return false };
+    let _ = slot.insert(Connection {
+        addr: *origin,
+        player_id: SPECTATOR_ID,
+        rmt_num: 0,
+        ack: 0,
+        chat_seq: 0,
+        ping_seq: 0,
+        heard_at: now,
+    });
+    true
+}
+
 pub fn update(
     mut sock: ResMut<net::Socket>,
     mut conns: ResMut<Connections>,
+    mut spectators: ResMut<Spectators>,
     tick_num: Res<net::TickNum>,
     mut usercmd_writer: EventWriter<UserCmdEvent>,
-    seed: Res<MapSeed>
+    mut chat_writer: EventWriter<ChatEvent>,
+    mut ping_writer: EventWriter<PingEvent>,
+    settings: (Res<MapSeed>, Res<NumCamps>, Res<GameMode>, Res<TieBreak>),
+    mut teams: ResMut<Teams>,
+    mut lobby: ResMut<Lobby>,
+    bots: Res<Bots>,
+    max_players: Res<MaxPlayers>,
+    mut roster: ResMut<Roster>,
+    mut profiles: ResMut<PlayerProfiles>,
+    time: Res<Time>,
 ) {
     if sock.0.is_none() { return }
     let sock = sock.0.as_mut().unwrap();
+    let (seed, num_camps, game_mode, tie_break) = settings;
+    let now = time.elapsed_seconds_f64();
     loop {
         let mut buf = [0; MAX_DATAGRAM_SIZE];
         if sock.peek(&mut buf).is_err() { break }
         let (_, origin) = sock.recv_from(&mut buf).unwrap();
         let magic = u16::from_be_bytes(buf[0..2].try_into().unwrap());
         if magic != MAGIC_NUMBER { break; }
+        for conn in conns.0.iter_mut().chain(spectators.0.iter_mut()).flatten() {
+            if conn.addr == origin {
+                conn.heard_at = now;
+            }
+        }
         let pt = u8::from_be_bytes(buf[2..3].try_into().unwrap());
         match pt {
             pt if pt == PacketType::ConnectionRequest as u8 => {
                 println!("ConnectionRequest received");
-                let mut maybe_id = get_id_of_origin(&conns, &origin);
-                if maybe_id.is_some() {
-                    continue;  // this user is already in the server
-                }
-                maybe_id = add_connection(&mut conns, &origin);
-                if maybe_id.is_none() {
-                    send_empty_packet(PacketType::ServerFull, sock, &origin).expect("cant send server full");
+                let request = ConnectionRequest::from_buf(&buf[3..]);
+                if request.is_err() {
+                    println!("Malformed ConnectionRequest Received!");
+                    continue;
                 }
-                let player_id = maybe_id.unwrap();
+                let request = request.unwrap();
+                let player_id = if request.spectate {
+                    if !add_spectator(&mut spectators, &origin, now) {
+                        if let Err(e) = send_empty_packet(PacketType::ServerFull, sock, &origin) {
+                            println!("can't send server full to {:?}: {}", origin, e);
+                        }
+                        continue;
+                    }
+                    SPECTATOR_ID
+                } else if let Some(player_id) = get_id_of_origin(&conns, &origin) {
+                    player_id  // already in, the response must have been lost so send it again
+                } else {
+                    let Some(player_id) = add_connection(&mut conns, &bots, max_players.0, &origin, now) else {
+                        if let Err(e) = send_empty_packet(PacketType::ServerFull, sock, &origin) {
+                            println!("can't send server full to {:?}: {}", origin, e);
+                        }
+                        continue;
+                    };
+                    teams.assign(player_id, request.team);
+                    profiles.claim(player_id, &request.name, request.color);
+                    roster.0[player_id as usize] = true;
+                    player_id
+                };
                 let packet = ConnectionResponse {
                     player_id,
-                    seed: seed.0
+                    seed: seed.0,
+                    mode: *game_mode as u8,
+                    tie_break: *tie_break as u8,
+                    num_camps: num_camps.0,
                 };
                 let mut bytes: Vec<u8> = Vec::new();
                 packet.to_buf(&mut bytes);
-                send_buf(bytes.as_slice(), sock, &origin).expect("Can't send connection response");
+                if let Err(e) = send_buf(bytes.as_slice(), sock, &origin) {
+                    println!("can't send connection response to {:?}: {}", origin, e);
+                }
             },
             pt if pt == PacketType::ClientTick as u8 => {
                 let packet = ClientTick::from_buf(&buf[3..]);
@@ -226,7 +427,7 @@ pub fn update(
                     continue;  // ignore packets from non connected clients
                 }
                 let id = maybe_id.unwrap();
-                if packet.seq_num < tick_num.0 - net::DELAY {
+                if packet.seq_num < tick_num.0.saturating_sub(net::DELAY) {
                     // TODO deal with packet misses
                     println!("packet late, local is {} remote is {}", tick_num.0, packet.seq_num);
                     continue
@@ -238,19 +439,198 @@ pub fn update(
                     tick: packet.tick
                 });
             },
+            pt if pt == PacketType::LobbyReady as u8 => {
+                let packet = LobbyReady::from_buf(&buf[3..]);
+                if packet.is_err() {
+                    println!("Malformed LobbyReady Received!");
+                    continue;
+                }
+                let maybe_id = get_id_of_origin(&conns, &origin);
+                if maybe_id.is_none() {
+                    continue;  // ignore packets from non connected clients
+                }
+                lobby.ready[maybe_id.unwrap() as usize] = packet.unwrap().ready;
+            },
             pt if pt == PacketType::Disconnect as u8 => {
-                // TODO make player dead
                 println!("disconnect received");
-                for conn in &mut conns.0 {
+                for conn in conns.0.iter_mut().chain(spectators.0.iter_mut()) {
                     if conn.is_some() {
                         let s = conn.unwrap().addr;
                         if s == origin {
-                            conn.take();
+                            let id = conn.take().unwrap().player_id;
+                            free_id(id, &mut roster, &mut teams, &mut profiles, &mut lobby);
                         }
                     }
                 }
             }
-            _ => panic!("Bad packet sent to host")
+            pt if pt == PacketType::ChatMessage as u8 => {
+                let packet = ChatMessage::from_buf(&buf[3..]);
+                if packet.is_err() {
+                    println!("Malformed ChatMessage Received!");
+                    continue;
+                }
+                let packet = packet.unwrap();
+                // spectators can't chat, so only player connections are looked at
+                let Some(conn) = conns.0.iter_mut().flatten().find(|conn| conn.addr == origin) else { continue };
+                // the client resends until acked, so only the next one in line is new
+                if packet.seq == conn.chat_seq.wrapping_add(1) {
+                    conn.chat_seq = packet.seq;
+                    chat_writer.send(ChatEvent { sender: conn.player_id, text: packet.text });
+                }
+                let mut bytes: Vec<u8> = Vec::new();
+                ChatAck { seq: conn.chat_seq }.to_buf(&mut bytes);
+                if let Err(e) = send_buf(bytes.as_slice(), sock, &origin) {
+                    println!("can't send chat ack to {:?}: {}", origin, e);
+                }
+            }
+            pt if pt == PacketType::PingRequest as u8 => {
+                let packet = PingRequest::from_buf(&buf[3..]);
+                if packet.is_err() {
+                    println!("Malformed PingRequest Received!");
+                    continue;
+                }
+                let packet = packet.unwrap();
+                let Some(conn) = conns.0.iter_mut().flatten().find(|conn| conn.addr == origin) else { continue };
+                // the client sends each ping a few times, only a newer one counts
+                let newer = packet.seq.wrapping_sub(conn.ping_seq);
+                if newer == 0 || newer > u16::MAX / 2 { continue }
+                conn.ping_seq = packet.seq;
+                let Some(kind) = PingType::from_u8(packet.kind) else { continue };
+                ping_writer.send(PingEvent { sender: conn.player_id, kind, pos: packet.pos });
+            }
+            // discovery queries sweep nearby ports, and anything else could be lying around on the network
+            _ => {
+                println!("Ignoring packet type {} from {:?}", pt, origin);
+                continue;
+            }
+        }
+    }
+}
+
+/// frees a player id up for whoever joins next
+fn free_id(id: u8, roster: &mut Roster, teams: &mut Teams, profiles: &mut PlayerProfiles, lobby: &mut Lobby) {
+    if id == SPECTATOR_ID { return }
+    roster.0[id as usize] = false;
+    teams.members[id as usize] = None;
+    profiles.0[id as usize] = None;
+    lobby.ready[id as usize] = false;
+}
+
+/// drops a kicked player's connection, resend_kicks tells them they're out
+pub fn kick_players(
+    mut kick_reader: EventReader<KickEvent>,
+    mut conns: ResMut<Connections>,
+    mut kicked: ResMut<Kicked>,
+    mut roster: ResMut<Roster>,
+    mut teams: ResMut<Teams>,
+    mut profiles: ResMut<PlayerProfiles>,
+    mut lobby: ResMut<Lobby>,
+) {
+    for ev in kick_reader.iter() {
+        let Some(conn) = conns.0.iter_mut().find(|conn| conn.is_some_and(|conn| conn.player_id == ev.0)) else { continue };
+        let conn = conn.take().unwrap();
+        kicked.0.push((conn.addr, KICK_RESEND_TICKS));
+        free_id(conn.player_id, &mut roster, &mut teams, &mut profiles, &mut lobby);
+    }
+}
+
+/// starts everyone's silence over, the results screen doesn't send anything so time spent on it doesn't count
+pub fn hear_everyone(
+    mut conns: ResMut<Connections>,
+    mut spectators: ResMut<Spectators>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds_f64();
+    for conn in conns.0.iter_mut().chain(spectators.0.iter_mut()).flatten() {
+        conn.heard_at = now;
+    }
+}
+
+/// drops connections that crashed or lost their network, so they don't hold up the lobby or keep their id.
+/// players send something every tick in the lobby and the round, spectators only in the lobby
+pub fn drop_silent(
+    mut conns: ResMut<Connections>,
+    mut spectators: ResMut<Spectators>,
+    time: Res<Time>,
+    app_state: Res<State<AppState>>,
+    mut roster: ResMut<Roster>,
+    mut teams: ResMut<Teams>,
+    mut profiles: ResMut<PlayerProfiles>,
+    mut lobby: ResMut<Lobby>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let watching = *app_state.get() == AppState::Lobby;
+    let spectators = spectators.0.iter_mut().filter(|_| watching);
+    for conn in conns.0.iter_mut().chain(spectators) {
+        let Some(silent) = conn.filter(|conn| now - conn.heard_at > PEER_TIMEOUT_S) else { continue };
+        println!("haven't heard from {:?} in {} seconds, dropping them", silent.addr, PEER_TIMEOUT_S);
+        conn.take();
+        free_id(silent.player_id, &mut roster, &mut teams, &mut profiles, &mut lobby);
+    }
+}
+
+/// sends every kicked player a Disconnect each tick for a while, in case some get dropped
+pub fn resend_kicks(
+    sock: Res<net::Socket>,
+    mut kicked: ResMut<Kicked>,
+) {
+    let Some(sock) = sock.0.as_ref() else { return };
+    for (addr, left) in kicked.0.iter_mut() {
+        if let Err(e) = send_empty_packet(PacketType::Disconnect, sock, addr) {
+            println!("can't send disconnect to {:?}: {}", addr, e);
+        }
+        *left -= 1;
+    }
+    kicked.0.retain(|(_, left)| *left > 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn spectators_fill_slots_once_each() {
+        let mut spectators = Spectators([None; MAX_SPECTATORS]);
+        assert!(add_spectator(&mut spectators, &addr(1), 0.));
+        assert!(add_spectator(&mut spectators, &addr(1), 5.));
+        assert_eq!(spectators.0.iter().flatten().count(), 1);
+        assert_eq!(spectators.0[0].unwrap().player_id, SPECTATOR_ID);
+        assert_eq!(spectators.0[0].unwrap().heard_at, 0.);
+        for port in 2..=MAX_SPECTATORS as u16 {
+            assert!(add_spectator(&mut spectators, &addr(port), 0.));
+        }
+        assert!(!add_spectator(&mut spectators, &addr(100), 0.));
+        spectators.0[3] = None;
+        assert!(add_spectator(&mut spectators, &addr(100), 0.));
+        assert_eq!(spectators.0[3].unwrap().addr, addr(100));
+    }
+
+    #[test]
+    fn connections_get_the_lowest_free_id_under_the_cap() {
+        let mut conns = Connections([None; player::MAX_PLAYERS - 1]);
+        let mut bots = Bots::default();
+        bots.0[2] = Some(Default::default());
+        assert_eq!(add_connection(&mut conns, &bots, 4, &addr(1), 0.), Some(1));
+        assert_eq!(add_connection(&mut conns, &bots, 4, &addr(2), 0.), Some(3));
+        assert_eq!(add_connection(&mut conns, &bots, 4, &addr(3), 0.), None);
+        assert_eq!(get_id_of_origin(&conns, &addr(2)), Some(3));
+        // a leaving player's id goes to the next one in
+        conns.0[0] = None;
+        assert_eq!(add_connection(&mut conns, &bots, 4, &addr(4), 0.), Some(1));
+        assert_eq!(get_id_of_origin(&conns, &addr(1)), None);
+    }
+
+    #[test]
+    fn connections_fill_every_slot_at_the_largest_cap() {
+        let mut conns = Connections([None; player::MAX_PLAYERS - 1]);
+        let bots = Bots::default();
+        for port in 1..player::MAX_PLAYERS as u16 {
+            assert_eq!(add_connection(&mut conns, &bots, player::MAX_PLAYERS as u8, &addr(port), 0.), Some(port as u8));
         }
+        assert_eq!(add_connection(&mut conns, &bots, u8::MAX, &addr(100), 0.), None);
     }
 }
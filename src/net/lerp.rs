@@ -8,21 +8,25 @@ use crate::game::map::WorldMap;
 use crate::game::movement;
 use crate::game::player::LocalPlayer;
 use crate::net;
+use crate::net::replay::Replay;
 
 const COLLISION_SHOVE_DIST: f32 = 4.0;
 
 pub fn lerp_pos(
     tick_time: Res<FixedTime>,
     tick: Res<net::TickNum>,
+    replay: Res<Replay>,
     mut query: Query<(&mut Transform, &PosBuffer), Without<LocalPlayer>>,
 ) {
+    // a paused replay holds still on the latest tick instead of going back and forth between the last two
+    let paused = replay.active && replay.paused;
     for (mut tf, bp) in &mut query {
         let next_state = bp.0.get(tick.0.saturating_sub(net::DELAY));
         let prev_state = bp.0.get(tick.0.saturating_sub(net::DELAY + 1));
         if next_state.is_none() || prev_state.is_none() { return }
         let next_state = next_state.unwrap();
         let prev_state = prev_state.unwrap();
-        let percent: f32 = tick_time.accumulated().as_secs_f32() / tick_time.period.as_secs_f32();
+        let percent: f32 = if paused { 1. } else { tick_time.accumulated().as_secs_f32() / tick_time.period.as_secs_f32() };
         let new_state = prev_state.lerp(next_state, percent);
         tf.translation.x = new_state.x;
         tf.translation.y = new_state.y;
@@ -0,0 +1,184 @@
+use bevy::prelude::*;
+use crate::AppState;
+use crate::net::{self, client};
+use crate::menus::layout::PopupEvent;
+use crate::net::host::{Connections, Spectators};
+use crate::net::packets::*;
+use crate::game::map::{MapSeed, NumCamps};
+use crate::game::mode::GameMode;
+use crate::game::round::TieBreak;
+use crate::game::bot::Bots;
+use crate::game::player::{MaxPlayers, MAX_PLAYERS};
+use crate::game::profile::PlayerProfiles;
+use crate::game::powerup::PowerUpConfig;
+
+pub const COUNTDOWN_TICKS: u8 = 30; // 3 seconds once everyone is ready
+
+/// Who is waiting for the round to start, the host owns this and clients mirror it
+#[derive(Resource, Default)]
+pub struct Lobby {
+    pub connected: [bool; MAX_PLAYERS],
+    pub ready: [bool; MAX_PLAYERS],
+    pub bots: [bool; MAX_PLAYERS],
+    pub spectators: u8,
+    pub local_ready: bool,
+    pub countdown: Option<u8>, // ticks until the round starts, None until everyone is ready
+}
+
+impl Lobby {
+    pub fn all_ready(&self) -> bool {
+        self.connected.iter().zip(self.ready.iter()).all(|(connected, ready)| !connected || *ready)
+    }
+
+    pub fn reset(&mut self) {
+        self.ready = [false; MAX_PLAYERS];
+        self.local_ready = false;
+        self.countdown = None;
+    }
+}
+
+/// sent by network module to disperse lobby information from the host
+#[derive(Event)]
+pub struct LobbyTickEvent(pub LobbyTick);
+
+pub fn reset_lobby(mut lobby: ResMut<Lobby>) {
+    lobby.reset();
+}
+
+/// tracks who is connected and ready, counts down once everyone is, and tells clients about it
+pub fn host_fixed(
+    conns: Res<Connections>,
+    spectators: Res<Spectators>,
+    sock: Res<net::Socket>,
+    mut lobby: ResMut<Lobby>,
+    seed: Res<MapSeed>,
+    num_camps: Res<NumCamps>,
+    game_mode: Res<GameMode>,
+    tie_break: Res<TieBreak>,
+    max_players: Res<MaxPlayers>,
+    powerup_config: Res<PowerUpConfig>,
+    bots: Res<Bots>,
+    mut profiles: ResMut<PlayerProfiles>,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
+) {
+    lobby.connected = [false; MAX_PLAYERS];
+    lobby.connected[0] = true;
+    for conn in conns.0.iter().flatten() {
+        lobby.connected[conn.player_id as usize] = true;
+    }
+    lobby.ready[0] = lobby.local_ready;
+    for i in 0..MAX_PLAYERS {
+        lobby.ready[i] &= lobby.connected[i];
+        // bots are always ready
+        lobby.bots[i] = bots.is_bot(i as u8);
+        lobby.connected[i] |= lobby.bots[i];
+        lobby.ready[i] |= lobby.bots[i];
+        // free up the names of anyone who left
+        if !lobby.connected[i] && profiles.0[i].is_some() {
+            profiles.0[i] = None;
+        }
+        else if lobby.bots[i] && profiles.0[i].is_none() {
+            profiles.claim(i as u8, &format!("Bot {}", i + 1), i as u8);
+        }
+    }
+    lobby.spectators = spectators.0.iter().flatten().count() as u8;
+    lobby.countdown = if lobby.all_ready() {
+        Some(lobby.countdown.map_or(COUNTDOWN_TICKS, |ticks| ticks.saturating_sub(1)))
+    } else {
+        None
+    };
+
+    if let Some(sock) = sock.0.as_ref() {
+        let packet = LobbyTick {
+            seed: seed.0,
+            mode: *game_mode as u8,
+            tie_break: *tie_break as u8,
+            num_camps: num_camps.0,
+            max_players: max_players.0,
+            powerups: powerup_config.clone(),
+            connected: lobby.connected,
+            ready: lobby.ready,
+            bots: lobby.bots,
+            profiles: profiles.0.clone(),
+            spectators: lobby.spectators,
+            countdown: lobby.countdown,
+        };
+        let mut bytes: Vec<u8> = Vec::new();
+        packet.to_buf(&mut bytes);
+        for conn in conns.0.iter().chain(spectators.0.iter()).flatten() {
+            if let Err(e) = send_buf(bytes.as_slice(), sock, &conn.addr) {
+                println!("failed to send LobbyTick to {:?}: {}", conn.addr, e);
+            }
+        }
+    }
+
+    if lobby.countdown == Some(0) {
+        app_state_next_state.set(AppState::Game);
+    }
+}
+
+/// lets the host know whether we're ready, sent every tick so it also works as a heartbeat
+pub fn client_fixed(
+    mut socket: ResMut<net::Socket>,
+    lobby: Res<Lobby>,
+    mut popup_writer: EventWriter<PopupEvent>,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
+) {
+    if socket.0.is_none() { return }
+    let sock = socket.0.as_ref().unwrap();
+    let mut bytes: Vec<u8> = Vec::new();
+    LobbyReady { ready: lobby.local_ready }.to_buf(&mut bytes);
+    if let Err(e) = sock.peer_addr().and_then(|host_addr| send_buf(bytes.as_slice(), sock, &host_addr)) {
+        client::lose_host(&mut socket, e, &mut popup_writer, &mut app_state_next_state, AppState::Joining);
+    }
+}
+
+/// mirrors the host's lobby, follows it into the round, and back out of game over for a rematch
+pub fn handle_lobby_ticks(
+    mut lobby_reader: EventReader<LobbyTickEvent>,
+    mut player_reader: EventReader<PlayerTickEvent>,
+    mut lobby: ResMut<Lobby>,
+    mut seed: ResMut<MapSeed>,
+    mut num_camps: ResMut<NumCamps>,
+    mut game_mode: ResMut<GameMode>,
+    mut tie_break: ResMut<TieBreak>,
+    mut max_players: ResMut<MaxPlayers>,
+    mut powerup_config: ResMut<PowerUpConfig>,
+    mut profiles: ResMut<PlayerProfiles>,
+    app_state_current_state: Res<State<AppState>>,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
+) {
+    let state = *app_state_current_state.get();
+    let round_started = player_reader.iter().count() > 0;
+    for ev in lobby_reader.iter() {
+        match state {
+            // the host already moved on, finish this round first so it gets cleaned up
+            AppState::Game => app_state_next_state.set(AppState::GameOver),
+            AppState::GameOver => app_state_next_state.set(AppState::Lobby),
+            AppState::Lobby => {
+                seed.0 = ev.0.seed;
+                num_camps.0 = ev.0.num_camps;
+                max_players.0 = ev.0.max_players;
+                *game_mode = GameMode::from_u8(ev.0.mode).unwrap_or(GameMode::FreeForAll);
+                *tie_break = TieBreak::from_u8(ev.0.tie_break).unwrap_or_default();
+                *powerup_config = ev.0.powerups.clone();
+                lobby.connected = ev.0.connected;
+                lobby.ready = ev.0.ready;
+                lobby.bots = ev.0.bots;
+                if profiles.0 != ev.0.profiles {
+                    profiles.0 = ev.0.profiles.clone();
+                }
+                lobby.spectators = ev.0.spectators;
+                lobby.countdown = ev.0.countdown;
+                if lobby.countdown == Some(0) {
+                    app_state_next_state.set(AppState::Game);
+                }
+            }
+            _ => {}
+        }
+    }
+    // we missed the end of the countdown but the round has clearly started
+    if state == AppState::Lobby && round_started {
+        app_state_next_state.set(AppState::Game);
+    }
+}
@@ -2,12 +2,15 @@ pub mod host;
 pub mod client;
 pub mod lerp;
 pub mod packets;
+pub mod lobby;
+pub mod replay;
+pub mod discovery;
 
 use std::net::UdpSocket;
 use bevy::prelude::*;
 use crate::AppState;
 use crate::game::{enemy, movement};
-use packets::{PlayerTickEvent, EnemyTickEvent, UserCmdEvent};
+use packets::{PlayerTickEvent, EnemyTickEvent, HostTickEvent, UserCmdEvent};
 use crate::game::buffers::{BUFFER_LEN, DirBuffer, EventBuffer, HpBuffer, PosBuffer};
 use crate::game::components::Player;
 use crate::game::player;
@@ -17,7 +20,8 @@ pub const TICKRATE: u8 = 10;
 pub const TICKLEN_S: f32 = 1. / TICKRATE as f32;
 pub const DELAY: u16 = 2;
 pub const MAGIC_NUMBER: u16 = 24835; // 8008135 % 69420
-pub const MAX_DATAGRAM_SIZE: usize = 1024;
+pub const MAX_DATAGRAM_SIZE: usize = 4096; // spectators get every enemy on the map
+pub const PROTOCOL_VERSION: u8 = 1; // bump whenever a packet changes, so the join page can tell old hosts apart
 
 #[derive(Resource)]
 pub struct TickNum(pub u16);  // this is the tick we're writing to, NOT playing back
@@ -41,22 +45,43 @@ impl Plugin for NetPlugin {
         app
             .add_systems(Startup, (startup, host::startup))  // you cant conditionally run this unless you do a bunch of bullshit
             .add_systems(FixedUpdate,
-                         (increment_tick.after(client::fixed).after(host::fixed).run_if(in_state(AppState::Game)),
+                         (increment_tick.after(client::fixed).after(host::fixed).run_if(in_state(AppState::Game)).run_if(not(replay::replay_paused)),
+                         lobby::host_fixed.run_if(is_host).run_if(in_state(AppState::Lobby)),
+                         lobby::client_fixed.run_if(is_client).run_if(in_state(AppState::Lobby)),
                          client::fixed.run_if(is_client).after(movement::update_buffer),
                          host::fixed.run_if(is_host).after(enemy::fixed_move).after(movement::update_buffer),
+                         host::resend_kicks.run_if(is_host),
+                         host::drop_silent.run_if(is_host).run_if(in_state(AppState::Lobby).or_else(in_state(AppState::Game))),
                          lerp::resolve_collisions.run_if(is_host).run_if(in_state(AppState::Game)).after(enemy::fixed_resolve).before(increment_tick)))
             .add_systems(Update,
                          (lerp::lerp_pos.after(host::update).after(player::handle_usercmd_events).after(increment_tick),
                          client::update.run_if(is_client),
-                         host::update.run_if(is_host)))
-            .add_systems(OnEnter(AppState::Game), host::connect.run_if(is_host))
-            .add_systems(OnExit(AppState::Game),
+                         client::handle_host_ticks.run_if(is_client).after(client::update),
+                         lobby::handle_lobby_ticks.run_if(is_client).after(client::update),
+                         host::update.run_if(is_host),
+                         host::kick_players.run_if(is_host).after(host::update)))
+            .add_systems(OnEnter(AppState::Lobby), (host::connect.run_if(is_host), host::hear_everyone.run_if(is_host), lobby::reset_lobby))
+            .add_systems(OnEnter(AppState::Game), (reset_tick, host::hear_everyone.run_if(is_host)))
+            // the socket stays open through game over so everyone can stay together for a rematch
+            .add_systems(OnEnter(AppState::MainMenu),
                      (client::disconnect.run_if(is_client),
                       host::disconnect.run_if(is_host)))
+            .add_systems(OnEnter(AppState::Credits),
+                     (client::disconnect.run_if(is_client),
+                      host::disconnect.run_if(is_host)))
+            // and when giving up on connecting
+            .add_systems(OnEnter(AppState::Joining), client::disconnect.run_if(is_client))
             .add_systems(OnEnter(AppState::Connecting), client::connect.run_if(is_client))
+            .add_systems(Update, client::retry_connect.run_if(is_client).run_if(in_state(AppState::Connecting)))
             .add_event::<EnemyTickEvent>()
             .add_event::<PlayerTickEvent>()
-            .add_event::<UserCmdEvent>();
+            .add_event::<UserCmdEvent>()
+            .add_event::<HostTickEvent>()
+            .add_event::<lobby::LobbyTickEvent>()
+            .init_resource::<lobby::Lobby>()
+            .init_resource::<host::Kicked>()
+            .init_resource::<client::ConnectAttempt>()
+            .add_plugins((replay::ReplayPlugin, discovery::DiscoveryPlugin));
     }
 }
 
@@ -68,6 +93,11 @@ pub fn startup(mut commands: Commands) {
     commands.insert_resource(Ack { rmt_num: 0, bitfield: 0 });
 }
 
+/// every machine starts the round on tick 0, clients jump ahead if they start late
+pub fn reset_tick(mut tick: ResMut<TickNum>) {
+    tick.0 = 0;
+}
+
 pub fn increment_tick(
     mut tick: ResMut<TickNum>,
     mut pos_buffers: Query<(&mut PosBuffer, &Player)>,
@@ -1,18 +1,36 @@
-use std::io::Result;
+use std::io::{Error, ErrorKind, Result};
 use std::net::{SocketAddr, UdpSocket};
 use bevy::prelude::*;
 use crate::game::components::{PowerUpType, Stats, StoredPowerUps};
+use crate::game::item::{Inventory, ItemType, HOTBAR_SLOTS, NO_ITEM};
 use crate::game::map::MAXCHESTS;
+use crate::game::player::MAX_PLAYERS;
+use crate::game::profile::PlayerProfile;
+use crate::game::killfeed::Kill;
+use crate::game::chat::ChatLine;
+use crate::game::ping::{Ping, PingType};
+use crate::game::powerup::{PowerUpConfig, PowerUpCurve};
 use crate::net::MAGIC_NUMBER;
 
+const NO_OWNER: u8 = 0xFF;
+const NO_TEAM: u8 = 0xFF;
+const NO_COUNTDOWN: u8 = 0xFF;
+
 
 pub enum PacketType {
     ServerFull,  // sent by host every time request is received and server is full
-    Disconnect,  // sent by client in disconnected state every time HostTick is received
+    Disconnect,  // sent by client in disconnected state every time HostTick is received, and by host to a kicked client
     ConnectionRequest,  // sent by client to request connection to a host
     ConnectionResponse,  // sent by a host to a client who has requested connection
     HostTick,  // sent by host to all connected clients individually
     ClientTick,  // sent by client to host every FixedUpdate unless ServerFull received
+    LobbyTick,  // sent by host to all connected clients every FixedUpdate while in the lobby
+    LobbyReady,  // sent by client to host every FixedUpdate while in the lobby
+    ChatMessage,  // sent by client to host every FixedUpdate until the host acks it
+    ChatAck,  // sent by host every time a ChatMessage is received
+    PingRequest,  // sent by client to host for a few FixedUpdates after the player pings
+    DiscoveryQuery,  // broadcast by the join page every so often to find hosts on the LAN
+    DiscoveryReply,  // sent by a host's discovery socket every time a DiscoveryQuery is received
 }
 
 /// sent over the network to describe an enemy
@@ -20,7 +38,16 @@ pub struct EnemyTick {
     pub id: u8,
     pub pos: Vec2,
     pub hp: u8,
-    pub events: u8
+    pub events: u8,
+    pub status: u8
+}
+
+/// sent over the network to describe a camp
+pub struct CampTick {
+    pub id: u8,
+    pub active: bool,
+    pub enemies: u8,
+    pub owner: Option<u8>,
 }
 
 /// sent by network module to disperse enemy information from the host
@@ -37,8 +64,14 @@ pub struct PlayerTick {
     pub hp: u8,
     pub dir: f32,
     pub events: u8,
+    pub stamina: u8,
+    pub status: u8,
+    pub team: u8,
+    pub respawn_wait: u8,
+    pub protection: u8,
     pub stats: Stats,
-    pub powerups: StoredPowerUps
+    pub powerups: StoredPowerUps,
+    pub inventory: Inventory
 }
 
 /// sent by network module to disperse player information from the host
@@ -48,6 +81,9 @@ pub struct PlayerTickEvent {
     pub tick: PlayerTick
 }
 
+/// sent by network module, or by a replay, to hand a host tick to the client systems
+#[derive(Event)]
+pub struct HostTickEvent(pub HostTick);
 
 /// the information that the client needs to produce on each tick
 pub struct UserCmd {
@@ -64,6 +100,158 @@ pub struct UserCmdEvent {
     pub tick: UserCmd
 }
 
+/// writes a counter in as few bytes as it needs, 7 bits at a time with the high bit set while more follow
+pub fn write_varint(bytes: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(buf: &[u8], i: &mut usize) -> Result<u32> {
+    let mut value: u32 = 0;
+    let mut shift = 0;
+    loop {
+        need(buf, *i, 1)?;
+        let byte = buf[*i];
+        *i += 1;
+        value |= ((byte & 0x7F) as u32).checked_shl(shift).unwrap_or(0);
+        if byte & 0x80 == 0 { return Ok(value) }
+        shift += 7;
+    }
+}
+
+/// fails unless there are len more bytes from i, for packets too varied to check the length of up front
+fn need(buf: &[u8], i: usize, len: usize) -> Result<()> {
+    if buf.len() < i + len { return Err(Error::new(ErrorKind::InvalidData, "packet cut short")) }
+    Ok(())
+}
+
+/// one bit per player id, so a flag for every player fits in two bytes
+fn write_player_bits(bytes: &mut Vec<u8>, flags: &[bool; MAX_PLAYERS]) {
+    let mut bits: u16 = 0;
+    for id in 0..MAX_PLAYERS {
+        if flags[id] { bits |= 1 << id }
+    }
+    bytes.extend_from_slice(&bits.to_be_bytes());
+}
+
+fn read_player_bits(buf: &[u8], i: &mut usize) -> [bool; MAX_PLAYERS] {
+    let bits = u16::from_be_bytes(buf[*i..*i+2].try_into().unwrap());
+    *i += 2;
+    let mut flags = [false; MAX_PLAYERS];
+    for id in 0..MAX_PLAYERS {
+        flags[id] = bits & (1 << id) != 0;
+    }
+    flags
+}
+
+/// a length byte then the utf-8, bad text comes out empty and text that runs off the end is an error.
+/// long text is cut between characters so what's left still reads
+fn write_text(bytes: &mut Vec<u8>, text: &str) {
+    let mut len = text.len().min(u8::MAX as usize);
+    while !text.is_char_boundary(len) {
+        len -= 1;
+    }
+    bytes.push(len as u8);
+    bytes.extend_from_slice(&text.as_bytes()[..len]);
+}
+
+fn read_text(buf: &[u8], i: &mut usize) -> Result<String> {
+    need(buf, *i, 1)?;
+    let len = buf[*i] as usize;
+    *i += 1;
+    need(buf, *i, len)?;
+    let text = String::from_utf8(buf[*i..*i+len].to_vec()).unwrap_or_default();
+    *i += len;
+    Ok(text)
+}
+
+/// which ids have a profile, then the color and name of each one that does
+fn write_profiles(bytes: &mut Vec<u8>, profiles: &[Option<PlayerProfile>; MAX_PLAYERS]) {
+    let present: [bool; MAX_PLAYERS] = std::array::from_fn(|id| profiles[id].is_some());
+    write_player_bits(bytes, &present);
+    for profile in profiles.iter().flatten() {
+        bytes.push(profile.color);
+        write_text(bytes, &profile.name);
+    }
+}
+
+fn read_profiles(buf: &[u8], i: &mut usize) -> Result<[Option<PlayerProfile>; MAX_PLAYERS]> {
+    need(buf, *i, 2)?;
+    let present = read_player_bits(buf, i);
+    let mut profiles: [Option<PlayerProfile>; MAX_PLAYERS] = Default::default();
+    for id in 0..MAX_PLAYERS {
+        if !present[id] { continue }
+        need(buf, *i, 1)?;
+        let color = buf[*i];
+        *i += 1;
+        profiles[id] = Some(PlayerProfile { name: read_text(buf, i)?, color });
+    }
+    Ok(profiles)
+}
+
+fn write_powerup_config(bytes: &mut Vec<u8>, config: &PowerUpConfig) {
+    for curve in config.curves.iter() {
+        bytes.push(curve.cap);
+        bytes.extend_from_slice(&curve.per_stack.to_be_bytes());
+        bytes.extend_from_slice(&curve.falloff.to_be_bytes());
+        bytes.extend_from_slice(&curve.weight.to_be_bytes());
+    }
+    bytes.extend_from_slice(&config.death_drop.to_be_bytes());
+}
+
+fn read_powerup_config(buf: &[u8], i: &mut usize) -> PowerUpConfig {
+    let mut config = PowerUpConfig::default();
+    for curve in config.curves.iter_mut() {
+        *curve = PowerUpCurve {
+            cap: buf[*i],
+            per_stack: f32::from_be_bytes(buf[*i+1..*i+5].try_into().unwrap()),
+            falloff: f32::from_be_bytes(buf[*i+5..*i+9].try_into().unwrap()),
+            weight: u32::from_be_bytes(buf[*i+9..*i+13].try_into().unwrap()),
+        };
+        *i += 13;
+    }
+    config.death_drop = f32::from_be_bytes(buf[*i..*i+4].try_into().unwrap());
+    *i += 4;
+    config
+}
+
+fn read_stats(buf: &[u8], i: &mut usize) -> Result<Stats> {
+    Ok(Stats {
+        score: read_varint(buf, i)?,
+        enemies_killed: read_varint(buf, i)? as u16,
+        players_killed: read_varint(buf, i)? as u16,
+        camps_captured: read_varint(buf, i)? as u16,
+        deaths: read_varint(buf, i)? as u16,
+        assists: read_varint(buf, i)? as u16,
+        damage_dealt: read_varint(buf, i)?,
+        damage_taken: read_varint(buf, i)?,
+        powerups_collected: read_varint(buf, i)? as u16,
+        chests_opened: read_varint(buf, i)? as u16,
+        kill_streak: read_varint(buf, i)? as u16,
+        best_streak: read_varint(buf, i)? as u16,
+        ticks_alive: read_varint(buf, i)?,
+    })
+}
+
+fn write_stats(bytes: &mut Vec<u8>, stats: &Stats) {
+    write_varint(bytes, stats.score);
+    write_varint(bytes, stats.enemies_killed as u32);
+    write_varint(bytes, stats.players_killed as u32);
+    write_varint(bytes, stats.camps_captured as u32);
+    write_varint(bytes, stats.deaths as u32);
+    write_varint(bytes, stats.assists as u32);
+    write_varint(bytes, stats.damage_dealt);
+    write_varint(bytes, stats.damage_taken);
+    write_varint(bytes, stats.powerups_collected as u32);
+    write_varint(bytes, stats.chests_opened as u32);
+    write_varint(bytes, stats.kill_streak as u32);
+    write_varint(bytes, stats.best_streak as u32);
+    write_varint(bytes, stats.ticks_alive);
+}
+
 pub fn send_buf(buf: &[u8], local: &UdpSocket, peer: &SocketAddr) -> Result<usize> {
     if local.peer_addr().is_ok() {
         return local.send(buf);
@@ -80,28 +268,91 @@ pub struct HostTick {
     pub seq_num: u16,
     pub rmt_num: u16,
     pub ack: u32,
+    pub phase: u8,
+    pub end_tick: u16,
+    pub roster: [bool; MAX_PLAYERS], // who is in the match, players that haven't spawned yet aren't in players
+    pub profiles: [Option<PlayerProfile>; MAX_PLAYERS],
+    pub kills: Vec<Kill>,
+    pub chat: Vec<ChatLine>, // recent lines for this player, repeated for a while so a dropped packet doesn't lose one
+    pub pings: Vec<Ping>, // the ones this player can see
     pub enemies: Vec<EnemyTick>,
     pub players: Vec<PlayerTick>,
     pub powerups: Vec<(PowerUpType, Vec2)>,
-    pub camps: Vec<(u8, u8)>,
+    pub items: Vec<(ItemType, Vec2)>,
+    pub camps: Vec<CampTick>,
     pub chests: Vec<(u8, u8)>,
 }
 
 impl Packet for HostTick {
     fn from_buf(buf: &[u8]) -> Result<Self> {
         let mut i: usize = 0;
+        need(buf, i, 13)?;
         let seq_num = u16::from_be_bytes(buf[i..i+2].try_into().unwrap());
         i += 2;
         let rmt_num = u16::from_be_bytes(buf[i..i+2].try_into().unwrap());
         i += 2;
         let ack = u32::from_be_bytes(buf[i..i+4].try_into().unwrap());
         i += 4;
+        let phase = u8::from_be_bytes([buf[i]].try_into().unwrap());
+        i += 1;
+        let end_tick = u16::from_be_bytes(buf[i..i+2].try_into().unwrap());
+        i += 2;
+        let roster = read_player_bits(buf, &mut i);
+        let profiles = read_profiles(buf, &mut i)?;
+        need(buf, i, 1)?;
+        let kill_count = u8::from_be_bytes([buf[i]].try_into().unwrap());
+        i += 1;
+        need(buf, i, kill_count as usize * 4)?;
+        let mut kills: Vec<Kill> = Vec::new();
+        for _ in 0..kill_count {
+            let tick = u16::from_be_bytes(buf[i..i+2].try_into().unwrap());
+            i += 2;
+            let killer = u8::from_be_bytes([buf[i]].try_into().unwrap());
+            i += 1;
+            let victim = u8::from_be_bytes([buf[i]].try_into().unwrap());
+            i += 1;
+            kills.push(Kill { tick, killer, victim });
+        }
+        need(buf, i, 1)?;
+        let chat_count = u8::from_be_bytes([buf[i]].try_into().unwrap());
+        i += 1;
+        let mut chat: Vec<ChatLine> = Vec::new();
+        for _ in 0..chat_count {
+            need(buf, i, 3)?;
+            let id = u16::from_be_bytes(buf[i..i+2].try_into().unwrap());
+            i += 2;
+            let sender = u8::from_be_bytes([buf[i]].try_into().unwrap());
+            i += 1;
+            chat.push(ChatLine { id, sender, text: read_text(buf, &mut i)? });
+        }
+        need(buf, i, 1)?;
+        let ping_count = u8::from_be_bytes([buf[i]].try_into().unwrap());
+        i += 1;
+        need(buf, i, ping_count as usize * 14)?;
+        let mut pings: Vec<Ping> = Vec::new();
+        for _ in 0..ping_count {
+            let id = u16::from_be_bytes(buf[i..i+2].try_into().unwrap());
+            i += 2;
+            let tick = u16::from_be_bytes(buf[i..i+2].try_into().unwrap());
+            i += 2;
+            let sender = u8::from_be_bytes([buf[i]].try_into().unwrap());
+            i += 1;
+            let kind = PingType::from_u8(buf[i]).unwrap_or(PingType::Attack);
+            i += 1;
+            let x = f32::from_be_bytes(buf[i..i+4].try_into().unwrap());
+            i += 4;
+            let y = f32::from_be_bytes(buf[i..i+4].try_into().unwrap());
+            i += 4;
+            pings.push(Ping { id, tick, sender, kind, pos: Vec2::new(x, y) });
+        }
+        need(buf, i, 3)?;
         let enemy_count = u8::from_be_bytes([buf[i]].try_into().unwrap());
         i += 1;
         let player_count = u8::from_be_bytes([buf[i]].try_into().unwrap());
         i += 1;
         let powerup_count = u8::from_be_bytes([buf[i]].try_into().unwrap());
         i += 1;
+        need(buf, i, enemy_count as usize * 12)?;
         let mut enemies: Vec<EnemyTick> = Vec::new();
         for _ in 0..enemy_count {
             let id = u8::from_be_bytes([buf[i]].try_into().unwrap());
@@ -115,10 +366,13 @@ impl Packet for HostTick {
             i += 1;
             let events = u8::from_be_bytes([buf[i]].try_into().unwrap());
             i += 1;
-            enemies.push(EnemyTick { id, pos, hp, events });
+            let status = u8::from_be_bytes([buf[i]].try_into().unwrap());
+            i += 1;
+            enemies.push(EnemyTick { id, pos, hp, events, status });
         }
         let mut players: Vec<PlayerTick> = Vec::new();
         for _ in 0..player_count {
+            need(buf, i, 20)?;
             let id = u8::from_be_bytes([buf[i]].try_into().unwrap());
             i += 1;
             let pos = Vec2 {
@@ -132,18 +386,18 @@ impl Packet for HostTick {
             i += 4;
             let events = u8::from_be_bytes([buf[i]].try_into().unwrap());
             i += 1;
-            let score = u8::from_be_bytes([buf[i]].try_into().unwrap());
+            let stamina = u8::from_be_bytes([buf[i]].try_into().unwrap());
             i += 1;
-            let enemies_killed = u8::from_be_bytes([buf[i]].try_into().unwrap());
+            let status = u8::from_be_bytes([buf[i]].try_into().unwrap());
             i += 1;
-            let players_killed = u8::from_be_bytes([buf[i]].try_into().unwrap());
+            let team = u8::from_be_bytes([buf[i]].try_into().unwrap());
             i += 1;
-            let camps_captured = u8::from_be_bytes([buf[i]].try_into().unwrap());
+            let respawn_wait = u8::from_be_bytes([buf[i]].try_into().unwrap());
             i += 1;
-            let deaths = u8::from_be_bytes([buf[i]].try_into().unwrap());
+            let protection = u8::from_be_bytes([buf[i]].try_into().unwrap());
             i += 1;
-            let kd_ratio = f32::from_be_bytes(buf[i..i+4].try_into().unwrap());
-            i += 4;
+            let stats = read_stats(buf, &mut i)?;
+            need(buf, i, 5 + HOTBAR_SLOTS + 2)?;
             let meat = u8::from_be_bytes([buf[i]].try_into().unwrap());
             i += 1;
             let damage_dealt_up = u8::from_be_bytes([buf[i]].try_into().unwrap());
@@ -154,16 +408,20 @@ impl Packet for HostTick {
             i += 1;
             let move_speed_up = u8::from_be_bytes([buf[i]].try_into().unwrap());
             i += 1;
-            players.push(PlayerTick { id, pos, hp, dir, events, stats: Stats {
-                score,
-                enemies_killed,
-                players_killed,
-                camps_captured,
-                deaths,
-                kd_ratio,
-            }, powerups: StoredPowerUps { power_ups: [meat, damage_dealt_up, damage_reduction_up, attack_speed_up, move_speed_up] } });
+            let mut inventory = Inventory::default();
+            for slot in 0..HOTBAR_SLOTS {
+                let id = u8::from_be_bytes([buf[i]].try_into().unwrap());
+                i += 1;
+                inventory.slots[slot] = if id == NO_ITEM { None } else { ItemType::from_u8(id - 1) };
+            }
+            inventory.speed_ticks = u8::from_be_bytes([buf[i]].try_into().unwrap());
+            i += 1;
+            inventory.reveal_ticks = u8::from_be_bytes([buf[i]].try_into().unwrap());
+            i += 1;
+            players.push(PlayerTick { id, pos, hp, dir, events, stamina, status, team, respawn_wait, protection, stats, powerups: StoredPowerUps { power_ups: [meat, damage_dealt_up, damage_reduction_up, attack_speed_up, move_speed_up] }, inventory });
         }
         let mut powerups: Vec<(PowerUpType, Vec2)> = Vec::new();
+        need(buf, i, powerup_count as usize * 9)?;
         for _ in 0..powerup_count {
             let ptype = u8::from_be_bytes([buf[i]].try_into().unwrap());
             i += 1;
@@ -173,7 +431,7 @@ impl Packet for HostTick {
                 2 => PowerUpType::DamageReductionUp,
                 3 => PowerUpType::AttackSpeedUp,
                 4 => PowerUpType::MovementSpeedUp,
-                _ => panic!()
+                _ => return Err(Error::new(ErrorKind::InvalidData, "unknown powerup type")),
             };
             let x = f32::from_be_bytes(buf[i..i+4].try_into().unwrap());
             i += 4;
@@ -181,14 +439,36 @@ impl Packet for HostTick {
             i += 4;
             powerups.push((ptype, Vec2 {x, y}));
         }
-        let mut camps: Vec<(u8, u8)> = Vec::new();
+        let mut items: Vec<(ItemType, Vec2)> = Vec::new();
+        need(buf, i, 1)?;
+        let item_count = u8::from_be_bytes([buf[i]].try_into().unwrap());
+        i += 1;
+        need(buf, i, item_count as usize * 9)?;
+        for _ in 0..item_count {
+            let item = ItemType::from_u8(u8::from_be_bytes([buf[i]].try_into().unwrap()));
+            i += 1;
+            let x = f32::from_be_bytes(buf[i..i+4].try_into().unwrap());
+            i += 4;
+            let y = f32::from_be_bytes(buf[i..i+4].try_into().unwrap());
+            i += 4;
+            if item.is_none() { continue }
+            items.push((item.unwrap(), Vec2 {x, y}));
+        }
+        let mut camps: Vec<CampTick> = Vec::new();
+        need(buf, i, 1)?;
         let num_camps = u8::from_be_bytes([buf[i]].try_into().unwrap());
         i += 1;
+        need(buf, i, num_camps as usize * 4 + MAXCHESTS * 2)?;
         for _ in 0..num_camps {
             let id = u8::from_be_bytes([buf[i]].try_into().unwrap());
             i += 1;
-            let count = u8::from_be_bytes([buf[i]].try_into().unwrap());
-            camps.push((id, count));
+            let active = u8::from_be_bytes([buf[i]].try_into().unwrap()) != 0;
+            i += 1;
+            let enemies = u8::from_be_bytes([buf[i]].try_into().unwrap());
+            i += 1;
+            let owner = u8::from_be_bytes([buf[i]].try_into().unwrap());
+            i += 1;
+            camps.push(CampTick { id, active, enemies, owner: if owner == NO_OWNER { None } else { Some(owner) } });
         }
         let mut chests: Vec<(u8, u8)> = Vec::new();
         for _ in 0..MAXCHESTS {
@@ -202,9 +482,17 @@ impl Packet for HostTick {
             seq_num,
             rmt_num,
             ack,
+            phase,
+            end_tick,
+            roster,
+            profiles,
+            kills,
+            chat,
+            pings,
             enemies,
             players,
             powerups,
+            items,
             camps,
             chests
         })
@@ -216,6 +504,31 @@ impl Packet for HostTick {
         bytes.extend_from_slice(&self.seq_num.to_be_bytes());
         bytes.extend_from_slice(&self.rmt_num.to_be_bytes());
         bytes.extend_from_slice(&self.ack.to_be_bytes());
+        bytes.extend_from_slice(&self.phase.to_be_bytes());
+        bytes.extend_from_slice(&self.end_tick.to_be_bytes());
+        write_player_bits(bytes, &self.roster);
+        write_profiles(bytes, &self.profiles);
+        bytes.extend_from_slice(&(self.kills.len() as u8).to_be_bytes());
+        for kill in &self.kills {
+            bytes.extend_from_slice(&kill.tick.to_be_bytes());
+            bytes.extend_from_slice(&kill.killer.to_be_bytes());
+            bytes.extend_from_slice(&kill.victim.to_be_bytes());
+        }
+        bytes.extend_from_slice(&(self.chat.len() as u8).to_be_bytes());
+        for line in &self.chat {
+            bytes.extend_from_slice(&line.id.to_be_bytes());
+            bytes.extend_from_slice(&line.sender.to_be_bytes());
+            write_text(bytes, &line.text);
+        }
+        bytes.extend_from_slice(&(self.pings.len() as u8).to_be_bytes());
+        for ping in &self.pings {
+            bytes.extend_from_slice(&ping.id.to_be_bytes());
+            bytes.extend_from_slice(&ping.tick.to_be_bytes());
+            bytes.extend_from_slice(&ping.sender.to_be_bytes());
+            bytes.extend_from_slice(&(ping.kind as u8).to_be_bytes());
+            bytes.extend_from_slice(&ping.pos.x.to_be_bytes());
+            bytes.extend_from_slice(&ping.pos.y.to_be_bytes());
+        }
         bytes.extend_from_slice(&(self.enemies.len() as u8).to_be_bytes());
         bytes.extend_from_slice(&(self.players.len() as u8).to_be_bytes());
         bytes.extend_from_slice(&(self.powerups.len() as u8).to_be_bytes());
@@ -225,6 +538,7 @@ impl Packet for HostTick {
             bytes.extend_from_slice(&enemy.pos.y.to_be_bytes());
             bytes.extend_from_slice(&enemy.hp.to_be_bytes());
             bytes.extend_from_slice(&enemy.events.to_be_bytes());
+            bytes.extend_from_slice(&enemy.status.to_be_bytes());
         }
         for player in &self.players {
             bytes.extend_from_slice(&player.id.to_be_bytes());
@@ -233,15 +547,21 @@ impl Packet for HostTick {
             bytes.extend_from_slice(&player.hp.to_be_bytes());
             bytes.extend_from_slice(&player.dir.to_be_bytes());
             bytes.extend_from_slice(&player.events.to_be_bytes());
-            bytes.extend_from_slice(&player.stats.score.to_be_bytes());
-            bytes.extend_from_slice(&player.stats.enemies_killed.to_be_bytes());
-            bytes.extend_from_slice(&player.stats.players_killed.to_be_bytes());
-            bytes.extend_from_slice(&player.stats.camps_captured.to_be_bytes());
-            bytes.extend_from_slice(&player.stats.deaths.to_be_bytes());
-            bytes.extend_from_slice(&player.stats.kd_ratio.to_be_bytes());
+            bytes.extend_from_slice(&player.stamina.to_be_bytes());
+            bytes.extend_from_slice(&player.status.to_be_bytes());
+            bytes.extend_from_slice(&player.team.to_be_bytes());
+            bytes.extend_from_slice(&player.respawn_wait.to_be_bytes());
+            bytes.extend_from_slice(&player.protection.to_be_bytes());
+            write_stats(bytes, &player.stats);
             for b in &player.powerups.power_ups {
                 bytes.extend_from_slice(&b.to_be_bytes());
             }
+            for slot in &player.inventory.slots {
+                let id = slot.map_or(NO_ITEM, |item| item as u8 + 1);
+                bytes.extend_from_slice(&id.to_be_bytes());
+            }
+            bytes.extend_from_slice(&player.inventory.speed_ticks.to_be_bytes());
+            bytes.extend_from_slice(&player.inventory.reveal_ticks.to_be_bytes());
         }
 
         for powerup in &self.powerups {
@@ -249,10 +569,18 @@ impl Packet for HostTick {
             bytes.extend_from_slice(&powerup.1.x.to_be_bytes());
             bytes.extend_from_slice(&powerup.1.y.to_be_bytes());
         }
+        bytes.extend_from_slice(&(self.items.len() as u8).to_be_bytes());
+        for item in &self.items {
+            bytes.extend_from_slice(&(item.0 as u8).to_be_bytes());
+            bytes.extend_from_slice(&item.1.x.to_be_bytes());
+            bytes.extend_from_slice(&item.1.y.to_be_bytes());
+        }
         bytes.extend_from_slice(&(self.camps.len() as u8).to_be_bytes());
         for camp in &self.camps {
-            bytes.extend_from_slice(&camp.0.to_be_bytes());
-            bytes.extend_from_slice(&camp.1.to_be_bytes());
+            bytes.extend_from_slice(&camp.id.to_be_bytes());
+            bytes.extend_from_slice(&(camp.active as u8).to_be_bytes());
+            bytes.extend_from_slice(&camp.enemies.to_be_bytes());
+            bytes.extend_from_slice(&camp.owner.unwrap_or(NO_OWNER).to_be_bytes());
         }
         for chest in &self.chests {
             bytes.extend_from_slice(&chest.0.to_be_bytes());
@@ -311,16 +639,49 @@ impl Packet for ClientTick {
     }
 }
 
+pub struct ConnectionRequest {
+    pub team: Option<u8>, // the team the player wants to be on, None lets the host pick
+    pub spectate: bool, // watch without taking a player slot
+    pub name: String, // from the player's profile, the host sanitizes it
+    pub color: u8,
+}
+
+impl Packet for ConnectionRequest {
+    fn from_buf(buf: &[u8]) -> Result<Self> {
+        let team = u8::from_be_bytes([buf[0]].try_into().unwrap());
+        let spectate = u8::from_be_bytes([buf[1]].try_into().unwrap()) != 0;
+        let color = u8::from_be_bytes([buf[2]].try_into().unwrap());
+        let mut i: usize = 3;
+        let name = read_text(buf, &mut i)?;
+        return Ok(ConnectionRequest { team: if team == NO_TEAM { None } else { Some(team) }, spectate, name, color });
+    }
+
+    fn to_buf(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&MAGIC_NUMBER.to_be_bytes());
+        bytes.extend_from_slice(&(PacketType::ConnectionRequest as u8).to_be_bytes());
+        bytes.extend_from_slice(&self.team.unwrap_or(NO_TEAM).to_be_bytes());
+        bytes.extend_from_slice(&(self.spectate as u8).to_be_bytes());
+        bytes.extend_from_slice(&self.color.to_be_bytes());
+        write_text(bytes, &self.name);
+    }
+}
+
 pub struct ConnectionResponse {
-    pub player_id: u8,
-    pub seed: u64
+    pub player_id: u8, // SPECTATOR_ID for spectators
+    pub seed: u64,
+    pub mode: u8,
+    pub tie_break: u8,
+    pub num_camps: u8, // with the seed, lets someone joining mid-match build the same map
 }
 
 impl Packet for ConnectionResponse {
     fn from_buf(buf: &[u8]) -> Result<Self> {
         let player_id = u8::from_be_bytes([buf[0]].try_into().unwrap());
         let seed = u64::from_be_bytes(buf[1..9].try_into().unwrap());
-        return Ok(ConnectionResponse { player_id, seed });
+        let mode = u8::from_be_bytes([buf[9]].try_into().unwrap());
+        let tie_break = u8::from_be_bytes([buf[10]].try_into().unwrap());
+        let num_camps = u8::from_be_bytes([buf[11]].try_into().unwrap());
+        return Ok(ConnectionResponse { player_id, seed, mode, tie_break, num_camps });
     }
 
     fn to_buf(&self, bytes: &mut Vec<u8>) {
@@ -328,6 +689,233 @@ impl Packet for ConnectionResponse {
         bytes.extend_from_slice(&(PacketType::ConnectionResponse as u8).to_be_bytes());
         bytes.extend_from_slice(&self.player_id.to_be_bytes());
         bytes.extend_from_slice(&self.seed.to_be_bytes());
+        bytes.extend_from_slice(&self.mode.to_be_bytes());
+        bytes.extend_from_slice(&self.tie_break.to_be_bytes());
+        bytes.extend_from_slice(&self.num_camps.to_be_bytes());
+    }
+}
+
+/// who is in the lobby and the settings for the next round
+pub struct LobbyTick {
+    pub seed: u64,
+    pub mode: u8,
+    pub tie_break: u8,
+    pub num_camps: u8,
+    pub max_players: u8,
+    pub powerups: PowerUpConfig,
+    pub connected: [bool; MAX_PLAYERS],
+    pub ready: [bool; MAX_PLAYERS],
+    pub bots: [bool; MAX_PLAYERS],
+    pub profiles: [Option<PlayerProfile>; MAX_PLAYERS],
+    pub spectators: u8,
+    pub countdown: Option<u8>,
+}
+
+impl Packet for LobbyTick {
+    fn from_buf(buf: &[u8]) -> Result<Self> {
+        let mut i: usize = 0;
+        let seed = u64::from_be_bytes(buf[i..i+8].try_into().unwrap());
+        i += 8;
+        let mode = u8::from_be_bytes([buf[i]].try_into().unwrap());
+        i += 1;
+        let tie_break = u8::from_be_bytes([buf[i]].try_into().unwrap());
+        i += 1;
+        let num_camps = u8::from_be_bytes([buf[i]].try_into().unwrap());
+        i += 1;
+        let max_players = u8::from_be_bytes([buf[i]].try_into().unwrap());
+        i += 1;
+        let powerups = read_powerup_config(buf, &mut i);
+        let connected = read_player_bits(buf, &mut i);
+        let ready = read_player_bits(buf, &mut i);
+        let bots = read_player_bits(buf, &mut i);
+        let profiles = read_profiles(buf, &mut i)?;
+        let spectators = u8::from_be_bytes([buf[i]].try_into().unwrap());
+        i += 1;
+        let countdown = u8::from_be_bytes([buf[i]].try_into().unwrap());
+        return Ok(LobbyTick {
+            seed,
+            mode,
+            tie_break,
+            num_camps,
+            max_players,
+            powerups,
+            connected,
+            ready,
+            bots,
+            profiles,
+            spectators,
+            countdown: if countdown == NO_COUNTDOWN { None } else { Some(countdown) },
+        });
+    }
+
+    fn to_buf(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&MAGIC_NUMBER.to_be_bytes());
+        bytes.extend_from_slice(&(PacketType::LobbyTick as u8).to_be_bytes());
+        bytes.extend_from_slice(&self.seed.to_be_bytes());
+        bytes.extend_from_slice(&self.mode.to_be_bytes());
+        bytes.extend_from_slice(&self.tie_break.to_be_bytes());
+        bytes.extend_from_slice(&self.num_camps.to_be_bytes());
+        bytes.extend_from_slice(&self.max_players.to_be_bytes());
+        write_powerup_config(bytes, &self.powerups);
+        write_player_bits(bytes, &self.connected);
+        write_player_bits(bytes, &self.ready);
+        write_player_bits(bytes, &self.bots);
+        write_profiles(bytes, &self.profiles);
+        bytes.extend_from_slice(&self.spectators.to_be_bytes());
+        bytes.extend_from_slice(&self.countdown.unwrap_or(NO_COUNTDOWN).to_be_bytes());
+    }
+}
+
+pub struct LobbyReady {
+    pub ready: bool,
+}
+
+impl Packet for LobbyReady {
+    fn from_buf(buf: &[u8]) -> Result<Self> {
+        let ready = u8::from_be_bytes([buf[0]].try_into().unwrap()) != 0;
+        return Ok(LobbyReady { ready });
+    }
+
+    fn to_buf(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&MAGIC_NUMBER.to_be_bytes());
+        bytes.extend_from_slice(&(PacketType::LobbyReady as u8).to_be_bytes());
+        bytes.extend_from_slice(&(self.ready as u8).to_be_bytes());
+    }
+}
+
+/// a line of chat from a client, numbered so the host takes each one once
+pub struct ChatMessage {
+    pub seq: u16,
+    pub text: String,
+}
+
+impl Packet for ChatMessage {
+    fn from_buf(buf: &[u8]) -> Result<Self> {
+        let seq = u16::from_be_bytes(buf[0..2].try_into().unwrap());
+        let mut i: usize = 2;
+        let text = read_text(buf, &mut i)?;
+        return Ok(ChatMessage { seq, text });
+    }
+
+    fn to_buf(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&MAGIC_NUMBER.to_be_bytes());
+        bytes.extend_from_slice(&(PacketType::ChatMessage as u8).to_be_bytes());
+        bytes.extend_from_slice(&self.seq.to_be_bytes());
+        write_text(bytes, &self.text);
+    }
+}
+
+/// the last ChatMessage the host has taken from this client
+pub struct ChatAck {
+    pub seq: u16,
+}
+
+impl Packet for ChatAck {
+    fn from_buf(buf: &[u8]) -> Result<Self> {
+        let seq = u16::from_be_bytes(buf[0..2].try_into().unwrap());
+        return Ok(ChatAck { seq });
+    }
+
+    fn to_buf(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&MAGIC_NUMBER.to_be_bytes());
+        bytes.extend_from_slice(&(PacketType::ChatAck as u8).to_be_bytes());
+        bytes.extend_from_slice(&self.seq.to_be_bytes());
+    }
+}
+
+/// a spot a client pinged, numbered so the host places each one once
+pub struct PingRequest {
+    pub seq: u16,
+    pub kind: u8,
+    pub pos: Vec2,
+}
+
+impl Packet for PingRequest {
+    fn from_buf(buf: &[u8]) -> Result<Self> {
+        let seq = u16::from_be_bytes(buf[0..2].try_into().unwrap());
+        let kind = u8::from_be_bytes([buf[2]].try_into().unwrap());
+        let x = f32::from_be_bytes(buf[3..7].try_into().unwrap());
+        let y = f32::from_be_bytes(buf[7..11].try_into().unwrap());
+        return Ok(PingRequest { seq, kind, pos: Vec2::new(x, y) });
+    }
+
+    fn to_buf(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&MAGIC_NUMBER.to_be_bytes());
+        bytes.extend_from_slice(&(PacketType::PingRequest as u8).to_be_bytes());
+        bytes.extend_from_slice(&self.seq.to_be_bytes());
+        bytes.extend_from_slice(&self.kind.to_be_bytes());
+        bytes.extend_from_slice(&self.pos.x.to_be_bytes());
+        bytes.extend_from_slice(&self.pos.y.to_be_bytes());
+    }
+}
+
+/// asks any host listening for it to describe its game
+pub struct DiscoveryQuery {
+    pub version: u8,
+    pub query_id: u16, // echoed back so the reply can be timed
+}
+
+impl Packet for DiscoveryQuery {
+    fn from_buf(buf: &[u8]) -> Result<Self> {
+        if buf.len() < 3 { return Err(Error::new(ErrorKind::InvalidData, "short DiscoveryQuery")) }
+        let version = u8::from_be_bytes([buf[0]].try_into().unwrap());
+        let query_id = u16::from_be_bytes(buf[1..3].try_into().unwrap());
+        return Ok(DiscoveryQuery { version, query_id });
+    }
+
+    fn to_buf(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&MAGIC_NUMBER.to_be_bytes());
+        bytes.extend_from_slice(&(PacketType::DiscoveryQuery as u8).to_be_bytes());
+        bytes.extend_from_slice(&self.version.to_be_bytes());
+        bytes.extend_from_slice(&self.query_id.to_be_bytes());
+    }
+}
+
+/// what a host tells the join page about its game, the address to join is where it came from
+pub struct DiscoveryReply {
+    pub version: u8,
+    pub query_id: u16,
+    pub host_id: u32, // picked at random when hosting, to tell apart replies from the same host over loopback and the LAN
+    pub port: u16, // the game port, not the discovery one
+    pub players: u8,
+    pub max_players: u8,
+    pub mode: u8,
+    pub in_game: bool,
+    pub seed: u64,
+    pub name: String,
+}
+
+impl Packet for DiscoveryReply {
+    fn from_buf(buf: &[u8]) -> Result<Self> {
+        // anyone on the LAN can send anything to the discovery port
+        if buf.len() < 22 || buf.len() < 22 + buf[21] as usize { return Err(Error::new(ErrorKind::InvalidData, "short DiscoveryReply")) }
+        let version = u8::from_be_bytes([buf[0]].try_into().unwrap());
+        let query_id = u16::from_be_bytes(buf[1..3].try_into().unwrap());
+        let host_id = u32::from_be_bytes(buf[3..7].try_into().unwrap());
+        let port = u16::from_be_bytes(buf[7..9].try_into().unwrap());
+        let players = u8::from_be_bytes([buf[9]].try_into().unwrap());
+        let max_players = u8::from_be_bytes([buf[10]].try_into().unwrap());
+        let mode = u8::from_be_bytes([buf[11]].try_into().unwrap());
+        let in_game = u8::from_be_bytes([buf[12]].try_into().unwrap()) != 0;
+        let seed = u64::from_be_bytes(buf[13..21].try_into().unwrap());
+        let mut i: usize = 21;
+        let name = read_text(buf, &mut i)?;
+        return Ok(DiscoveryReply { version, query_id, host_id, port, players, max_players, mode, in_game, seed, name });
+    }
+
+    fn to_buf(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&MAGIC_NUMBER.to_be_bytes());
+        bytes.extend_from_slice(&(PacketType::DiscoveryReply as u8).to_be_bytes());
+        bytes.extend_from_slice(&self.version.to_be_bytes());
+        bytes.extend_from_slice(&self.query_id.to_be_bytes());
+        bytes.extend_from_slice(&self.host_id.to_be_bytes());
+        bytes.extend_from_slice(&self.port.to_be_bytes());
+        bytes.extend_from_slice(&self.players.to_be_bytes());
+        bytes.extend_from_slice(&self.max_players.to_be_bytes());
+        bytes.extend_from_slice(&self.mode.to_be_bytes());
+        bytes.extend_from_slice(&(self.in_game as u8).to_be_bytes());
+        bytes.extend_from_slice(&self.seed.to_be_bytes());
+        write_text(bytes, &self.name);
     }
 }
 
@@ -339,4 +927,234 @@ pub fn send_empty_packet(pt: PacketType, local: &UdpSocket, peer: &SocketAddr) -
         return local.send(bytes.as_slice());
     }
     return local.send_to(bytes.as_slice(), peer);
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// writes a packet and reads it back the way the other end does, past the magic number and type
+    fn round_trip<P: Packet>(packet: &P, pt: PacketType) -> P {
+        let mut bytes: Vec<u8> = Vec::new();
+        packet.to_buf(&mut bytes);
+        assert_eq!(u16::from_be_bytes(bytes[0..2].try_into().unwrap()), MAGIC_NUMBER);
+        assert_eq!(bytes[2], pt as u8);
+        P::from_buf(&bytes[3..]).unwrap()
+    }
+
+    fn profile(name: &str, color: u8) -> Option<PlayerProfile> {
+        Some(PlayerProfile { name: name.to_string(), color })
+    }
+
+    #[test]
+    fn lobby_tick_round_trip() {
+        let mut powerups = PowerUpConfig::default();
+        powerups.next_cap();
+        powerups.next_falloff();
+        powerups.next_death_drop();
+        let mut connected = [false; MAX_PLAYERS];
+        connected[0] = true;
+        connected[3] = true;
+        connected[MAX_PLAYERS - 1] = true;
+        let mut ready = [false; MAX_PLAYERS];
+        ready[3] = true;
+        let mut bots = [false; MAX_PLAYERS];
+        bots[MAX_PLAYERS - 1] = true;
+        let mut profiles: [Option<PlayerProfile>; MAX_PLAYERS] = Default::default();
+        profiles[0] = profile("host", 2);
+        profiles[3] = profile("ünïcode", 7);
+        profiles[MAX_PLAYERS - 1] = profile("Bot 16", 15);
+        let packet = LobbyTick {
+            seed: 0xDEAD_BEEF_1234_5678,
+            mode: 2,
+            tie_break: 1,
+            num_camps: 9,
+            max_players: 16,
+            powerups: powerups.clone(),
+            connected,
+            ready,
+            bots,
+            profiles: profiles.clone(),
+            spectators: 3,
+            countdown: Some(12),
+        };
+        let read = round_trip(&packet, PacketType::LobbyTick);
+        assert_eq!(read.seed, packet.seed);
+        assert_eq!((read.mode, read.tie_break, read.num_camps, read.max_players), (2, 1, 9, 16));
+        for (read, sent) in read.powerups.curves.iter().zip(powerups.curves.iter()) {
+            assert_eq!((read.cap, read.per_stack, read.falloff, read.weight), (sent.cap, sent.per_stack, sent.falloff, sent.weight));
+        }
+        assert_eq!(read.powerups.death_drop, powerups.death_drop);
+        assert_eq!(read.powerups.cap_name(), powerups.cap_name());
+        assert_eq!(read.powerups.falloff_name(), powerups.falloff_name());
+        assert_eq!((read.connected, read.ready, read.bots), (connected, ready, bots));
+        assert!(read.profiles == profiles);
+        assert_eq!((read.spectators, read.countdown), (3, Some(12)));
+
+        let read = round_trip(&LobbyTick { countdown: None, ..packet }, PacketType::LobbyTick);
+        assert_eq!(read.countdown, None);
+    }
+
+    #[test]
+    fn host_tick_round_trip() {
+        let mut roster = [false; MAX_PLAYERS];
+        roster[0] = true;
+        roster[9] = true;
+        let mut profiles: [Option<PlayerProfile>; MAX_PLAYERS] = Default::default();
+        profiles[9] = profile("nine", 9);
+        let stats = Stats {
+            score: u32::MAX,
+            enemies_killed: 300,
+            players_killed: u16::MAX,
+            camps_captured: 2,
+            deaths: 0,
+            assists: 127,
+            damage_dealt: 128,
+            damage_taken: 1 << 21,
+            powerups_collected: 16384,
+            chests_opened: 1,
+            kill_streak: 5,
+            best_streak: 6,
+            ticks_alive: 123_456,
+        };
+        let mut inventory = Inventory::default();
+        inventory.slots[0] = Some(ItemType::Potion);
+        inventory.slots[HOTBAR_SLOTS - 1] = Some(ItemType::CampMap);
+        inventory.speed_ticks = 40;
+        inventory.reveal_ticks = 200;
+        let packet = HostTick {
+            seq_num: 65535,
+            rmt_num: 7,
+            ack: 0x8000_0001,
+            phase: 2,
+            end_tick: 4321,
+            roster,
+            profiles: profiles.clone(),
+            kills: vec![Kill { tick: 100, killer: 9, victim: 0 }],
+            chat: vec![ChatLine { id: 3, sender: 9, text: "gg".to_string() }, ChatLine { id: 4, sender: 0, text: String::new() }],
+            pings: vec![Ping { id: 1, tick: 90, sender: 0, kind: PingType::Chest, pos: Vec2::new(-12.5, 300.) }],
+            enemies: vec![EnemyTick { id: 4, pos: Vec2::new(1., -2.), hp: 50, events: 1, status: 0b1010 }],
+            players: vec![PlayerTick {
+                id: 9,
+                pos: Vec2::new(-100.25, 64.),
+                hp: 80,
+                dir: 1.5,
+                events: 0b1001,
+                stamina: 60,
+                status: 0b0100,
+                team: 1,
+                respawn_wait: 0,
+                protection: 10,
+                stats: stats.clone(),
+                powerups: StoredPowerUps { power_ups: [3, 1, 0, 8, 255] },
+                inventory: inventory.clone(),
+            }],
+            powerups: vec![(PowerUpType::AttackSpeedUp, Vec2::new(5., 6.))],
+            items: vec![(ItemType::Bomb, Vec2::new(7., 8.))],
+            camps: vec![CampTick { id: 0, active: true, enemies: 3, owner: None }, CampTick { id: 1, active: false, enemies: 0, owner: Some(9) }],
+            chests: (0..MAXCHESTS as u8).map(|id| (id, id * 10)).collect(),
+        };
+        let read = round_trip(&packet, PacketType::HostTick);
+        assert_eq!((read.seq_num, read.rmt_num, read.ack, read.phase, read.end_tick), (65535, 7, 0x8000_0001, 2, 4321));
+        assert_eq!(read.roster, roster);
+        assert!(read.profiles == profiles);
+        assert!(read.kills == packet.kills);
+        assert!(read.chat == packet.chat);
+        assert!(read.pings == packet.pings);
+        assert_eq!(read.enemies.len(), 1);
+        let enemy = &read.enemies[0];
+        assert_eq!((enemy.id, enemy.pos, enemy.hp, enemy.events, enemy.status), (4, Vec2::new(1., -2.), 50, 1, 0b1010));
+        assert_eq!(read.players.len(), 1);
+        let player = &read.players[0];
+        assert_eq!((player.id, player.pos, player.hp, player.dir, player.events), (9, Vec2::new(-100.25, 64.), 80, 1.5, 0b1001));
+        assert_eq!((player.stamina, player.status, player.team, player.respawn_wait, player.protection), (60, 0b0100, 1, 0, 10));
+        let read_stats = &player.stats;
+        assert_eq!((read_stats.score, read_stats.enemies_killed, read_stats.players_killed, read_stats.camps_captured),
+            (stats.score, stats.enemies_killed, stats.players_killed, stats.camps_captured));
+        assert_eq!((read_stats.deaths, read_stats.assists, read_stats.damage_dealt, read_stats.damage_taken),
+            (stats.deaths, stats.assists, stats.damage_dealt, stats.damage_taken));
+        assert_eq!((read_stats.powerups_collected, read_stats.chests_opened, read_stats.kill_streak, read_stats.best_streak, read_stats.ticks_alive),
+            (stats.powerups_collected, stats.chests_opened, stats.kill_streak, stats.best_streak, stats.ticks_alive));
+        assert!(player.powerups == StoredPowerUps { power_ups: [3, 1, 0, 8, 255] });
+        assert_eq!(player.inventory.slots, inventory.slots);
+        assert_eq!((player.inventory.speed_ticks, player.inventory.reveal_ticks), (40, 200));
+        assert_eq!(read.powerups, packet.powerups);
+        assert_eq!(read.items, packet.items);
+        assert_eq!(read.camps.len(), 2);
+        assert_eq!((read.camps[0].id, read.camps[0].active, read.camps[0].enemies, read.camps[0].owner), (0, true, 3, None));
+        assert_eq!((read.camps[1].id, read.camps[1].active, read.camps[1].enemies, read.camps[1].owner), (1, false, 0, Some(9)));
+        assert_eq!(read.chests, packet.chests);
+        // replay frames aren't padded like a live datagram, so a cut one has to fail rather than panic
+        let mut bytes: Vec<u8> = Vec::new();
+        packet.to_buf(&mut bytes);
+        for len in 3..bytes.len() {
+            assert!(HostTick::from_buf(&bytes[3..len]).is_err(), "read a HostTick cut to {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn chat_round_trip() {
+        let read = round_trip(&ChatMessage { seq: 513, text: "hello ✓".to_string() }, PacketType::ChatMessage);
+        assert_eq!((read.seq, read.text.as_str()), (513, "hello ✓"));
+        // anything past a length byte is cut off rather than corrupting the packet
+        let read = round_trip(&ChatMessage { seq: 1, text: "y".repeat(300) }, PacketType::ChatMessage);
+        assert_eq!(read.text.len(), u8::MAX as usize);
+        // and never in the middle of a character, 85 of these is 255 bytes so the 86th has to go
+        let read = round_trip(&ChatMessage { seq: 2, text: "✓".repeat(100) }, PacketType::ChatMessage);
+        assert_eq!(read.text, "✓".repeat(85));
+        let read = round_trip(&ChatMessage { seq: 3, text: format!("{}é", "y".repeat(254)) }, PacketType::ChatMessage);
+        assert_eq!(read.text, "y".repeat(254));
+        let read = round_trip(&ChatAck { seq: u16::MAX }, PacketType::ChatAck);
+        assert_eq!(read.seq, u16::MAX);
+    }
+
+    #[test]
+    fn ping_request_round_trip() {
+        let read = round_trip(&PingRequest { seq: 42, kind: PingType::Danger as u8, pos: Vec2::new(-2048., 0.5) }, PacketType::PingRequest);
+        assert_eq!((read.seq, read.kind, read.pos), (42, PingType::Danger as u8, Vec2::new(-2048., 0.5)));
+    }
+
+    #[test]
+    fn discovery_round_trip() {
+        let read = round_trip(&DiscoveryQuery { version: 1, query_id: 999 }, PacketType::DiscoveryQuery);
+        assert_eq!((read.version, read.query_id), (1, 999));
+        let reply = DiscoveryReply {
+            version: 1,
+            query_id: 999,
+            host_id: 0xCAFE_F00D,
+            port: 8085,
+            players: 3,
+            max_players: 8,
+            mode: 1,
+            in_game: true,
+            seed: u64::MAX,
+            name: "host's game".to_string(),
+        };
+        let read = round_trip(&reply, PacketType::DiscoveryReply);
+        assert_eq!((read.version, read.query_id, read.host_id, read.port), (1, 999, 0xCAFE_F00D, 8085));
+        assert_eq!((read.players, read.max_players, read.mode, read.in_game, read.seed), (3, 8, 1, true, u64::MAX));
+        assert_eq!(read.name, "host's game");
+    }
+
+    #[test]
+    fn discovery_rejects_short_packets() {
+        assert!(DiscoveryQuery::from_buf(&[1, 0]).is_err());
+        let mut bytes: Vec<u8> = Vec::new();
+        DiscoveryReply {
+            version: 1, query_id: 0, host_id: 0, port: 0, players: 0, max_players: 0, mode: 0, in_game: false, seed: 0,
+            name: "cut off".to_string(),
+        }.to_buf(&mut bytes);
+        assert!(DiscoveryReply::from_buf(&bytes[3..bytes.len() - 1]).is_err());
+        assert!(DiscoveryReply::from_buf(&bytes[3..10]).is_err());
+    }
+
+    #[test]
+    fn connection_round_trip() {
+        let read = round_trip(&ConnectionRequest { team: Some(1), spectate: false, name: "Jord".to_string(), color: 5 }, PacketType::ConnectionRequest);
+        assert_eq!((read.team, read.spectate, read.name.as_str(), read.color), (Some(1), false, "Jord", 5));
+        let read = round_trip(&ConnectionRequest { team: None, spectate: true, name: String::new(), color: 0 }, PacketType::ConnectionRequest);
+        assert_eq!((read.team, read.spectate, read.name.as_str()), (None, true, ""));
+        let response = ConnectionResponse { player_id: 15, seed: 0x0123_4567_89AB_CDEF, mode: 3, tie_break: 2, num_camps: 12 };
+        let read = round_trip(&response, PacketType::ConnectionResponse);
+        assert_eq!((read.player_id, read.seed, read.mode, read.tie_break, read.num_camps), (15, 0x0123_4567_89AB_CDEF, 3, 2, 12));
+    }
+}
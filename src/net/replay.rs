@@ -0,0 +1,619 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use bevy::prelude::*;
+use crate::AppState;
+use crate::game::PlayerId;
+use crate::game::map::{MapSeed, NumCamps};
+use crate::game::mode::GameMode;
+use crate::game::player::MAX_PLAYERS;
+use crate::game::round::TieBreak;
+use crate::game::spectate::SPECTATOR_ID;
+use crate::net::{self, DELAY, IsHost, MAGIC_NUMBER, MAX_DATAGRAM_SIZE, TICKLEN_S, TICKRATE, TickNum};
+use crate::net::lobby::Lobby;
+use crate::net::packets::{write_varint, ConnectionResponse, HostTick, HostTickEvent, Packet, PacketType};
+
+pub const REPLAYS_DIR: &str = "replays"; // every recorded match gets its own file in here
+const REPLAY_EXTENSION: &str = "jqr";
+const REPLAY_MAGIC: &[u8; 4] = b"JQRP";
+const REPLAY_VERSION: u8 = 2;
+const MIN_COPY: usize = 4; // shorter runs that match the last frame are cheaper to write out again
+pub const REPLAY_SPEEDS: [f32; 5] = [0.25, 0.5, 1., 2., 4.];
+const NORMAL_SPEED: usize = 2;
+const SEEK_TICKS: u16 = 5 * TICKRATE as u16;
+const LONG_SEEK_TICKS: u16 = 30 * TICKRATE as u16; // with shift held
+
+/// Whether clients record the matches they play, the host always does
+#[derive(Resource, Default)]
+pub struct RecordAsClient(pub bool);
+
+impl RecordAsClient {
+    pub fn name(&self) -> &'static str {
+        if self.0 { "On" } else { "Off" }
+    }
+}
+
+/// The match being recorded. A replay file is the magic and version, then a length-prefixed
+/// ConnectionResponse with everything needed to rebuild the world, then every HostTick the way it
+/// went over the network, each stored as a length-prefixed delta from the one before it
+#[derive(Resource, Default)]
+pub struct ReplayRecorder {
+    bytes: Option<Vec<u8>>,
+    last: Vec<u8>, // the last HostTick recorded, the next one is written as the changes from it
+}
+
+impl ReplayRecorder {
+    fn start(&mut self, world: &ConnectionResponse) {
+        let mut bytes = REPLAY_MAGIC.to_vec();
+        bytes.push(REPLAY_VERSION);
+        let mut packet: Vec<u8> = Vec::new();
+        world.to_buf(&mut packet);
+        bytes.extend_from_slice(&(packet.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&packet);
+        self.bytes = Some(bytes);
+        self.last.clear();
+    }
+
+    pub fn recording(&self) -> bool {
+        self.bytes.is_some()
+    }
+
+    /// adds a HostTick if a match is being recorded
+    pub fn record(&mut self, packet: &[u8]) {
+        let Some(bytes) = self.bytes.as_mut() else { return };
+        let mut delta: Vec<u8> = Vec::new();
+        encode_delta(&self.last, packet, &mut delta);
+        write_varint(bytes, delta.len() as u32);
+        bytes.extend_from_slice(&delta);
+        self.last.clear();
+        self.last.extend_from_slice(packet);
+    }
+}
+
+/// how many bytes at the start of a and b are the same
+fn match_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+fn write_literals(delta: &mut Vec<u8>, bytes: &[u8]) {
+    if bytes.is_empty() { return }
+    write_varint(delta, bytes.len() as u32 * 2);
+    delta.extend_from_slice(bytes);
+}
+
+/// writes frame as runs of new bytes and runs copied from the last frame. every run starts with a varint
+/// of its length times two, plus one for copies, which then say where in the last frame they start
+fn encode_delta(last: &[u8], frame: &[u8], delta: &mut Vec<u8>) {
+    // where each short run of bytes can be found in the last frame
+    let mut index: HashMap<&[u8], usize> = HashMap::new();
+    for from in (0..last.len().saturating_sub(MIN_COPY - 1)).rev() {
+        index.insert(&last[from..from + MIN_COPY], from);
+    }
+    let mut literal_start = 0;
+    let mut next_from = 0; // most of a tick lines up with the last one, so try carrying on from the last copy first
+    let mut i = 0;
+    while i < frame.len() {
+        let found = frame.get(i..i + MIN_COPY).and_then(|key| index.get(key).copied());
+        let best = [Some(next_from), found].into_iter().flatten()
+            .map(|from| (from, match_len(&last[from.min(last.len())..], &frame[i..])))
+            .max_by_key(|(_, len)| *len);
+        match best {
+            Some((from, len)) if len >= MIN_COPY => {
+                write_literals(delta, &frame[literal_start..i]);
+                write_varint(delta, len as u32 * 2 + 1);
+                write_varint(delta, from as u32);
+                i += len;
+                literal_start = i;
+                next_from = from + len;
+            }
+            _ => i += 1,
+        }
+    }
+    write_literals(delta, &frame[literal_start..]);
+}
+
+fn not_a_replay(what: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("not a replay: {}", what))
+}
+
+/// a varint, but a file can be cut off anywhere so running out is an error rather than a panic
+fn read_count(buf: &[u8], i: &mut usize) -> Result<usize> {
+    let mut value: usize = 0;
+    for shift in (0..32).step_by(7) {
+        let Some(byte) = buf.get(*i) else { return Err(not_a_replay("cut short")) };
+        *i += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 { return Ok(value) }
+    }
+    Err(not_a_replay("bad length"))
+}
+
+/// rebuilds a frame written by encode_delta
+fn decode_delta(last: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let mut frame: Vec<u8> = Vec::new();
+    let mut i = 0;
+    while i < delta.len() {
+        let run = read_count(delta, &mut i)?;
+        let len = run / 2;
+        let bytes = if run % 2 == 1 {
+            let from = read_count(delta, &mut i)?;
+            from.checked_add(len).and_then(|to| last.get(from..to))
+        } else {
+            i += len;
+            delta.get(i - len..i)
+        };
+        let Some(bytes) = bytes else { return Err(not_a_replay("bad delta")) };
+        frame.extend_from_slice(bytes);
+        if frame.len() > MAX_DATAGRAM_SIZE { return Err(not_a_replay("frame too big")) }
+    }
+    Ok(frame)
+}
+
+/// A replay loaded from REPLAYS_DIR and how far into it playback is
+#[derive(Resource)]
+pub struct Replay {
+    pub active: bool, // watching it right now
+    pub world: Option<ConnectionResponse>, // None until one is loaded
+    pub frames: Vec<(u16, Vec<u8>)>, // the tick each host tick was sent on, and the packet
+    pub cursor: usize, // next frame to play
+    pub paused: bool,
+    pub speed: usize, // index into REPLAY_SPEEDS
+}
+
+impl Default for Replay {
+    fn default() -> Replay {
+        Replay {
+            active: false,
+            world: None,
+            frames: Vec::new(),
+            cursor: 0,
+            paused: false,
+            speed: NORMAL_SPEED,
+        }
+    }
+}
+
+impl Replay {
+    /// reads a replay file, checking every packet in it is one it can play
+    pub fn load(path: &Path) -> Result<Replay> {
+        Replay::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Replay> {
+        let bad = not_a_replay;
+        let header = REPLAY_MAGIC.len() + 1;
+        if bytes.len() < header + 2 || &bytes[..REPLAY_MAGIC.len()] != REPLAY_MAGIC { return Err(bad("wrong magic")) }
+        if bytes[REPLAY_MAGIC.len()] != REPLAY_VERSION { return Err(bad("unknown version")) }
+        let len = u16::from_be_bytes(bytes[header..header + 2].try_into().unwrap()) as usize;
+        let mut i = header + 2;
+        let Some(world) = bytes.get(i..i + len) else { return Err(bad("cut short")) };
+        i += len;
+        if world.len() < 3 || u16::from_be_bytes(world[0..2].try_into().unwrap()) != MAGIC_NUMBER
+            || world[2] != PacketType::ConnectionResponse as u8 { return Err(bad("no world config")) }
+        let world = ConnectionResponse::from_buf(&world[3..])?;
+        let mut frames: Vec<(u16, Vec<u8>)> = Vec::new();
+        let mut last: &[u8] = &[];
+        while i < bytes.len() {
+            let len = read_count(bytes, &mut i)?;
+            let Some(delta) = bytes.get(i..i + len) else { return Err(bad("cut short")) };
+            i += len;
+            let packet = decode_delta(last, delta)?;
+            if packet.len() < 5 || u16::from_be_bytes(packet[0..2].try_into().unwrap()) != MAGIC_NUMBER
+                || packet[2] != PacketType::HostTick as u8 { return Err(bad("bad packet")) }
+            // parsed now so a corrupt tick can't take down playback halfway through
+            if HostTick::from_buf(&packet[3..]).is_err() { return Err(bad("bad packet")) }
+            frames.push((u16::from_be_bytes(packet[3..5].try_into().unwrap()), packet));
+            last = &frames.last().unwrap().1;
+        }
+        if frames.is_empty() { return Err(bad("no ticks")) }
+        // a client records ticks in the order they arrived
+        frames.sort_by_key(|(seq, _)| *seq);
+        Ok(Replay { world: Some(world), frames, ..default() })
+    }
+
+    /// what the replays page says about it
+    pub fn describe(&self) -> String {
+        let Some(world) = self.world.as_ref() else { return "Couldn't read this replay".to_string() };
+        let secs = self.last_tick().saturating_sub(self.first_tick()) as u32 / TICKRATE as u32;
+        format!("{}, seed {}, {}:{:02} long",
+            GameMode::from_u8(world.mode).unwrap_or(GameMode::FreeForAll).name(), world.seed, secs / 60, secs % 60)
+    }
+
+    pub fn first_tick(&self) -> u16 {
+        self.frames.first().map_or(0, |(seq, _)| *seq)
+    }
+
+    pub fn last_tick(&self) -> u16 {
+        self.frames.last().map_or(0, |(seq, _)| *seq)
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.cursor >= self.frames.len()
+    }
+
+    /// plays from tick, starting a few frames early so players have somewhere to move from
+    fn seek(&mut self, tick: &mut TickNum, to: u16) {
+        let to = to.clamp(self.first_tick(), self.last_tick());
+        let from = to.saturating_sub(DELAY + 1);
+        self.cursor = self.frames.iter().position(|(seq, _)| *seq >= from).unwrap_or(self.frames.len());
+        tick.0 = to;
+    }
+}
+
+/// The replay files in REPLAYS_DIR, newest first, and which one the replays page has picked
+#[derive(Resource, Default)]
+pub struct ReplayFiles {
+    pub paths: Vec<PathBuf>,
+    pub selected: usize,
+}
+
+impl ReplayFiles {
+    /// file names start with when the match finished, so sorting them sorts by age
+    pub fn scan() -> ReplayFiles {
+        let mut paths: Vec<PathBuf> = fs::read_dir(REPLAYS_DIR).map_or(Vec::new(), |entries| entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == REPLAY_EXTENSION))
+            .collect());
+        paths.sort();
+        paths.reverse();
+        ReplayFiles { paths, selected: 0 }
+    }
+
+    pub fn selected(&self) -> Option<&PathBuf> {
+        self.paths.get(self.selected)
+    }
+
+    pub fn next(&mut self) {
+        self.selected = (self.selected + 1) % self.paths.len().max(1);
+    }
+
+    /// what the button for picking a replay says
+    pub fn label(&self) -> String {
+        match self.selected() {
+            Some(path) => format!("Replay {}/{}: {}", self.selected + 1, self.paths.len(),
+                path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().to_string())),
+            None => "No replays recorded yet".to_string(),
+        }
+    }
+}
+
+/// Shows where a replay is at and its controls
+#[derive(Component)]
+pub struct ReplayDisplay;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Game), (
+                start_recording,
+                start_playback.after(net::reset_tick),
+            ))
+            .add_systems(OnExit(AppState::Game), (save_recording, stop_playback))
+            .add_systems(OnEnter(AppState::Replays), load_replay)
+            .add_systems(FixedUpdate, replay_fixed
+                .run_if(in_state(AppState::Game)).run_if(is_replaying).before(net::increment_tick))
+            .add_systems(Update, (replay_controls, replay_feedback)
+                .run_if(in_state(AppState::Game)).run_if(is_replaying))
+            .init_resource::<RecordAsClient>()
+            .init_resource::<ReplayFiles>()
+            .init_resource::<ReplayRecorder>()
+            .init_resource::<Replay>();
+    }
+}
+
+// for conditionally running systems
+pub fn is_replaying(replay: Res<Replay>) -> bool {
+    replay.active
+}
+
+pub fn replay_paused(replay: Res<Replay>) -> bool {
+    replay.active && replay.paused
+}
+
+/// the host records every match, clients only if they've asked to
+pub fn start_recording(
+    mut recorder: ResMut<ReplayRecorder>,
+    replay: Res<Replay>,
+    record_as_client: Res<RecordAsClient>,
+    is_host: Res<IsHost>,
+    player_id: Res<PlayerId>,
+    seed: Res<MapSeed>,
+    game_mode: Res<GameMode>,
+    tie_break: Res<TieBreak>,
+    num_camps: Res<NumCamps>,
+) {
+    recorder.bytes = None;
+    if replay.active || !(is_host.0 || record_as_client.0) { return }
+    recorder.start(&ConnectionResponse {
+        player_id: player_id.0,
+        seed: seed.0,
+        mode: *game_mode as u8,
+        tie_break: *tie_break as u8,
+        num_camps: num_camps.0,
+    });
+}
+
+/// writes the match to its own file, named like its match report
+pub fn save_recording(
+    mut recorder: ResMut<ReplayRecorder>,
+    seed: Res<MapSeed>,
+) {
+    let Some(bytes) = recorder.bytes.take() else { return };
+    recorder.last.clear();
+    let finished = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    let path = Path::new(REPLAYS_DIR).join(format!("match-{}-{}.{}", finished, seed.0, REPLAY_EXTENSION));
+    match fs::create_dir_all(REPLAYS_DIR).and_then(|_| fs::write(&path, bytes)) {
+        Ok(_) => println!("replay saved to {}", path.display()),
+        Err(e) => println!("couldn't save replay: {}", e),
+    }
+}
+
+/// finds the recorded matches and loads the newest one
+pub fn load_replay(
+    mut files: ResMut<ReplayFiles>,
+    mut replay: ResMut<Replay>,
+) {
+    *files = ReplayFiles::scan();
+    select_replay(&files, &mut replay);
+}
+
+/// loads whichever replay is picked, an unreadable one stays unloaded
+pub fn select_replay(files: &ReplayFiles, replay: &mut Replay) {
+    let Some(path) = files.selected() else {
+        *replay = Replay::default();
+        return;
+    };
+    *replay = Replay::load(path).unwrap_or_else(|e| {
+        println!("couldn't load replay {}: {}", path.display(), e);
+        Replay::default()
+    });
+}
+
+/// sets everything up the way a spectator joining the recorded match would have it, then goes to the game
+pub fn watch_replay(
+    replay: &mut Replay,
+    is_host: &mut IsHost,
+    player_id: &mut PlayerId,
+    settings: (&mut MapSeed, &mut GameMode, &mut TieBreak, &mut NumCamps),
+    lobby: &mut Lobby,
+) -> bool {
+    let Some(world) = replay.world.as_ref() else { return false };
+    let (seed, game_mode, tie_break, num_camps) = settings;
+    seed.0 = world.seed;
+    *game_mode = GameMode::from_u8(world.mode).unwrap_or(GameMode::FreeForAll);
+    *tie_break = TieBreak::from_u8(world.tie_break).unwrap_or_default();
+    num_camps.0 = world.num_camps;
+    is_host.0 = false;
+    player_id.0 = SPECTATOR_ID;
+    // the players in the first tick get spawned with the map, anyone else as they show up
+    lobby.connected = HostTick::from_buf(&replay.frames[0].1[3..]).map_or([false; MAX_PLAYERS], |tick| tick.roster);
+    replay.active = true;
+    true
+}
+
+pub fn start_playback(
+    mut replay: ResMut<Replay>,
+    mut tick: ResMut<TickNum>,
+    mut fixed_time: ResMut<FixedTime>,
+) {
+    if !replay.active { return }
+    replay.paused = false;
+    replay.speed = NORMAL_SPEED;
+    fixed_time.period = Duration::from_secs_f32(TICKLEN_S);
+    let first = replay.first_tick();
+    replay.seek(&mut tick, first);
+}
+
+pub fn stop_playback(
+    mut replay: ResMut<Replay>,
+    mut fixed_time: ResMut<FixedTime>,
+) {
+    if !replay.active { return }
+    replay.active = false;
+    fixed_time.period = Duration::from_secs_f32(TICKLEN_S);
+}
+
+/// hands over every recorded tick up to now, the way they would have come in from the host.
+/// the tick doesn't move while paused, but this still catches up after a seek
+pub fn replay_fixed(
+    mut replay: ResMut<Replay>,
+    tick: Res<TickNum>,
+    mut host_tick_writer: EventWriter<HostTickEvent>,
+) {
+    while let Some((seq, packet)) = replay.frames.get(replay.cursor) {
+        if *seq > tick.0 { break }
+        if let Ok(packet) = HostTick::from_buf(&packet[3..]) {
+            host_tick_writer.send(HostTickEvent(packet));
+        }
+        replay.cursor += 1;
+    }
+    // hold on the last tick so it can be scrubbed back through
+    if replay.at_end() {
+        replay.paused = true;
+    }
+}
+
+/// P pauses, comma and period seek, minus and equals change speed, backspace leaves
+pub fn replay_controls(
+    input: Res<Input<KeyCode>>,
+    mut replay: ResMut<Replay>,
+    mut tick: ResMut<TickNum>,
+    mut fixed_time: ResMut<FixedTime>,
+    mut app_state_next_state: ResMut<NextState<AppState>>,
+) {
+    if input.just_pressed(KeyCode::P) && !replay.at_end() {
+        replay.paused = !replay.paused;
+    }
+    let seek = if input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) { LONG_SEEK_TICKS } else { SEEK_TICKS };
+    if input.just_pressed(KeyCode::Comma) {
+        let to = tick.0.saturating_sub(seek);
+        replay.seek(&mut tick, to);
+    }
+    if input.just_pressed(KeyCode::Period) {
+        let to = tick.0.saturating_add(seek);
+        replay.seek(&mut tick, to);
+    }
+    if input.just_pressed(KeyCode::Minus) {
+        replay.speed = replay.speed.saturating_sub(1);
+    }
+    if input.just_pressed(KeyCode::Equals) {
+        replay.speed = (replay.speed + 1).min(REPLAY_SPEEDS.len() - 1);
+    }
+    // everything on the fixed schedule speeds up with it, including the interpolation
+    let period = Duration::from_secs_f32(TICKLEN_S / REPLAY_SPEEDS[replay.speed]);
+    if fixed_time.period != period {
+        fixed_time.period = period;
+    }
+    if input.just_pressed(KeyCode::Back) {
+        app_state_next_state.set(AppState::MainMenu);
+    }
+}
+
+fn clock(ticks: u16) -> String {
+    let secs = ticks as u32 / TICKRATE as u32;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+pub fn replay_feedback(
+    replay: Res<Replay>,
+    tick: Res<TickNum>,
+    mut replay_displays: Query<&mut Text, With<ReplayDisplay>>,
+) {
+    let first = replay.first_tick();
+    let state = if replay.at_end() { "  End" } else if replay.paused { "  Paused" } else { "" };
+    for mut text in &mut replay_displays {
+        text.sections[0].value = format!("Replay {} / {}  x{}{}\nP: pause  , .: seek (Shift: 30s)  - =: speed  Backspace: leave",
+            clock(tick.0.min(replay.last_tick()).saturating_sub(first)), clock(replay.last_tick().saturating_sub(first)),
+            REPLAY_SPEEDS[replay.speed], state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::map::MAXCHESTS;
+    use crate::net::packets::EnemyTick;
+
+    /// a host tick with a few enemies that drift a little every tick, the way a real match does
+    fn host_tick(seq_num: u16) -> Vec<u8> {
+        let mut roster = [false; MAX_PLAYERS];
+        roster[0] = true;
+        let packet = HostTick {
+            seq_num,
+            rmt_num: seq_num.wrapping_sub(2),
+            ack: u32::MAX,
+            phase: 1,
+            end_tick: 3000,
+            roster,
+            profiles: Default::default(),
+            kills: Vec::new(),
+            chat: Vec::new(),
+            pings: Vec::new(),
+            enemies: (0..20).map(|id| EnemyTick {
+                id,
+                pos: Vec2::new(id as f32 * 40., (seq_num % 7) as f32),
+                hp: 100 - id,
+                events: 0,
+                status: 0,
+            }).collect(),
+            players: Vec::new(),
+            powerups: Vec::new(),
+            items: Vec::new(),
+            camps: Vec::new(),
+            chests: vec![(0, 0); MAXCHESTS],
+        };
+        let mut bytes: Vec<u8> = Vec::new();
+        packet.to_buf(&mut bytes);
+        bytes
+    }
+
+    fn world() -> ConnectionResponse {
+        ConnectionResponse { player_id: 0, seed: 77, mode: 2, tie_break: 1, num_camps: 6 }
+    }
+
+    fn round_trip_delta(last: &[u8], frame: &[u8]) -> usize {
+        let mut delta: Vec<u8> = Vec::new();
+        encode_delta(last, frame, &mut delta);
+        assert_eq!(decode_delta(last, &delta).unwrap(), frame);
+        delta.len()
+    }
+
+    #[test]
+    fn delta_round_trips() {
+        let frame = host_tick(10);
+        assert!(round_trip_delta(&[], &frame) > frame.len());
+        assert!(round_trip_delta(&frame, &frame) < 8);
+        assert!(round_trip_delta(&host_tick(9), &frame) < frame.len() / 2);
+        // moved around and partly rewritten
+        let mut shuffled = frame[frame.len() / 2..].to_vec();
+        shuffled.extend_from_slice(&[1, 2, 3]);
+        shuffled.extend_from_slice(&frame[..frame.len() / 2]);
+        round_trip_delta(&frame, &shuffled);
+        round_trip_delta(&frame, &[]);
+        round_trip_delta(&frame, &frame[..MIN_COPY - 1]);
+    }
+
+    #[test]
+    fn decode_rejects_bad_deltas() {
+        let last = host_tick(1);
+        let mut delta: Vec<u8> = Vec::new();
+        // a copy from past the end of the last frame
+        write_varint(&mut delta, 8 * 2 + 1);
+        write_varint(&mut delta, last.len() as u32);
+        assert!(decode_delta(&last, &delta).is_err());
+        // literals that run off the end
+        assert!(decode_delta(&last, &[10 * 2, 1, 2]).is_err());
+        // a varint that never ends
+        assert!(decode_delta(&last, &[0xFF, 0xFF]).is_err());
+    }
+
+    #[test]
+    fn recorded_match_loads_back() {
+        let mut recorder = ReplayRecorder::default();
+        recorder.record(&host_tick(1)); // not recording yet
+        assert!(!recorder.recording());
+        recorder.start(&world());
+        // a client can get ticks out of order
+        let seqs = [5, 6, 8, 7, 9];
+        for seq in seqs {
+            recorder.record(&host_tick(seq));
+        }
+        let bytes = recorder.bytes.clone().unwrap();
+        assert!(bytes.len() < host_tick(5).len() * seqs.len());
+        let replay = Replay::from_bytes(&bytes).unwrap();
+        let world = replay.world.as_ref().unwrap();
+        assert_eq!((world.seed, world.mode, world.tie_break, world.num_camps), (77, 2, 1, 6));
+        assert_eq!(replay.frames.iter().map(|(seq, _)| *seq).collect::<Vec<u16>>(), vec![5, 6, 7, 8, 9]);
+        for (seq, packet) in &replay.frames {
+            assert_eq!(*packet, host_tick(*seq));
+        }
+        assert_eq!((replay.first_tick(), replay.last_tick()), (5, 9));
+    }
+
+    #[test]
+    fn broken_files_dont_load() {
+        let mut recorder = ReplayRecorder::default();
+        recorder.start(&world());
+        assert!(Replay::from_bytes(recorder.bytes.as_ref().unwrap()).is_err()); // no ticks
+        recorder.record(&host_tick(1));
+        recorder.record(&host_tick(2));
+        let bytes = recorder.bytes.unwrap();
+        assert!(Replay::from_bytes(&bytes).is_ok());
+        for cut in [0, 3, REPLAY_MAGIC.len() + 2, bytes.len() - 1] {
+            assert!(Replay::from_bytes(&bytes[..cut]).is_err(), "loaded a file cut to {} bytes", cut);
+        }
+        let mut wrong_version = bytes.clone();
+        wrong_version[REPLAY_MAGIC.len()] = REPLAY_VERSION + 1;
+        assert!(Replay::from_bytes(&wrong_version).is_err());
+        assert!(Replay::from_bytes(b"not a replay file at all").is_err());
+        // every frame is read through, not just its header
+        let mut recorder = ReplayRecorder::default();
+        recorder.start(&world());
+        recorder.record(&host_tick(1));
+        recorder.record(&host_tick(2)[..40]);
+        assert!(Replay::from_bytes(recorder.bytes.as_ref().unwrap()).is_err());
+    }
+}